@@ -25,6 +25,31 @@ fn set_hip_feature(default_version: &str) {
     }
 }
 
+/// Cfgs set when the selected HIP patch version is at least the given one, so that code can
+/// test for an API rather than list every `hip_*` feature that has it.
+const API_CFGS: &[(&str, u32)] = &[
+    // `hipStreamBatchMemOp` and `hipGraphAddBatchMemOpNode`.
+    ("has_batch_mem_op", 43482),
+    // `hipDrvLaunchKernelEx` and `hipLaunchAttribute`.
+    ("has_launch_attributes", 51831),
+    // `hipMemAllocationProp::requestedHandleType` in a union with `requestedHandleTypes`.
+    ("has_handle_type_union", 51831),
+];
+
+/// Declare the cfgs of `API_CFGS` and set the ones available in `feature`, e.g. `hip_51831`.
+fn set_api_cfgs(feature: &str) {
+    let patch: u32 = feature
+        .strip_prefix("hip_")
+        .and_then(|patch| patch.parse().ok())
+        .unwrap_or_else(|| panic!("Error: unexpected HIP feature '{feature}'"));
+    for (cfg, since) in API_CFGS {
+        println!("cargo::rustc-check-cfg=cfg({cfg})");
+        if patch >= *since {
+            println!("cargo::rustc-cfg={cfg}");
+        }
+    }
+}
+
 fn main() {
     println!("cargo::rerun-if-changed=build.rs");
     println!("cargo::rerun-if-env-changed=ROCM_PATH");
@@ -32,6 +57,7 @@ fn main() {
     let hip_system_patch = get_hip_patch_version();
     if let Ok(ref patch) = hip_system_patch {
         set_hip_feature(patch);
+        set_api_cfgs(&format!("hip_{patch}"));
         println!("cargo::rustc-link-lib=dylib=hiprtc");
         println!("cargo::rustc-link-lib=dylib=amdhip64");
         let lib_path = get_hip_ld_library_path().unwrap();
@@ -47,5 +73,6 @@ fn main() {
             "cargo::warning=Defaulting to the latest feature of HIP bindings available: {feature}"
         );
         println!("cargo:rustc-cfg=feature=\"{feature}\"");
+        set_api_cfgs(&feature);
     }
 }
@@ -37,3 +37,15 @@ pub use bindings_43484::*;
 mod bindings_51831;
 #[cfg(feature = "hip_51831")]
 pub use bindings_51831::*;
+
+/// Return a value of a bindgen struct or union with every byte set to zero, the state the HIP
+/// headers document as "unset" for parameter and descriptor structs.
+///
+/// # Safety
+///
+/// `T` must be plain data for which all zeroes is a valid value: integers, floats, raw
+/// pointers, `Option` of function pointers, and structs, unions and arrays of those. It must
+/// not hold references, non-nullable function pointers or enums without a zero variant.
+pub(crate) unsafe fn zeroed_raw<T>() -> T {
+    std::mem::zeroed()
+}
@@ -0,0 +1,222 @@
+use std::ptr;
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::graph::GraphBuilder;
+use crate::stream::Stream;
+
+/// Interaction of a capture with unsafe calls made by other threads, see
+/// `hipStreamCaptureMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// Unsafe calls are forbidden in every thread while the capture is active.
+    Global,
+    /// Unsafe calls are forbidden in the capturing thread only.
+    ThreadLocal,
+    /// Unsafe calls are allowed.
+    Relaxed,
+}
+
+impl From<CaptureMode> for hipStreamCaptureMode {
+    fn from(mode: CaptureMode) -> Self {
+        match mode {
+            CaptureMode::Global => hipStreamCaptureMode_hipStreamCaptureModeGlobal,
+            CaptureMode::ThreadLocal => hipStreamCaptureMode_hipStreamCaptureModeThreadLocal,
+            CaptureMode::Relaxed => hipStreamCaptureMode_hipStreamCaptureModeRelaxed,
+        }
+    }
+}
+
+/// Capture state of a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureStatus {
+    /// The stream is not capturing.
+    None,
+    /// The stream is capturing.
+    Active,
+    /// The capture failed and must be ended.
+    Invalidated,
+}
+
+impl From<hipStreamCaptureStatus> for CaptureStatus {
+    fn from(status: hipStreamCaptureStatus) -> Self {
+        match status {
+            hipStreamCaptureStatus_hipStreamCaptureStatusActive => CaptureStatus::Active,
+            hipStreamCaptureStatus_hipStreamCaptureStatusInvalidated => CaptureStatus::Invalidated,
+            _ => CaptureStatus::None,
+        }
+    }
+}
+
+/// Capture state of a stream returned by [`Stream::capture_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureInfo {
+    /// Whether the stream is capturing, and whether the capture is still valid.
+    pub status: CaptureStatus,
+    /// Unique id of the capture, only meaningful when the capture is active.
+    pub id: u64,
+    /// Number of nodes the next captured operation will depend on.
+    pub dependency_count: usize,
+}
+
+impl Stream {
+    /// Record the work queued on the stream by `f` into a graph instead of running it, with
+    /// `hipStreamBeginCapture` and `hipStreamEndCapture`.
+    ///
+    /// The capture always ends before this function returns, even when `f` returns an error or
+    /// panics, in which case the partial graph is destroyed.
+    ///
+    /// # Safety
+    ///
+    /// Any device memory and function referenced by the captured work must remain valid as
+    /// long as the graph can be launched.
+    pub unsafe fn capture<'a, F>(&self, mode: CaptureMode, f: F) -> HipResult<GraphBuilder<'a>>
+    where
+        F: FnOnce(&Stream) -> HipResult<()>,
+    {
+        check(hipStreamBeginCapture(self.as_raw(), mode.into()))?;
+        let mut guard = CaptureGuard {
+            stream: self,
+            active: true,
+        };
+        let result = f(self);
+        let graph = guard.end();
+        result?;
+        graph
+    }
+
+    /// Return the capture status of the stream with `hipStreamIsCapturing`.
+    pub fn capture_status(&self) -> HipResult<CaptureStatus> {
+        let mut status = hipStreamCaptureStatus_hipStreamCaptureStatusNone;
+        unsafe { check(hipStreamIsCapturing(self.as_raw(), &mut status))? };
+        Ok(status.into())
+    }
+
+    /// Return true if the stream is capturing.
+    pub fn is_capturing(&self) -> HipResult<bool> {
+        Ok(self.capture_status()? == CaptureStatus::Active)
+    }
+
+    /// Return the capture state of the stream with `hipStreamGetCaptureInfo_v2`.
+    pub fn capture_info(&self) -> HipResult<CaptureInfo> {
+        let mut status = hipStreamCaptureStatus_hipStreamCaptureStatusNone;
+        let mut id = 0;
+        let mut graph: hipGraph_t = ptr::null_mut();
+        let mut dependencies: *const hipGraphNode_t = ptr::null();
+        let mut dependency_count = 0;
+        unsafe {
+            check(hipStreamGetCaptureInfo_v2(
+                self.as_raw(),
+                &mut status,
+                &mut id,
+                &mut graph,
+                &mut dependencies,
+                &mut dependency_count,
+            ))?
+        };
+        Ok(CaptureInfo {
+            status: status.into(),
+            id,
+            dependency_count,
+        })
+    }
+}
+
+/// Ends the capture of a stream when dropped, so that it is never left capturing.
+struct CaptureGuard<'s> {
+    stream: &'s Stream,
+    active: bool,
+}
+
+impl CaptureGuard<'_> {
+    fn end<'a>(&mut self) -> HipResult<GraphBuilder<'a>> {
+        self.active = false;
+        let mut graph: hipGraph_t = ptr::null_mut();
+        let status = unsafe { hipStreamEndCapture(self.stream.as_raw(), &mut graph) };
+        // An invalidated capture may still return a graph to destroy.
+        let graph = (!graph.is_null()).then(|| unsafe { GraphBuilder::from_raw(graph) });
+        check(status)?;
+        graph.ok_or(HipError::InvalidArgument(
+            "stream capture returned no graph".to_string(),
+        ))
+    }
+}
+
+impl Drop for CaptureGuard<'_> {
+    fn drop(&mut self) {
+        if self.active {
+            let _ = self.end();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(CaptureMode::Global, hipStreamCaptureMode_hipStreamCaptureModeGlobal)]
+    #[case(
+        CaptureMode::ThreadLocal,
+        hipStreamCaptureMode_hipStreamCaptureModeThreadLocal
+    )]
+    #[case(CaptureMode::Relaxed, hipStreamCaptureMode_hipStreamCaptureModeRelaxed)]
+    fn test_capture_mode_to_raw(#[case] mode: CaptureMode, #[case] expected: hipStreamCaptureMode) {
+        assert_eq!(hipStreamCaptureMode::from(mode), expected);
+    }
+
+    #[rstest]
+    #[case(hipStreamCaptureStatus_hipStreamCaptureStatusNone, CaptureStatus::None)]
+    #[case(
+        hipStreamCaptureStatus_hipStreamCaptureStatusActive,
+        CaptureStatus::Active
+    )]
+    #[case(
+        hipStreamCaptureStatus_hipStreamCaptureStatusInvalidated,
+        CaptureStatus::Invalidated
+    )]
+    fn test_capture_status_from_raw(
+        #[case] status: hipStreamCaptureStatus,
+        #[case] expected: CaptureStatus,
+    ) {
+        assert_eq!(CaptureStatus::from(status), expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_capture_replay_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let src = crate::DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+        let dst = crate::DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+        let stream = Stream::new().expect("Should create a stream");
+        let graph = unsafe {
+            stream.capture(CaptureMode::ThreadLocal, |stream| {
+                assert!(stream.is_capturing()?);
+                check(hipMemsetD32Async(
+                    src.as_ptr() as hipDeviceptr_t,
+                    0x1234,
+                    src.len(),
+                    stream.as_raw(),
+                ))?;
+                check(hipMemcpyAsync(
+                    dst.as_ptr() as *mut std::ffi::c_void,
+                    src.as_ptr() as *const std::ffi::c_void,
+                    dst.size_in_bytes(),
+                    hipMemcpyKind_hipMemcpyDeviceToDevice,
+                    stream.as_raw(),
+                ))
+            })
+        }
+        .expect("Should capture the stream");
+        assert!(!stream.is_capturing().unwrap());
+        // Captured work is recorded, not run.
+        stream.synchronize().unwrap();
+        assert_eq!(dst.to_vec().unwrap(), vec![0; 64]);
+
+        let exec = graph.instantiate().expect("Should instantiate the graph");
+        exec.launch(&stream).expect("Should replay the graph");
+        stream.synchronize().unwrap();
+        assert_eq!(dst.to_vec().unwrap(), vec![0x1234; 64]);
+    }
+}
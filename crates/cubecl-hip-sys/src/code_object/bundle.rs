@@ -0,0 +1,475 @@
+//! Reader and writer for clang offload bundles (`__CLANG_OFFLOAD_BUNDLE__`).
+//!
+//! Bundles hold one code object per target and are produced by hipcc and hiprtc for
+//! multi-architecture builds. Compressed bundles (`CCOB`) can be read when the
+//! `compression` feature is enabled.
+
+use crate::error::{HipError, HipResult};
+
+/// Magic number of an uncompressed offload bundle.
+pub const OFFLOAD_BUNDLE_MAGIC: &[u8; 24] = b"__CLANG_OFFLOAD_BUNDLE__";
+/// Magic number of a compressed offload bundle.
+pub const COMPRESSED_OFFLOAD_BUNDLE_MAGIC: &[u8; 4] = b"CCOB";
+
+/// Alignment of the code objects in the bundles written by [`OffloadBundle::to_bytes`],
+/// the same as the one used by `clang-offload-bundler` for HIP.
+const BUNDLE_ALIGNMENT: usize = 4096;
+
+/// Offload kind and triple prefixed to the target id of HIP code objects.
+const HIP_ENTRY_PREFIX: &str = "hipv4-amdgcn-amd-amdhsa--";
+
+/// Clang offload bundle holding code objects for several targets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OffloadBundle {
+    /// Entries in bundle order.
+    pub entries: Vec<BundleEntry>,
+}
+
+/// Entry of an [`OffloadBundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleEntry {
+    /// Bundle entry id, for instance `hipv4-amdgcn-amd-amdhsa--gfx90a:xnack-`.
+    pub id: String,
+    /// Content of the entry, usually an AMDGPU code object.
+    pub code: Vec<u8>,
+}
+
+impl BundleEntry {
+    /// Return the offload kind of the entry, for instance `hipv4` or `host`.
+    pub fn kind(&self) -> &str {
+        self.id.split('-').next().unwrap_or_default()
+    }
+
+    /// Return the target triple of the entry, for instance `amdgcn-amd-amdhsa`.
+    pub fn triple(&self) -> &str {
+        let rest = self.id.split_once('-').map_or("", |(_, rest)| rest);
+        rest.split_once("--").map_or(rest, |(triple, _)| triple)
+    }
+
+    /// Return the target id of the entry, for instance `gfx90a:xnack-`, if any.
+    pub fn target_id(&self) -> Option<&str> {
+        self.id
+            .split_once("--")
+            .map(|(_, target)| target)
+            .filter(|target| !target.is_empty())
+    }
+
+    /// Return true if the entry can run on a device with the given `gcnArchName`.
+    ///
+    /// The processors must be equal, and every target feature set in the entry (`xnack+`,
+    /// `sramecc-`, ...) must have the same value on the device. Features absent from the
+    /// entry match any device setting.
+    pub fn is_compatible_with(&self, gcn_arch_name: &str) -> bool {
+        let Some(target) = self.target_id() else {
+            return false;
+        };
+        let mut entry_parts = target.split(':');
+        let mut device_parts = gcn_arch_name.split(':');
+        if entry_parts.next() != device_parts.next() {
+            return false;
+        }
+        let device_features: Vec<&str> = device_parts.collect();
+        entry_parts.all(|feature| {
+            let name = feature.trim_end_matches(['+', '-']);
+            match device_features
+                .iter()
+                .find(|f| f.trim_end_matches(['+', '-']) == name)
+            {
+                Some(device_feature) => *device_feature == feature,
+                None => false,
+            }
+        })
+    }
+}
+
+impl OffloadBundle {
+    /// Create an empty bundle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a bundle of HIP code objects from `(target id, code object)` pairs, where the
+    /// target id is a `gcnArchName` such as `gfx90a` or `gfx942:sramecc+:xnack-`.
+    pub fn from_code_objects<'a, I>(code_objects: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        let mut bundle = Self::new();
+        for (target, code) in code_objects {
+            bundle.push(format!("{HIP_ENTRY_PREFIX}{target}"), code.to_vec());
+        }
+        bundle
+    }
+
+    /// Append an entry with the given bundle entry id.
+    pub fn push(&mut self, id: impl Into<String>, code: Vec<u8>) -> &mut Self {
+        self.entries.push(BundleEntry {
+            id: id.into(),
+            code,
+        });
+        self
+    }
+
+    /// Return true if `bytes` starts with the magic number of a compressed or uncompressed
+    /// offload bundle.
+    pub fn is_bundle(bytes: &[u8]) -> bool {
+        bytes.starts_with(OFFLOAD_BUNDLE_MAGIC)
+            || bytes.starts_with(COMPRESSED_OFFLOAD_BUNDLE_MAGIC)
+    }
+
+    /// Parse a compressed or uncompressed offload bundle.
+    pub fn parse(bytes: &[u8]) -> HipResult<Self> {
+        if bytes.starts_with(COMPRESSED_OFFLOAD_BUNDLE_MAGIC) {
+            let decompressed = decompress(bytes)?;
+            return Self::parse_uncompressed(&decompressed);
+        }
+        Self::parse_uncompressed(bytes)
+    }
+
+    fn parse_uncompressed(bytes: &[u8]) -> HipResult<Self> {
+        if !bytes.starts_with(OFFLOAD_BUNDLE_MAGIC) {
+            return Err(invalid("missing offload bundle magic number"));
+        }
+        let mut pos = OFFLOAD_BUNDLE_MAGIC.len();
+        let count = read_u64(bytes, &mut pos)?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let offset = read_u64(bytes, &mut pos)?;
+            let size = read_u64(bytes, &mut pos)?;
+            let id_size = read_u64(bytes, &mut pos)?;
+            let id = range(bytes, pos as u64, id_size)?;
+            pos += id.len();
+            let id = std::str::from_utf8(id)
+                .map_err(|_| invalid("bundle entry id is not valid UTF-8"))?
+                .to_string();
+            let code = range(bytes, offset, size)?.to_vec();
+            entries.push(BundleEntry { id, code });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Return the ids of the bundled entries.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.id.as_str())
+    }
+
+    /// Return the entry compatible with a device `gcnArchName`, for instance
+    /// `gfx90a:sramecc+:xnack-`.
+    ///
+    /// Entries that set more target features are preferred over generic ones, and the first
+    /// entry wins between equally specific ones.
+    pub fn find(&self, gcn_arch_name: &str) -> Option<&BundleEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.is_compatible_with(gcn_arch_name))
+            .max_by_key(|entry| {
+                entry
+                    .target_id()
+                    .map_or(0, |target| target.matches(':').count())
+            })
+    }
+
+    /// Serialize the bundle in the uncompressed format, each code object aligned to 4096 bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header_size = OFFLOAD_BUNDLE_MAGIC.len()
+            + 8
+            + self
+                .entries
+                .iter()
+                .map(|entry| 24 + entry.id.len())
+                .sum::<usize>();
+        let mut offsets = Vec::with_capacity(self.entries.len());
+        let mut end = header_size;
+        for entry in &self.entries {
+            let offset = if entry.code.is_empty() {
+                end
+            } else {
+                end.next_multiple_of(BUNDLE_ALIGNMENT)
+            };
+            offsets.push(offset);
+            end = offset + entry.code.len();
+        }
+
+        let mut bytes = Vec::with_capacity(end);
+        bytes.extend_from_slice(OFFLOAD_BUNDLE_MAGIC);
+        bytes.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        for (entry, offset) in self.entries.iter().zip(&offsets) {
+            bytes.extend_from_slice(&(*offset as u64).to_le_bytes());
+            bytes.extend_from_slice(&(entry.code.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&(entry.id.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(entry.id.as_bytes());
+        }
+        for (entry, offset) in self.entries.iter().zip(&offsets) {
+            bytes.resize(*offset, 0);
+            bytes.extend_from_slice(&entry.code);
+        }
+        bytes
+    }
+}
+
+/// Header of a compressed offload bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CompressedHeader {
+    method: u16,
+    uncompressed_size: u64,
+    data_offset: usize,
+}
+
+fn compressed_header(bytes: &[u8]) -> HipResult<CompressedHeader> {
+    let mut pos = COMPRESSED_OFFLOAD_BUNDLE_MAGIC.len();
+    let version = read_u16(bytes, &mut pos)?;
+    let method = read_u16(bytes, &mut pos)?;
+    let uncompressed_size = match version {
+        1 => read_u32(bytes, &mut pos)? as u64,
+        2 => {
+            read_u32(bytes, &mut pos)?;
+            read_u32(bytes, &mut pos)? as u64
+        }
+        3 => {
+            read_u64(bytes, &mut pos)?;
+            read_u64(bytes, &mut pos)?
+        }
+        _ => {
+            return Err(invalid(&format!(
+                "unsupported compressed bundle version {version}"
+            )))
+        }
+    };
+    // Truncated MD5 hash of the uncompressed bundle.
+    read_u64(bytes, &mut pos)?;
+    Ok(CompressedHeader {
+        method,
+        uncompressed_size,
+        data_offset: pos,
+    })
+}
+
+#[cfg(feature = "compression")]
+fn decompress(bytes: &[u8]) -> HipResult<Vec<u8>> {
+    use std::io::Read;
+
+    const ZLIB: u16 = 0;
+    const ZSTD: u16 = 1;
+
+    let header = compressed_header(bytes)?;
+    let data = &bytes[header.data_offset..];
+    let decompressed = match header.method {
+        ZLIB => miniz_oxide::inflate::decompress_to_vec_zlib(data)
+            .map_err(|e| invalid(&format!("zlib decompression failed: {e}")))?,
+        ZSTD => {
+            let zstd_error =
+                |e: &dyn std::fmt::Display| invalid(&format!("zstd decompression failed: {e}"));
+            let mut decoder =
+                ruzstd::decoding::StreamingDecoder::new(data).map_err(|e| zstd_error(&e))?;
+            let mut decompressed = Vec::new();
+            decoder
+                .read_to_end(&mut decompressed)
+                .map_err(|e| zstd_error(&e))?;
+            decompressed
+        }
+        method => return Err(invalid(&format!("unsupported compression method {method}"))),
+    };
+    if decompressed.len() as u64 != header.uncompressed_size {
+        return Err(invalid(&format!(
+            "decompressed bundle is {} bytes, expected {}",
+            decompressed.len(),
+            header.uncompressed_size
+        )));
+    }
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(bytes: &[u8]) -> HipResult<Vec<u8>> {
+    compressed_header(bytes)?;
+    Err(invalid(
+        "compressed offload bundles require the 'compression' feature",
+    ))
+}
+
+fn range(bytes: &[u8], offset: u64, size: u64) -> HipResult<&[u8]> {
+    let start = usize::try_from(offset).map_err(|_| invalid("bundle offset overflows"))?;
+    let size = usize::try_from(size).map_err(|_| invalid("bundle size overflows"))?;
+    start
+        .checked_add(size)
+        .and_then(|end| bytes.get(start..end))
+        .ok_or_else(|| invalid("bundle range is out of bounds"))
+}
+
+fn read_array<const N: usize>(bytes: &[u8], pos: &mut usize) -> HipResult<[u8; N]> {
+    let array = range(bytes, *pos as u64, N as u64)?
+        .try_into()
+        .map_err(|_| invalid("truncated offload bundle"))?;
+    *pos += N;
+    Ok(array)
+}
+
+fn read_u16(bytes: &[u8], pos: &mut usize) -> HipResult<u16> {
+    read_array(bytes, pos).map(u16::from_le_bytes)
+}
+
+fn read_u32(bytes: &[u8], pos: &mut usize) -> HipResult<u32> {
+    read_array(bytes, pos).map(u32::from_le_bytes)
+}
+
+fn read_u64(bytes: &[u8], pos: &mut usize) -> HipResult<u64> {
+    read_array(bytes, pos).map(u64::from_le_bytes)
+}
+
+fn invalid(reason: &str) -> HipError {
+    HipError::InvalidCodeObject(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_object::CodeObjectMetadata;
+    use rstest::*;
+
+    const GFX90A: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/kernels_gfx90a.co"
+    ));
+    const GFX1030: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/kernels_gfx1030.co"
+    ));
+
+    fn fixture_bundle() -> OffloadBundle {
+        let mut bundle = OffloadBundle::new();
+        bundle.push("host-x86_64-unknown-linux-gnu-", Vec::new());
+        bundle.push("hipv4-amdgcn-amd-amdhsa--gfx90a", GFX90A.to_vec());
+        bundle.push("hipv4-amdgcn-amd-amdhsa--gfx1030", GFX1030.to_vec());
+        bundle
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bundle = fixture_bundle();
+        let bytes = bundle.to_bytes();
+        assert!(OffloadBundle::is_bundle(&bytes));
+        let parsed = OffloadBundle::parse(&bytes).expect("should parse the bundle");
+        assert_eq!(parsed, bundle);
+        assert_eq!(
+            parsed.ids().collect::<Vec<_>>(),
+            [
+                "host-x86_64-unknown-linux-gnu-",
+                "hipv4-amdgcn-amd-amdhsa--gfx90a",
+                "hipv4-amdgcn-amd-amdhsa--gfx1030",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_code_objects_are_aligned() {
+        let bytes = fixture_bundle().to_bytes();
+        let elf_offsets: Vec<usize> = bytes
+            .windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == b"\x7fELF")
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(elf_offsets.len(), 2);
+        assert!(elf_offsets.iter().all(|o| o % BUNDLE_ALIGNMENT == 0));
+    }
+
+    #[test]
+    fn test_extract_code_object_for_device() {
+        let bytes =
+            OffloadBundle::from_code_objects([("gfx90a", GFX90A), ("gfx1030", GFX1030)]).to_bytes();
+        let bundle = OffloadBundle::parse(&bytes).unwrap();
+        let entry = bundle
+            .find("gfx1030")
+            .expect("should find the gfx1030 entry");
+        assert_eq!(entry.kind(), "hipv4");
+        assert_eq!(entry.triple(), "amdgcn-amd-amdhsa");
+        assert_eq!(entry.target_id(), Some("gfx1030"));
+        let metadata = CodeObjectMetadata::parse(&entry.code).unwrap();
+        assert_eq!(metadata.arch(), Some("gfx1030"));
+        assert!(bundle.find("gfx942:sramecc+:xnack-").is_none());
+    }
+
+    #[rstest]
+    #[case::generic_entry("gfx90a", "gfx90a:sramecc+:xnack-", true)]
+    #[case::same_feature("gfx90a:xnack-", "gfx90a:sramecc+:xnack-", true)]
+    #[case::different_feature("gfx90a:xnack+", "gfx90a:sramecc+:xnack-", false)]
+    #[case::feature_unknown_on_device("gfx90a:xnack+", "gfx90a", false)]
+    #[case::different_processor("gfx90a", "gfx908", false)]
+    #[case::processor_prefix("gfx90", "gfx90a", false)]
+    fn test_is_compatible_with(#[case] target: &str, #[case] device: &str, #[case] expected: bool) {
+        let entry = BundleEntry {
+            id: format!("{HIP_ENTRY_PREFIX}{target}"),
+            code: Vec::new(),
+        };
+        assert_eq!(entry.is_compatible_with(device), expected);
+    }
+
+    #[test]
+    fn test_find_prefers_specific_entry() {
+        let bundle = OffloadBundle::from_code_objects([
+            ("gfx90a", &b"generic"[..]),
+            ("gfx90a:xnack-", &b"specific"[..]),
+        ]);
+        let entry = bundle.find("gfx90a:sramecc+:xnack-").unwrap();
+        assert_eq!(entry.code, b"specific");
+    }
+
+    #[rstest]
+    #[case::bad_magic(b"__CLANG_OFFLOAD_BUNDLX__\x00\x00\x00\x00\x00\x00\x00\x00".to_vec())]
+    #[case::truncated_entry(fixture_bundle().to_bytes()[..40].to_vec())]
+    #[case::unknown_version(b"CCOB\x09\x00\x00\x00\x00\x00\x00\x00".to_vec())]
+    fn test_parse_invalid(#[case] bytes: Vec<u8>) {
+        assert!(OffloadBundle::parse(&bytes).is_err());
+    }
+
+    #[cfg(feature = "compression")]
+    fn compressed(bundle: &[u8], method: u16, version: u16) -> Vec<u8> {
+        let data = match method {
+            0 => miniz_oxide::deflate::compress_to_vec_zlib(bundle, 6),
+            _ => ruzstd::encoding::compress_to_vec(
+                bundle,
+                ruzstd::encoding::CompressionLevel::Fastest,
+            ),
+        };
+        let mut bytes = COMPRESSED_OFFLOAD_BUNDLE_MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&method.to_le_bytes());
+        match version {
+            1 => bytes.extend_from_slice(&(bundle.len() as u32).to_le_bytes()),
+            2 => {
+                bytes.extend_from_slice(&((24 + data.len()) as u32).to_le_bytes());
+                bytes.extend_from_slice(&(bundle.len() as u32).to_le_bytes());
+            }
+            _ => {
+                bytes.extend_from_slice(&((32 + data.len()) as u64).to_le_bytes());
+                bytes.extend_from_slice(&(bundle.len() as u64).to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    #[cfg(feature = "compression")]
+    #[rstest]
+    #[case::zlib_v1(0, 1)]
+    #[case::zlib_v2(0, 2)]
+    #[case::zstd_v2(1, 2)]
+    #[case::zstd_v3(1, 3)]
+    fn test_parse_compressed(#[case] method: u16, #[case] version: u16) {
+        let bundle = fixture_bundle();
+        let bytes = compressed(&bundle.to_bytes(), method, version);
+        assert!(OffloadBundle::is_bundle(&bytes));
+        assert_eq!(OffloadBundle::parse(&bytes).unwrap(), bundle);
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn test_parse_compressed_requires_feature() {
+        let bytes =
+            b"CCOB\x02\x00\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        let err = OffloadBundle::parse(bytes).unwrap_err();
+        assert!(err.to_string().contains("compression"));
+    }
+}
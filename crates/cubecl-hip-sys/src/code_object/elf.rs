@@ -0,0 +1,141 @@
+//! Just enough of an ELF64 reader to extract the notes of an AMDGPU code object.
+
+use crate::error::{HipError, HipResult};
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EM_AMDGPU: u16 = 224;
+const SHT_NOTE: u32 = 7;
+const PT_NOTE: u32 = 4;
+
+/// Note embedded in an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Note<'a> {
+    pub(crate) owner: &'a [u8],
+    pub(crate) kind: u32,
+    pub(crate) desc: &'a [u8],
+}
+
+/// Return true if `bytes` starts with the ELF magic number.
+pub(crate) fn is_elf(bytes: &[u8]) -> bool {
+    bytes.starts_with(ELF_MAGIC)
+}
+
+/// Check that `bytes` is a little-endian ELF64 file for AMDGPU.
+pub(crate) fn check_header(bytes: &[u8]) -> HipResult<()> {
+    if !is_elf(bytes) {
+        return Err(invalid("missing ELF magic number"));
+    }
+    if bytes.len() < 64 {
+        return Err(invalid("truncated ELF header"));
+    }
+    if bytes[4] != ELFCLASS64 || bytes[5] != ELFDATA2LSB {
+        return Err(invalid(
+            "AMDGPU code objects should be little-endian ELF64 files",
+        ));
+    }
+    let machine = read_u16(bytes, 18)?;
+    if machine != EM_AMDGPU {
+        return Err(invalid(&format!(
+            "unexpected ELF machine {machine}, expected EM_AMDGPU ({EM_AMDGPU})"
+        )));
+    }
+    Ok(())
+}
+
+/// Return all the notes of the file, read from the note sections or from the note segments
+/// when there is no note section.
+pub(crate) fn notes(bytes: &[u8]) -> HipResult<Vec<Note<'_>>> {
+    check_header(bytes)?;
+    let mut ranges = note_ranges(bytes, read_u64(bytes, 40)?, read_u16(bytes, 60)?, SHT_NOTE)?;
+    if ranges.is_empty() {
+        ranges = note_ranges(bytes, read_u64(bytes, 32)?, read_u16(bytes, 56)?, PT_NOTE)?;
+    }
+    let mut notes = Vec::new();
+    for (offset, size) in ranges {
+        parse_notes(slice(bytes, offset, size)?, &mut notes)?;
+    }
+    Ok(notes)
+}
+
+/// Return the file ranges of the section headers (`SHT_NOTE`) or program headers (`PT_NOTE`)
+/// of type `kind`.
+fn note_ranges(bytes: &[u8], table: u64, count: u16, kind: u32) -> HipResult<Vec<(u64, u64)>> {
+    let (entry_size, type_offset, offset_offset, size_offset) = if kind == SHT_NOTE {
+        (read_u16(bytes, 58)? as u64, 4, 24, 32)
+    } else {
+        (read_u16(bytes, 54)? as u64, 0, 8, 32)
+    };
+    let mut ranges = Vec::new();
+    for index in 0..count as u64 {
+        let entry = index
+            .checked_mul(entry_size)
+            .and_then(|o| o.checked_add(table))
+            .ok_or_else(|| invalid("ELF header table offset overflows"))?;
+        let entry = to_usize(entry)?;
+        if read_u32(bytes, entry.saturating_add(type_offset))? == kind {
+            ranges.push((
+                read_u64(bytes, entry.saturating_add(offset_offset))?,
+                read_u64(bytes, entry.saturating_add(size_offset))?,
+            ));
+        }
+    }
+    Ok(ranges)
+}
+
+fn parse_notes<'a>(mut data: &'a [u8], notes: &mut Vec<Note<'a>>) -> HipResult<()> {
+    while data.len() >= 12 {
+        let name_size = read_u32(data, 0)? as usize;
+        let desc_size = read_u32(data, 4)? as usize;
+        let kind = read_u32(data, 8)?;
+        let name_start = 12;
+        let desc_start = name_start + name_size.next_multiple_of(4);
+        let next = desc_start + desc_size.next_multiple_of(4);
+        let name = slice(data, name_start as u64, name_size as u64)?;
+        let desc = slice(data, desc_start as u64, desc_size as u64)?;
+        notes.push(Note {
+            owner: name.strip_suffix(&[0]).unwrap_or(name),
+            kind,
+            desc,
+        });
+        data = data.get(next..).unwrap_or_default();
+    }
+    Ok(())
+}
+
+fn slice(bytes: &[u8], offset: u64, size: u64) -> HipResult<&[u8]> {
+    let start = to_usize(offset)?;
+    let end = start
+        .checked_add(to_usize(size)?)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| invalid("ELF range is out of bounds"))?;
+    Ok(&bytes[start..end])
+}
+
+fn to_usize(value: u64) -> HipResult<usize> {
+    usize::try_from(value).map_err(|_| invalid("ELF offset does not fit in usize"))
+}
+
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> HipResult<[u8; N]> {
+    bytes
+        .get(offset..offset.saturating_add(N))
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| invalid("truncated ELF file"))
+}
+
+pub(crate) fn read_u16(bytes: &[u8], offset: usize) -> HipResult<u16> {
+    read_array(bytes, offset).map(u16::from_le_bytes)
+}
+
+pub(crate) fn read_u32(bytes: &[u8], offset: usize) -> HipResult<u32> {
+    read_array(bytes, offset).map(u32::from_le_bytes)
+}
+
+pub(crate) fn read_u64(bytes: &[u8], offset: usize) -> HipResult<u64> {
+    read_array(bytes, offset).map(u64::from_le_bytes)
+}
+
+fn invalid(reason: &str) -> HipError {
+    HipError::InvalidCodeObject(reason.to_string())
+}
@@ -0,0 +1,350 @@
+//! Reader for the metadata of AMDGPU code objects, such as the output of `hiprtcGetCode`.
+//!
+//! The kernel descriptors are decoded from the MessagePack `NT_AMDGPU_METADATA` note
+//! (`amdhsa.kernels`) without any call to the HIP runtime.
+
+mod bundle;
+mod elf;
+mod msgpack;
+
+use crate::error::{HipError, HipResult};
+use crate::function_attributes::LaunchLimits;
+use crate::kernel_args::KernelArgsBuffer;
+use crate::launch::LaunchConfig;
+pub use bundle::*;
+use msgpack::Value;
+
+const NT_AMDGPU_METADATA: u32 = 32;
+
+/// Metadata of an AMDGPU code object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeObjectMetadata {
+    /// Target id of the code object, for instance `amdgcn-amd-amdhsa--gfx90a:xnack-`.
+    pub target: Option<String>,
+    /// Version of the metadata format as `[major, minor]`.
+    pub version: Vec<u64>,
+    /// Kernels defined in the code object.
+    pub kernels: Vec<KernelMetadata>,
+}
+
+/// Metadata of a kernel, from an entry of `amdhsa.kernels`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelMetadata {
+    /// Source name of the kernel.
+    pub name: String,
+    /// Symbol of the kernel descriptor, usually `<name>.kd`.
+    pub symbol: String,
+    /// Explicit and hidden kernel arguments in kernarg segment order.
+    pub args: Vec<KernelArgMetadata>,
+    /// Size of the kernarg segment in bytes.
+    pub kernarg_segment_size: u64,
+    /// Alignment of the kernarg segment in bytes.
+    pub kernarg_segment_align: u64,
+    /// Statically allocated LDS (group segment) size in bytes.
+    pub lds_size: u64,
+    /// Scratch (private segment) size per work-item in bytes.
+    pub scratch_size: u64,
+    /// Number of scalar registers used by a wavefront.
+    pub sgpr_count: u32,
+    /// Number of vector registers used by a work-item.
+    pub vgpr_count: u32,
+    /// Number of accumulation registers used by a work-item, on targets that have them.
+    pub agpr_count: Option<u32>,
+    /// Number of scalar registers spilled to memory.
+    pub sgpr_spill_count: u32,
+    /// Number of vector registers spilled to memory.
+    pub vgpr_spill_count: u32,
+    /// Number of work-items in a wavefront, 32 or 64.
+    pub wavefront_size: u32,
+    /// Maximum number of work-items in a work-group.
+    pub max_flat_workgroup_size: u32,
+}
+
+/// Metadata of a kernel argument, from an entry of `.args`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelArgMetadata {
+    /// Source name of the argument, if known.
+    pub name: Option<String>,
+    /// Source type of the argument, if known.
+    pub type_name: Option<String>,
+    /// Offset of the argument in the kernarg segment in bytes.
+    pub offset: u64,
+    /// Size of the argument in bytes.
+    pub size: u64,
+    /// Kind of the argument, for instance `by_value`, `global_buffer` or `hidden_global_offset_x`.
+    pub value_kind: String,
+    /// Address space of pointer arguments, for instance `global`.
+    pub address_space: Option<String>,
+}
+
+impl KernelArgMetadata {
+    /// Return true if the argument is added by the compiler instead of declared by the kernel.
+    pub fn is_hidden(&self) -> bool {
+        self.value_kind.starts_with("hidden_")
+    }
+}
+
+impl CodeObjectMetadata {
+    /// Decode the metadata of an AMDGPU ELF code object.
+    pub fn parse(code: &[u8]) -> HipResult<Self> {
+        let note = elf::notes(code)?
+            .into_iter()
+            .find(|note| note.owner == b"AMDGPU" && note.kind == NT_AMDGPU_METADATA)
+            .ok_or_else(|| {
+                invalid("missing NT_AMDGPU_METADATA note, code objects before v3 are not supported")
+            })?;
+        let root = msgpack::decode(note.desc)?;
+        let kernels = root
+            .get("amdhsa.kernels")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("missing amdhsa.kernels in metadata"))?
+            .iter()
+            .map(KernelMetadata::from_value)
+            .collect::<HipResult<Vec<_>>>()?;
+        let version = root
+            .get("amdhsa.version")
+            .and_then(Value::as_array)
+            .map(|items| items.iter().filter_map(Value::as_u64).collect())
+            .unwrap_or_default();
+        Ok(Self {
+            target: optional_str(&root, "amdhsa.target"),
+            version,
+            kernels,
+        })
+    }
+
+    /// Return the processor of the target id, for instance `gfx90a`.
+    pub fn arch(&self) -> Option<&str> {
+        let target = self.target.as_deref()?;
+        let processor = target.rsplit_once("--").map_or(target, |(_, p)| p);
+        processor.split(':').next()
+    }
+
+    /// Return the metadata of the kernel named `name`.
+    pub fn kernel(&self, name: &str) -> Option<&KernelMetadata> {
+        self.kernels.iter().find(|kernel| kernel.name == name)
+    }
+}
+
+impl KernelMetadata {
+    fn from_value(value: &Value) -> HipResult<Self> {
+        let name = optional_str(value, ".name").ok_or_else(|| invalid("kernel without .name"))?;
+        let args = value
+            .get(".args")
+            .and_then(Value::as_array)
+            .unwrap_or_default()
+            .iter()
+            .map(|arg| KernelArgMetadata::from_value(arg, &name))
+            .collect::<HipResult<Vec<_>>>()?;
+        Ok(Self {
+            symbol: optional_str(value, ".symbol").unwrap_or_else(|| format!("{name}.kd")),
+            args,
+            kernarg_segment_size: required(value, ".kernarg_segment_size", &name)?,
+            kernarg_segment_align: required(value, ".kernarg_segment_align", &name)?,
+            lds_size: required(value, ".group_segment_fixed_size", &name)?,
+            scratch_size: required(value, ".private_segment_fixed_size", &name)?,
+            sgpr_count: required_u32(value, ".sgpr_count", &name)?,
+            vgpr_count: required_u32(value, ".vgpr_count", &name)?,
+            agpr_count: optional(value, ".agpr_count").map(|n| n as u32),
+            sgpr_spill_count: optional(value, ".sgpr_spill_count").unwrap_or(0) as u32,
+            vgpr_spill_count: optional(value, ".vgpr_spill_count").unwrap_or(0) as u32,
+            wavefront_size: required_u32(value, ".wavefront_size", &name)?,
+            max_flat_workgroup_size: required_u32(value, ".max_flat_workgroup_size", &name)?,
+            name,
+        })
+    }
+
+    /// Return the arguments declared in the kernel source, without the hidden ones.
+    pub fn explicit_args(&self) -> impl Iterator<Item = &KernelArgMetadata> {
+        self.args.iter().filter(|arg| !arg.is_hidden())
+    }
+
+    /// Return the limits a launch of the kernel must fit, known without loading it.
+    pub fn launch_limits(&self) -> LaunchLimits {
+        LaunchLimits {
+            max_threads_per_block: self.max_flat_workgroup_size,
+            max_dynamic_shared_size_bytes: None,
+        }
+    }
+
+    /// Check that the block of `config` fits in the maximum work-group size of the kernel,
+    /// see [`LaunchLimits::check`].
+    pub fn check_launch(&self, config: &LaunchConfig) -> HipResult<()> {
+        self.launch_limits().check_kernel(&self.name, config)
+    }
+
+    /// Check that `buffer` has the same explicit argument offsets as the kernel.
+    pub fn check_args(&self, buffer: &KernelArgsBuffer) -> HipResult<()> {
+        let expected: Vec<u64> = self.explicit_args().map(|arg| arg.offset).collect();
+        let actual: Vec<u64> = buffer.offsets().iter().map(|o| *o as u64).collect();
+        if expected != actual {
+            return Err(HipError::InvalidArgument(format!(
+                "kernel '{}' expects arguments at offsets {expected:?}, got {actual:?}",
+                self.name
+            )));
+        }
+        let end = self
+            .explicit_args()
+            .map(|arg| arg.offset + arg.size)
+            .max()
+            .unwrap_or(0);
+        if (buffer.len() as u64) < end {
+            return Err(HipError::InvalidArgument(format!(
+                "kernel '{}' expects {end} bytes of arguments, got {}",
+                self.name,
+                buffer.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl KernelArgMetadata {
+    fn from_value(value: &Value, kernel: &str) -> HipResult<Self> {
+        Ok(Self {
+            name: optional_str(value, ".name"),
+            type_name: optional_str(value, ".type_name"),
+            offset: required(value, ".offset", kernel)?,
+            size: required(value, ".size", kernel)?,
+            value_kind: optional_str(value, ".value_kind")
+                .ok_or_else(|| invalid(&format!("argument of '{kernel}' without .value_kind")))?,
+            address_space: optional_str(value, ".address_space"),
+        })
+    }
+}
+
+fn optional(value: &Value, key: &str) -> Option<u64> {
+    value.get(key).and_then(Value::as_u64)
+}
+
+fn optional_str(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+fn required(value: &Value, key: &str, kernel: &str) -> HipResult<u64> {
+    optional(value, key).ok_or_else(|| invalid(&format!("kernel '{kernel}' without {key}")))
+}
+
+fn required_u32(value: &Value, key: &str, kernel: &str) -> HipResult<u32> {
+    u32::try_from(required(value, key, kernel)?)
+        .map_err(|_| invalid(&format!("{key} of kernel '{kernel}' does not fit in u32")))
+}
+
+fn invalid(reason: &str) -> HipError {
+    HipError::InvalidCodeObject(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const GFX90A: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/kernels_gfx90a.co"
+    ));
+    const GFX1030: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/kernels_gfx1030.co"
+    ));
+
+    #[rstest]
+    #[case::gfx90a(GFX90A, "gfx90a", 64)]
+    #[case::gfx1030(GFX1030, "gfx1030", 32)]
+    fn test_parse_fixture(#[case] code: &[u8], #[case] arch: &str, #[case] wavefront: u32) {
+        let metadata = CodeObjectMetadata::parse(code).expect("should parse the fixture");
+        assert_eq!(metadata.arch(), Some(arch));
+        assert_eq!(metadata.version, vec![1, 1]);
+        let names: Vec<&str> = metadata.kernels.iter().map(|k| k.name.as_str()).collect();
+        assert_eq!(names, ["axpb", "reduce_tile", "scratch_walk"]);
+        for kernel in &metadata.kernels {
+            assert_eq!(kernel.wavefront_size, wavefront);
+            assert_eq!(kernel.max_flat_workgroup_size, 256);
+            assert_eq!(kernel.symbol, format!("{}.kd", kernel.name));
+            assert!(kernel.vgpr_count > 0);
+            assert!(kernel.sgpr_count > 0);
+        }
+    }
+
+    #[test]
+    fn test_kernel_args_layout() {
+        let metadata = CodeObjectMetadata::parse(GFX90A).unwrap();
+        let kernel = metadata.kernel("axpb").expect("should find the kernel");
+        let layout: Vec<(Option<&str>, u64, u64, &str)> = kernel
+            .explicit_args()
+            .map(|arg| {
+                (
+                    arg.name.as_deref(),
+                    arg.offset,
+                    arg.size,
+                    arg.value_kind.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                (Some("a"), 0, 4, "by_value"),
+                (Some("x"), 8, 8, "global_buffer"),
+                (Some("b"), 16, 8, "global_buffer"),
+                (Some("out"), 24, 8, "global_buffer"),
+                (Some("n"), 32, 4, "by_value"),
+            ]
+        );
+        assert_eq!(kernel.kernarg_segment_size, 36);
+        assert_eq!(kernel.args[1].address_space.as_deref(), Some("global"));
+    }
+
+    #[test]
+    fn test_lds_and_scratch_sizes() {
+        let metadata = CodeObjectMetadata::parse(GFX1030).unwrap();
+        assert_eq!(metadata.kernel("reduce_tile").unwrap().lds_size, 1024);
+        assert_eq!(metadata.kernel("axpb").unwrap().scratch_size, 0);
+        assert!(metadata.kernel("scratch_walk").unwrap().scratch_size >= 256);
+        assert!(metadata.kernel("missing").is_none());
+    }
+
+    #[test]
+    fn test_check_args_against_buffer() {
+        let metadata = CodeObjectMetadata::parse(GFX90A).unwrap();
+        let kernel = metadata.kernel("axpb").unwrap();
+        let mut buffer = KernelArgsBuffer::new();
+        buffer
+            .push(2.0f32)
+            .push_device_ptr(std::ptr::null::<f32>())
+            .push_device_ptr(std::ptr::null::<f32>())
+            .push_device_ptr(std::ptr::null::<f32>());
+        assert!(kernel.check_args(&buffer).is_err(), "missing argument n");
+        buffer.push(16i32);
+        assert!(kernel.check_args(&buffer).is_ok());
+    }
+
+    #[rstest]
+    #[case::fits((256, 1, 1), true)]
+    #[case::fits_2d((16, 16, 1), true)]
+    #[case::too_large((512, 1, 1), false)]
+    fn test_check_launch(#[case] block: (u32, u32, u32), #[case] valid: bool) {
+        let metadata = CodeObjectMetadata::parse(GFX90A).unwrap();
+        let kernel = metadata.kernel("axpb").unwrap();
+        let config = LaunchConfig::new(1, block);
+        assert_eq!(kernel.check_launch(&config).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case::empty(&[])]
+    #[case::not_elf(b"not an ELF file at all, just some text that is long enough..........")]
+    #[case::truncated(&GFX90A[..128])]
+    fn test_parse_invalid(#[case] code: &[u8]) {
+        assert!(CodeObjectMetadata::parse(code).is_err());
+    }
+
+    #[test]
+    fn test_arch_from_target_id() {
+        let metadata = CodeObjectMetadata {
+            target: Some("amdgcn-amd-amdhsa--gfx942:sramecc+:xnack-".to_string()),
+            version: vec![1, 2],
+            kernels: Vec::new(),
+        };
+        assert_eq!(metadata.arch(), Some("gfx942"));
+    }
+}
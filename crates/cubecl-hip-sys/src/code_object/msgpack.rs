@@ -0,0 +1,213 @@
+//! Minimal MessagePack decoder for the AMDGPU metadata note.
+
+use crate::error::{HipError, HipResult};
+
+/// Maximum nesting of arrays and maps, the AMDGPU metadata only uses a few levels.
+const MAX_DEPTH: usize = 32;
+
+/// Decoded MessagePack value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Nil,
+    Bool(bool),
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bin(Vec<u8>),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Ext(i8, Vec<u8>),
+}
+
+impl Value {
+    /// Return the value of `key` if this value is a map with string keys.
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::UInt(n) => Some(*n),
+            Value::Int(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Decode a single value from `bytes`, trailing bytes are ignored.
+pub(crate) fn decode(bytes: &[u8]) -> HipResult<Value> {
+    let mut reader = Reader { bytes, pos: 0 };
+    reader.value(0)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> HipResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("truncated MessagePack data"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn uint(&mut self, len: usize) -> HipResult<u64> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | *byte as u64))
+    }
+
+    fn int(&mut self, len: usize) -> HipResult<i64> {
+        let raw = self.uint(len)?;
+        let shift = 64 - 8 * len as u32;
+        Ok(((raw << shift) as i64) >> shift)
+    }
+
+    fn str(&mut self, len: usize) -> HipResult<Value> {
+        let bytes = self.take(len)?;
+        let s = std::str::from_utf8(bytes).map_err(|_| invalid("string is not valid UTF-8"))?;
+        Ok(Value::Str(s.to_string()))
+    }
+
+    fn array(&mut self, len: usize, depth: usize) -> HipResult<Value> {
+        let mut items = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> HipResult<Value> {
+        let mut entries = Vec::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            let key = self.value(depth + 1)?;
+            let value = self.value(depth + 1)?;
+            entries.push((key, value));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    fn ext(&mut self, len: usize) -> HipResult<Value> {
+        let tag = self.int(1)? as i8;
+        Ok(Value::Ext(tag, self.take(len)?.to_vec()))
+    }
+
+    fn value(&mut self, depth: usize) -> HipResult<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("MessagePack data is nested too deeply"));
+        }
+        let marker = self.take(1)?[0];
+        match marker {
+            0x00..=0x7f => Ok(Value::UInt(marker as u64)),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth),
+            0x90..=0x9f => self.array((marker & 0x0f) as usize, depth),
+            0xa0..=0xbf => self.str((marker & 0x1f) as usize),
+            0xc0 => Ok(Value::Nil),
+            0xc2 => Ok(Value::Bool(false)),
+            0xc3 => Ok(Value::Bool(true)),
+            0xc4..=0xc6 => {
+                let len = self.uint(1 << (marker - 0xc4))? as usize;
+                Ok(Value::Bin(self.take(len)?.to_vec()))
+            }
+            0xc7..=0xc9 => {
+                let len = self.uint(1 << (marker - 0xc7))? as usize;
+                self.ext(len)
+            }
+            0xca => Ok(Value::Float(f32::from_bits(self.uint(4)? as u32) as f64)),
+            0xcb => Ok(Value::Float(f64::from_bits(self.uint(8)?))),
+            0xcc..=0xcf => Ok(Value::UInt(self.uint(1 << (marker - 0xcc))?)),
+            0xd0..=0xd3 => Ok(Value::Int(self.int(1 << (marker - 0xd0))?)),
+            0xd4..=0xd8 => self.ext(1 << (marker - 0xd4)),
+            0xd9..=0xdb => {
+                let len = self.uint(1 << (marker - 0xd9))? as usize;
+                self.str(len)
+            }
+            0xdc | 0xdd => {
+                let len = self.uint(2 << (marker - 0xdc))? as usize;
+                self.array(len, depth)
+            }
+            0xde | 0xdf => {
+                let len = self.uint(2 << (marker - 0xde))? as usize;
+                self.map(len, depth)
+            }
+            0xe0..=0xff => Ok(Value::Int(marker as i8 as i64)),
+            0xc1 => Err(invalid("reserved MessagePack marker 0xc1")),
+        }
+    }
+}
+
+fn invalid(reason: &str) -> HipError {
+    HipError::InvalidCodeObject(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::positive_fixint(&[0x2a], Value::UInt(42))]
+    #[case::negative_fixint(&[0xff], Value::Int(-1))]
+    #[case::uint16(&[0xcd, 0x01, 0x00], Value::UInt(256))]
+    #[case::int32(&[0xd2, 0xff, 0xff, 0xff, 0xfe], Value::Int(-2))]
+    #[case::float64(&[0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0], Value::Float(1.5))]
+    #[case::fixstr(&[0xa3, b'a', b'b', b'c'], Value::Str("abc".to_string()))]
+    #[case::str8(&[0xd9, 0x01, b'x'], Value::Str("x".to_string()))]
+    #[case::bool(&[0xc3], Value::Bool(true))]
+    #[case::nil(&[0xc0], Value::Nil)]
+    #[case::array16(&[0xdc, 0x00, 0x02, 0x01, 0x02], Value::Array(vec![Value::UInt(1), Value::UInt(2)]))]
+    fn test_decode(#[case] bytes: &[u8], #[case] expected: Value) {
+        assert_eq!(decode(bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_decode_map_lookup() {
+        // {"a": 1, "b": [true]}
+        let bytes = [0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x91, 0xc3];
+        let value = decode(&bytes).unwrap();
+        assert_eq!(value.get("a").and_then(Value::as_u64), Some(1));
+        assert_eq!(
+            value.get("b").and_then(Value::as_array),
+            Some(&[Value::Bool(true)][..])
+        );
+        assert!(value.get("c").is_none());
+    }
+
+    #[rstest]
+    #[case::truncated_str(&[0xa3, b'a'])]
+    #[case::truncated_array(&[0x92, 0x01])]
+    #[case::reserved(&[0xc1])]
+    #[case::empty(&[])]
+    #[case::too_deep(&[0x91; 64])]
+    fn test_decode_invalid(#[case] bytes: &[u8]) {
+        assert!(decode(bytes).is_err());
+    }
+}
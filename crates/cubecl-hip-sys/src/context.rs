@@ -0,0 +1,211 @@
+use std::marker::PhantomData;
+use std::ptr;
+
+use crate::bindings::*;
+use crate::error::{check, HipResult};
+
+/// How a host thread waits for the device, from the `hipDeviceSchedule*` flags of a context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleMode {
+    /// Let the runtime choose from the number of active contexts and processors.
+    Auto,
+    /// Spin while waiting, for the lowest latency.
+    Spin,
+    /// Yield the thread while waiting.
+    Yield,
+    /// Block the thread on a synchronization primitive while waiting.
+    BlockingSync,
+}
+
+impl ScheduleMode {
+    fn from_flags(flags: u32) -> Self {
+        match flags & hipDeviceScheduleMask {
+            hipDeviceScheduleSpin => ScheduleMode::Spin,
+            hipDeviceScheduleYield => ScheduleMode::Yield,
+            hipDeviceScheduleBlockingSync => ScheduleMode::BlockingSync,
+            _ => ScheduleMode::Auto,
+        }
+    }
+
+    /// Return the `hipDeviceSchedule*` flag of the mode.
+    pub fn to_flags(self) -> u32 {
+        match self {
+            ScheduleMode::Auto => hipDeviceScheduleAuto,
+            ScheduleMode::Spin => hipDeviceScheduleSpin,
+            ScheduleMode::Yield => hipDeviceScheduleYield,
+            ScheduleMode::BlockingSync => hipDeviceScheduleBlockingSync,
+        }
+    }
+}
+
+/// State of the primary context of a device, from `hipDevicePrimaryCtxGetState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimaryContextState {
+    /// Flags of the context, a combination of `hipDeviceSchedule*`, `hipDeviceMapHost` and
+    /// `hipDeviceLmemResizeToMax`.
+    pub flags: u32,
+    /// Whether the context is retained by anyone.
+    pub active: bool,
+}
+
+impl PrimaryContextState {
+    /// Return the schedule mode set in the flags.
+    pub fn schedule(&self) -> ScheduleMode {
+        ScheduleMode::from_flags(self.flags)
+    }
+}
+
+/// Primary context of a device retained with `hipDevicePrimaryCtxRetain`, released on drop.
+///
+/// The primary context is shared with the runtime API and every other retainer, it is
+/// destroyed once the last one releases it.
+#[derive(Debug)]
+pub struct PrimaryContext {
+    raw: hipCtx_t,
+    device: i32,
+}
+
+impl PrimaryContext {
+    /// Retain the primary context of `device`, creating it if it is not active.
+    pub fn retain(device: i32) -> HipResult<Self> {
+        let mut raw: hipCtx_t = ptr::null_mut();
+        unsafe { check(hipDevicePrimaryCtxRetain(&mut raw, device))? };
+        Ok(Self { raw, device })
+    }
+
+    /// Return the state of the primary context of `device`, whether it is retained or not.
+    pub fn state(device: i32) -> HipResult<PrimaryContextState> {
+        let mut flags = 0;
+        let mut active = 0;
+        unsafe { check(hipDevicePrimaryCtxGetState(device, &mut flags, &mut active))? };
+        Ok(PrimaryContextState {
+            flags,
+            active: active != 0,
+        })
+    }
+
+    /// Set the flags the primary context of `device` is created with, with
+    /// `hipDevicePrimaryCtxSetFlags`.
+    ///
+    /// Fails with `hipErrorContextAlreadyInUse` while the context is active.
+    pub fn set_flags(device: i32, flags: u32) -> HipResult<()> {
+        unsafe { check(hipDevicePrimaryCtxSetFlags(device, flags)) }
+    }
+
+    /// Make the context current on the calling thread until the returned guard is dropped.
+    pub fn push_current(&self) -> HipResult<CurrentContext<'_>> {
+        CurrentContext::push(self)
+    }
+
+    /// Return the underlying `hipCtx_t`.
+    pub fn as_raw(&self) -> hipCtx_t {
+        self.raw
+    }
+
+    /// Return the device of the context.
+    pub fn device(&self) -> i32 {
+        self.device
+    }
+}
+
+impl Drop for PrimaryContext {
+    fn drop(&mut self) {
+        unsafe {
+            hipDevicePrimaryCtxRelease(self.device);
+        }
+    }
+}
+
+/// Context pushed on the context stack of the calling thread with `hipCtxPushCurrent`,
+/// popped with `hipCtxPopCurrent` on drop.
+///
+/// Guards should be dropped in the reverse order they were created, which scopes ensure and
+/// debug builds check.
+#[derive(Debug)]
+pub struct CurrentContext<'a> {
+    raw: hipCtx_t,
+    _context: PhantomData<&'a PrimaryContext>,
+}
+
+impl<'a> CurrentContext<'a> {
+    /// Push `context` on the context stack of the calling thread.
+    pub fn push(context: &'a PrimaryContext) -> HipResult<Self> {
+        unsafe { check(hipCtxPushCurrent(context.raw))? };
+        Ok(Self {
+            raw: context.raw,
+            _context: PhantomData,
+        })
+    }
+
+    /// Return the underlying `hipCtx_t`.
+    pub fn as_raw(&self) -> hipCtx_t {
+        self.raw
+    }
+}
+
+impl Drop for CurrentContext<'_> {
+    fn drop(&mut self) {
+        let mut popped: hipCtx_t = ptr::null_mut();
+        let status = unsafe { hipCtxPopCurrent(&mut popped) };
+        if status == hipError_t_hipSuccess {
+            debug_assert_eq!(
+                popped, self.raw,
+                "current context guards should be dropped in reverse order"
+            );
+        }
+    }
+}
+
+/// Return the context current on the calling thread with `hipCtxGetCurrent`, `None` if
+/// there is none.
+pub fn current_context() -> HipResult<Option<hipCtx_t>> {
+    let mut raw: hipCtx_t = ptr::null_mut();
+    unsafe { check(hipCtxGetCurrent(&mut raw))? };
+    Ok((!raw.is_null()).then_some(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(ScheduleMode::Auto)]
+    #[case(ScheduleMode::Spin)]
+    #[case(ScheduleMode::Yield)]
+    #[case(ScheduleMode::BlockingSync)]
+    fn test_schedule_mode_flags_roundtrip(#[case] mode: ScheduleMode) {
+        assert_eq!(ScheduleMode::from_flags(mode.to_flags()), mode);
+    }
+
+    #[test]
+    fn test_state_schedule_ignores_other_flags() {
+        let state = PrimaryContextState {
+            flags: hipDeviceScheduleYield | hipDeviceMapHost,
+            active: true,
+        };
+        assert_eq!(state.schedule(), ScheduleMode::Yield);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_push_current_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let previous = current_context().expect("Should query the current context");
+        let context = PrimaryContext::retain(0).expect("Should retain the primary context");
+        assert!(PrimaryContext::state(0).unwrap().active);
+        {
+            let current = context.push_current().expect("Should push the context");
+            assert_eq!(current.as_raw(), context.as_raw());
+            assert_eq!(current_context().unwrap(), Some(context.as_raw()));
+            {
+                let _nested = context
+                    .push_current()
+                    .expect("Should push the context again");
+                assert_eq!(current_context().unwrap(), Some(context.as_raw()));
+            }
+            assert_eq!(current_context().unwrap(), Some(context.as_raw()));
+        }
+        assert_eq!(current_context().unwrap(), previous);
+    }
+}
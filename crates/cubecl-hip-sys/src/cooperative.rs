@@ -0,0 +1,328 @@
+use std::ffi::c_void;
+
+use crate::bindings::*;
+use crate::device::{current_device, device_attribute, DeviceGuard};
+use crate::error::{check, HipError, HipResult};
+use crate::launch::{KernelArgs, LaunchConfig};
+use crate::module::Function;
+
+/// Launch of a kernel on one device of a multi-device launch.
+pub struct LaunchParams<'a, A: ?Sized> {
+    /// Kernel to launch, loaded on the device of the stream.
+    pub function: &'a Function<'a>,
+    /// Launch configuration, its stream selects the device.
+    pub config: LaunchConfig<'a>,
+    /// Arguments of the kernel, matching its signature.
+    pub args: &'a A,
+}
+
+impl<'a, A: KernelArgs + ?Sized> LaunchParams<'a, A> {
+    pub fn new(function: &'a Function<'a>, config: LaunchConfig<'a>, args: &'a A) -> Self {
+        Self {
+            function,
+            config,
+            args,
+        }
+    }
+}
+
+/// Launch of a kernel registered by a hipcc host stub on one device of a multi-device launch.
+pub struct HostLaunchParams<'a, A: ?Sized> {
+    /// Address of the host stub of the kernel.
+    pub function: *const c_void,
+    /// Launch configuration, its stream selects the device.
+    pub config: LaunchConfig<'a>,
+    /// Arguments of the kernel, matching its signature.
+    pub args: &'a A,
+}
+
+impl<'a, A: KernelArgs + ?Sized> HostLaunchParams<'a, A> {
+    pub fn new(function: *const c_void, config: LaunchConfig<'a>, args: &'a A) -> Self {
+        Self {
+            function,
+            config,
+            args,
+        }
+    }
+}
+
+/// Return the device of the stream of a launch, which must be set.
+fn stream_device(config: &LaunchConfig, index: usize) -> HipResult<i32> {
+    match config.stream {
+        Some(stream) => stream.device(),
+        None => Err(HipError::InvalidArgument(format!(
+            "launch {index} of a multi-device launch has no stream"
+        ))),
+    }
+}
+
+impl Function<'_> {
+    /// Launch the kernel with `hipModuleLaunchCooperativeKernel`, so that the blocks of the
+    /// grid can synchronize with grid-wide barriers.
+    ///
+    /// The device must support `hipDeviceAttributeCooperativeLaunch`, and the whole grid
+    /// must be resident at once according to the occupancy of the kernel.
+    ///
+    /// # Safety
+    ///
+    /// The arguments must match the kernel signature in number, order and layout, and any
+    /// device memory they reference must remain valid until the kernel completes.
+    pub unsafe fn launch_cooperative<A: KernelArgs + ?Sized>(
+        &self,
+        config: &LaunchConfig,
+        args: &A,
+    ) -> HipResult<()> {
+        self.validate_launch(config)?;
+        let device = match config.stream {
+            Some(stream) => stream.device()?,
+            None => current_device()?,
+        };
+        check_cooperative(
+            self,
+            config,
+            device,
+            hipDeviceAttribute_t_hipDeviceAttributeCooperativeLaunch,
+        )?;
+        let mut params = args.as_kernel_params();
+        check(hipModuleLaunchCooperativeKernel(
+            self.as_raw(),
+            config.grid.x,
+            config.grid.y,
+            config.grid.z,
+            config.block.x,
+            config.block.y,
+            config.block.z,
+            config.shared_mem,
+            config.raw_stream(),
+            params.as_mut_ptr(),
+        ))
+    }
+}
+
+/// Launch one kernel per device with `hipModuleLaunchCooperativeKernelMultiDevice`, so that
+/// the blocks of all the grids can synchronize with multi-grid barriers.
+///
+/// Each launch must be on a stream of a different device supporting
+/// `hipDeviceAttributeCooperativeMultiDeviceLaunch`, with a grid that is resident at once.
+/// `flags` combines `hipCooperativeLaunchMultiDeviceNoPreSync` and
+/// `hipCooperativeLaunchMultiDeviceNoPostSync`.
+///
+/// `hipLaunchCooperativeKernelMultiDevice` is the same launch for kernels registered by
+/// hipcc host stubs, which modules do not have.
+///
+/// # Safety
+///
+/// The arguments of each launch must match its kernel signature in number, order and
+/// layout, and any device memory they reference must remain valid until the kernels complete.
+pub unsafe fn launch_cooperative_multi_device<A: KernelArgs + ?Sized>(
+    launches: &[LaunchParams<'_, A>],
+    flags: u32,
+) -> HipResult<()> {
+    let devices = launch_devices(launches)?;
+    for (launch, device) in launches.iter().zip(&devices) {
+        check_cooperative(
+            launch.function,
+            &launch.config,
+            *device,
+            hipDeviceAttribute_t_hipDeviceAttributeCooperativeMultiDeviceLaunch,
+        )?;
+    }
+    // The parameter arrays must outlive the launch call.
+    let mut kernel_params: Vec<_> = launches
+        .iter()
+        .map(|launch| launch.args.as_kernel_params())
+        .collect();
+    let mut raw: Vec<_> = launches
+        .iter()
+        .zip(&mut kernel_params)
+        .map(|(launch, params)| hipFunctionLaunchParams {
+            function: launch.function.as_raw(),
+            gridDimX: launch.config.grid.x,
+            gridDimY: launch.config.grid.y,
+            gridDimZ: launch.config.grid.z,
+            blockDimX: launch.config.block.x,
+            blockDimY: launch.config.block.y,
+            blockDimZ: launch.config.block.z,
+            sharedMemBytes: launch.config.shared_mem,
+            hStream: launch.config.raw_stream(),
+            kernelParams: params.as_mut_ptr(),
+        })
+        .collect();
+    check(hipModuleLaunchCooperativeKernelMultiDevice(
+        raw.as_mut_ptr(),
+        raw.len() as u32,
+        flags,
+    ))
+}
+
+/// Launch one kernel per device, each on its own stream, without grid-wide synchronization.
+///
+/// This is the module counterpart of [`launch_host_multi_device`], which only accepts kernels
+/// registered by hipcc host stubs. Every launch is validated before the first one is queued.
+///
+/// # Safety
+///
+/// The arguments of each launch must match its kernel signature in number, order and
+/// layout, and any device memory they reference must remain valid until the kernels complete.
+pub unsafe fn launch_multi_device<A: KernelArgs + ?Sized>(
+    launches: &[LaunchParams<'_, A>],
+) -> HipResult<()> {
+    launch_devices(launches)?;
+    for launch in launches {
+        launch.function.launch(&launch.config, launch.args)?;
+    }
+    Ok(())
+}
+
+/// Launch one kernel registered by a hipcc host stub per device, each on its own stream,
+/// without grid-wide synchronization, with `hipExtLaunchMultiKernelMultiDevice`.
+///
+/// `flags` combines `hipCooperativeLaunchMultiDeviceNoPreSync` and
+/// `hipCooperativeLaunchMultiDeviceNoPostSync`.
+///
+/// # Safety
+///
+/// Each `function` must be the host stub of a kernel registered in this process, for instance
+/// a `__global__` function compiled by hipcc and linked into the program. The arguments of
+/// each launch must match its kernel signature in number, order and layout, and any device
+/// memory they reference must remain valid until the kernels complete.
+pub unsafe fn launch_host_multi_device<A: KernelArgs + ?Sized>(
+    launches: &[HostLaunchParams<'_, A>],
+    flags: u32,
+) -> HipResult<()> {
+    let devices = launches
+        .iter()
+        .enumerate()
+        .map(|(index, launch)| {
+            launch.config.validate()?;
+            stream_device(&launch.config, index)
+        })
+        .collect::<HipResult<Vec<_>>>()?;
+    check_distinct_devices(&devices)?;
+    // The parameter arrays must outlive the launch call.
+    let mut kernel_params: Vec<_> = launches
+        .iter()
+        .map(|launch| launch.args.as_kernel_params())
+        .collect();
+    let mut raw: Vec<_> = launches
+        .iter()
+        .zip(&mut kernel_params)
+        .map(|(launch, params)| hipLaunchParams {
+            func: launch.function as *mut c_void,
+            gridDim: launch.config.grid,
+            blockDim: launch.config.block,
+            args: params.as_mut_ptr(),
+            sharedMem: launch.config.shared_mem as usize,
+            stream: launch.config.raw_stream(),
+        })
+        .collect();
+    check(hipExtLaunchMultiKernelMultiDevice(
+        raw.as_mut_ptr(),
+        raw.len() as i32,
+        flags,
+    ))
+}
+
+/// Validate the configurations of a multi-device launch and return the device of each one.
+fn launch_devices<A: KernelArgs + ?Sized>(launches: &[LaunchParams<'_, A>]) -> HipResult<Vec<i32>> {
+    let devices = launches
+        .iter()
+        .enumerate()
+        .map(|(index, launch)| {
+            launch.function.validate_launch(&launch.config)?;
+            stream_device(&launch.config, index)
+        })
+        .collect::<HipResult<Vec<_>>>()?;
+    check_distinct_devices(&devices)?;
+    Ok(devices)
+}
+
+fn check_distinct_devices(devices: &[i32]) -> HipResult<()> {
+    if devices.is_empty() {
+        return Err(HipError::InvalidArgument(
+            "a multi-device launch needs at least one launch".to_string(),
+        ));
+    }
+    for (index, device) in devices.iter().enumerate() {
+        if devices[..index].contains(device) {
+            return Err(HipError::InvalidArgument(format!(
+                "device {device} appears in more than one launch"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `device` supports the cooperative launch `attribute` and can hold the whole grid.
+fn check_cooperative(
+    function: &Function,
+    config: &LaunchConfig,
+    device: i32,
+    attribute: hipDeviceAttribute_t,
+) -> HipResult<()> {
+    if device_attribute(attribute, device)? == 0 {
+        return Err(HipError::InvalidArgument(format!(
+            "device {device} does not support this cooperative launch"
+        )));
+    }
+    let block = config.block;
+    let threads = block
+        .x
+        .checked_mul(block.y)
+        .and_then(|threads| threads.checked_mul(block.z))
+        .ok_or_else(|| HipError::InvalidArgument("block has too many threads".to_string()))?;
+    let blocks_per_cu = {
+        let _guard = DeviceGuard::new(device)?;
+        function
+            .occupancy()
+            .max_active_blocks(threads, config.shared_mem as usize)?
+    };
+    let compute_units = device_attribute(
+        hipDeviceAttribute_t_hipDeviceAttributeMultiprocessorCount,
+        device,
+    )?;
+    check_co_resident(config.grid, blocks_per_cu, compute_units as u32)
+}
+
+fn check_co_resident(grid: dim3, blocks_per_cu: u32, compute_units: u32) -> HipResult<()> {
+    let blocks = grid.x as u64 * grid.y as u64 * grid.z as u64;
+    let capacity = blocks_per_cu as u64 * compute_units as u64;
+    if blocks > capacity {
+        return Err(HipError::InvalidArgument(format!(
+            "grid of {blocks} blocks exceeds the {capacity} blocks that can be resident at \
+             once ({blocks_per_cu} per compute unit on {compute_units} compute units)"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::fits((110, 1, 1), 1, 110, true)]
+    #[case::fits_2d((20, 22, 1), 4, 110, true)]
+    #[case::one_block_too_many((111, 1, 1), 1, 110, false)]
+    #[case::kernel_does_not_fit((1, 1, 1), 0, 110, false)]
+    fn test_check_co_resident(
+        #[case] grid: (u32, u32, u32),
+        #[case] blocks_per_cu: u32,
+        #[case] compute_units: u32,
+        #[case] valid: bool,
+    ) {
+        assert_eq!(
+            check_co_resident(grid.into(), blocks_per_cu, compute_units).is_ok(),
+            valid
+        );
+    }
+
+    #[rstest]
+    #[case::distinct(vec![0, 1, 2], true)]
+    #[case::repeated(vec![0, 1, 0], false)]
+    #[case::empty(vec![], false)]
+    fn test_check_distinct_devices(#[case] devices: Vec<i32>, #[case] valid: bool) {
+        assert_eq!(check_distinct_devices(&devices).is_ok(), valid);
+    }
+}
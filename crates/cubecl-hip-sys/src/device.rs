@@ -0,0 +1,102 @@
+use std::marker::PhantomData;
+
+use crate::bindings::*;
+use crate::error::{check, HipResult};
+
+/// Return the number of visible devices with `hipGetDeviceCount`.
+pub fn device_count() -> HipResult<i32> {
+    let mut count = 0;
+    unsafe { check(hipGetDeviceCount(&mut count))? };
+    Ok(count)
+}
+
+/// Return the device used by the calling thread with `hipGetDevice`.
+pub fn current_device() -> HipResult<i32> {
+    let mut device = 0;
+    unsafe { check(hipGetDevice(&mut device))? };
+    Ok(device)
+}
+
+/// Set the device used by the calling thread with `hipSetDevice`.
+pub fn set_device(device: i32) -> HipResult<()> {
+    unsafe { check(hipSetDevice(device)) }
+}
+
+/// Return the value of `attribute` for `device` with `hipDeviceGetAttribute`.
+pub fn device_attribute(attribute: hipDeviceAttribute_t, device: i32) -> HipResult<i32> {
+    let mut value = 0;
+    unsafe { check(hipDeviceGetAttribute(&mut value, attribute, device))? };
+    Ok(value)
+}
+
+/// Device made current on the calling thread with [`DeviceGuard::new`], restoring the
+/// previous device on drop.
+///
+/// Guards should be dropped in the reverse order they were created, which scopes ensure.
+#[derive(Debug)]
+pub struct DeviceGuard {
+    previous: i32,
+    // The current device is per thread, the guard must be dropped on the thread that made it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl DeviceGuard {
+    /// Make `device` current on the calling thread, remembering the current one.
+    pub fn new(device: i32) -> HipResult<Self> {
+        let previous = current_device()?;
+        if previous != device {
+            set_device(device)?;
+        }
+        Ok(Self {
+            previous,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Return the device restored on drop.
+    pub fn previous(&self) -> i32 {
+        self.previous
+    }
+}
+
+impl Drop for DeviceGuard {
+    fn drop(&mut self) {
+        unsafe {
+            hipSetDevice(self.previous);
+        }
+    }
+}
+
+/// Run `f` with `device` current on the calling thread, then restore the previous device.
+pub fn with_device<R>(device: i32, f: impl FnOnce() -> R) -> HipResult<R> {
+    let _guard = DeviceGuard::new(device)?;
+    Ok(f())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_device_guard_restores_previous_device_end_to_end() {
+        set_device(0).expect("Should set the GPU device");
+        let last = device_count().expect("Should count the devices") - 1;
+        {
+            let guard = DeviceGuard::new(last).expect("Should switch device");
+            assert_eq!(guard.previous(), 0);
+            assert_eq!(current_device().unwrap(), last);
+            {
+                let nested = DeviceGuard::new(0).expect("Should switch device back");
+                assert_eq!(nested.previous(), last);
+                assert_eq!(current_device().unwrap(), 0);
+            }
+            assert_eq!(current_device().unwrap(), last);
+        }
+        assert_eq!(current_device().unwrap(), 0);
+
+        let inside = with_device(last, current_device).expect("Should switch device");
+        assert_eq!(inside.unwrap(), last);
+        assert_eq!(current_device().unwrap(), 0);
+    }
+}
@@ -0,0 +1,190 @@
+use std::{ffi::c_void, mem, ptr};
+
+use crate::bindings::*;
+use crate::device::current_device;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::KernelArgValue;
+use crate::launch::KernelArg;
+use crate::mem_pool::byte_size;
+use crate::stream::Stream;
+
+/// Typed device allocation made with `hipMalloc`, freed on drop.
+///
+/// The buffer remembers the device that was current when it was allocated.
+#[derive(Debug)]
+pub struct DeviceBuffer<T: KernelArgValue> {
+    ptr: *mut T,
+    len: usize,
+    device: i32,
+}
+
+impl<T: KernelArgValue> DeviceBuffer<T> {
+    /// Allocate `len` elements on the current device, with all their bytes set to zero.
+    pub fn zeroed(len: usize) -> HipResult<Self> {
+        let buffer = Self::alloc(len)?;
+        unsafe {
+            check(hipMemset(
+                buffer.ptr as *mut c_void,
+                0,
+                buffer.size_in_bytes(),
+            ))?
+        };
+        Ok(buffer)
+    }
+
+    /// Allocate a buffer on the current device and copy `data` into it.
+    pub fn from_slice(data: &[T]) -> HipResult<Self> {
+        let mut buffer = Self::alloc(data.len())?;
+        buffer.copy_from_host(data)?;
+        Ok(buffer)
+    }
+
+    /// Take ownership of `len` elements at `ptr` on `device`, freed with `hipFree` on drop.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be a device allocation of at least `len` elements that `hipFree`
+    /// releases and that is not freed elsewhere.
+    pub(crate) unsafe fn from_raw(ptr: *mut T, len: usize, device: i32) -> Self {
+        Self { ptr, len, device }
+    }
+
+    fn alloc(len: usize) -> HipResult<Self> {
+        let size = byte_size::<T>(len)?;
+        let device = current_device()?;
+        let mut raw: *mut c_void = ptr::null_mut();
+        unsafe { check(hipMalloc(&mut raw, size))? };
+        Ok(Self {
+            ptr: raw as *mut T,
+            len,
+            device,
+        })
+    }
+
+    /// Copy `data` from the host into the buffer, blocking until the copy is complete.
+    pub fn copy_from_host(&mut self, data: &[T]) -> HipResult<()> {
+        self.check_len(data.len())?;
+        unsafe {
+            check(hipMemcpy(
+                self.ptr as *mut c_void,
+                data.as_ptr() as *const c_void,
+                self.size_in_bytes(),
+                hipMemcpyKind_hipMemcpyHostToDevice,
+            ))
+        }
+    }
+
+    /// Copy the buffer into `data` on the host, blocking until the copy is complete.
+    pub fn copy_to_host(&self, data: &mut [T]) -> HipResult<()> {
+        self.check_len(data.len())?;
+        unsafe {
+            check(hipMemcpy(
+                data.as_mut_ptr() as *mut c_void,
+                self.ptr as *const c_void,
+                self.size_in_bytes(),
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+            ))
+        }
+    }
+
+    /// Copy the buffer into a new vector on the host.
+    pub fn to_vec(&self) -> HipResult<Vec<T>> {
+        let mut data = Vec::with_capacity(self.len);
+        unsafe {
+            check(hipMemcpy(
+                data.as_mut_ptr() as *mut c_void,
+                self.ptr as *const c_void,
+                self.size_in_bytes(),
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+            ))?;
+            data.set_len(self.len);
+        }
+        Ok(data)
+    }
+
+    /// Copy `src`, which may live on another device, into the buffer with `hipMemcpyPeer`.
+    pub fn copy_from_peer(&mut self, src: &DeviceBuffer<T>) -> HipResult<()> {
+        self.check_len(src.len)?;
+        unsafe {
+            check(hipMemcpyPeer(
+                self.ptr as *mut c_void,
+                self.device,
+                src.ptr as *const c_void,
+                src.device,
+                self.size_in_bytes(),
+            ))
+        }
+    }
+
+    /// Queue a copy of `src`, which may live on another device, into the buffer on `stream`
+    /// with `hipMemcpyPeerAsync`.
+    ///
+    /// # Safety
+    ///
+    /// The copy runs after this call returns. Both buffers must stay alive, and must not be
+    /// read or written by the host or by another stream, until `stream` has completed it.
+    pub unsafe fn copy_from_peer_async(
+        &mut self,
+        src: &DeviceBuffer<T>,
+        stream: &Stream,
+    ) -> HipResult<()> {
+        self.check_len(src.len)?;
+        check(hipMemcpyPeerAsync(
+            self.ptr as *mut c_void,
+            self.device,
+            src.ptr as *const c_void,
+            src.device,
+            self.size_in_bytes(),
+            stream.as_raw(),
+        ))
+    }
+
+    fn check_len(&self, len: usize) -> HipResult<()> {
+        if len != self.len {
+            return Err(HipError::InvalidArgument(format!(
+                "buffer has {} elements, got {len} elements",
+                self.len
+            )));
+        }
+        Ok(())
+    }
+
+    /// Return the device pointer.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// Return the device the buffer is allocated on.
+    pub fn device(&self) -> i32 {
+        self.device
+    }
+
+    /// Return the number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the buffer has no element.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the size of the buffer in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        self.len * mem::size_of::<T>()
+    }
+}
+
+impl<T: KernelArgValue> Drop for DeviceBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            hipFree(self.ptr as *mut c_void);
+        }
+    }
+}
+
+unsafe impl<T: KernelArgValue> KernelArg for DeviceBuffer<T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut T as *mut c_void
+    }
+}
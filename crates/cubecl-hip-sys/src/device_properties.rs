@@ -0,0 +1,128 @@
+use std::ffi::{c_char, CStr};
+
+use crate::bindings::*;
+use crate::device::current_device;
+use crate::error::{check, HipResult};
+
+/// Properties of a device relevant to launching kernels, from `hipGetDevicePropertiesR0600`.
+///
+/// The fields are public so that properties of a device that is not present can be built by
+/// hand, for instance to feed an [`OccupancyModel`](crate::OccupancyModel).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceProperties {
+    /// Marketing name of the device.
+    pub name: String,
+    /// Architecture with its target features, for instance `gfx90a:sramecc+:xnack-`.
+    pub gcn_arch_name: String,
+    /// Global memory in bytes.
+    pub total_global_mem: usize,
+    /// Number of compute units, or of work-group processors on RDNA devices.
+    pub multiprocessor_count: u32,
+    /// Number of threads in a wavefront, 32 or 64.
+    pub warp_size: u32,
+    /// Largest block in threads, over all dimensions.
+    pub max_threads_per_block: u32,
+    /// Threads that can be resident on a compute unit.
+    pub max_threads_per_multiprocessor: u32,
+    /// Largest block in threads along x, y and z.
+    pub max_block_dim: [u32; 3],
+    /// Largest grid in blocks along x, y and z.
+    pub max_grid_dim: [u32; 3],
+    /// Shared memory in bytes available to a block.
+    pub shared_mem_per_block: usize,
+    /// Shared memory in bytes of a compute unit, shared by its resident blocks.
+    pub shared_mem_per_multiprocessor: usize,
+    /// 32-bit registers available to a block.
+    pub regs_per_block: u32,
+    /// 32-bit registers of a compute unit.
+    pub regs_per_multiprocessor: u32,
+    /// L2 cache size in bytes.
+    pub l2_cache_size: u32,
+    /// Peak clock frequency in kilohertz.
+    pub clock_rate_khz: u32,
+}
+
+impl DeviceProperties {
+    /// Query the properties of `device`.
+    pub fn query(device: i32) -> HipResult<Self> {
+        let mut raw: hipDeviceProp_tR0600 = unsafe { zeroed_raw() };
+        unsafe { check(hipGetDevicePropertiesR0600(&mut raw, device))? };
+        Ok(Self {
+            name: c_string(&raw.name),
+            gcn_arch_name: c_string(&raw.gcnArchName),
+            total_global_mem: raw.totalGlobalMem,
+            multiprocessor_count: raw.multiProcessorCount as u32,
+            warp_size: raw.warpSize as u32,
+            max_threads_per_block: raw.maxThreadsPerBlock as u32,
+            max_threads_per_multiprocessor: raw.maxThreadsPerMultiProcessor as u32,
+            max_block_dim: raw.maxThreadsDim.map(|d| d as u32),
+            max_grid_dim: raw.maxGridSize.map(|d| d as u32),
+            shared_mem_per_block: raw.sharedMemPerBlock,
+            shared_mem_per_multiprocessor: raw.sharedMemPerMultiprocessor,
+            regs_per_block: raw.regsPerBlock as u32,
+            regs_per_multiprocessor: raw.regsPerMultiprocessor as u32,
+            l2_cache_size: raw.l2CacheSize as u32,
+            clock_rate_khz: raw.clockRate as u32,
+        })
+    }
+
+    /// Query the properties of the device used by the calling thread.
+    pub fn current() -> HipResult<Self> {
+        Self::query(current_device()?)
+    }
+
+    /// Return the processor without its target features, for instance `gfx90a`.
+    pub fn arch(&self) -> &str {
+        self.gcn_arch_name
+            .split(':')
+            .next()
+            .unwrap_or(&self.gcn_arch_name)
+    }
+}
+
+/// Read a nul-terminated string from a fixed-size array, up to its end if there is no nul.
+fn c_string(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars.iter().map(|c| *c as u8).collect();
+    match CStr::from_bytes_until_nul(&bytes) {
+        Ok(s) => s.to_string_lossy().into_owned(),
+        Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::with_features("gfx90a:sramecc+:xnack-", "gfx90a")]
+    #[case::plain("gfx1030", "gfx1030")]
+    fn test_arch_strips_target_features(#[case] gcn_arch_name: &str, #[case] expected: &str) {
+        let properties = DeviceProperties {
+            name: String::new(),
+            gcn_arch_name: gcn_arch_name.to_string(),
+            total_global_mem: 0,
+            multiprocessor_count: 0,
+            warp_size: 64,
+            max_threads_per_block: 1024,
+            max_threads_per_multiprocessor: 2048,
+            max_block_dim: [1024; 3],
+            max_grid_dim: [u32::MAX; 3],
+            shared_mem_per_block: 0,
+            shared_mem_per_multiprocessor: 0,
+            regs_per_block: 0,
+            regs_per_multiprocessor: 0,
+            l2_cache_size: 0,
+            clock_rate_khz: 0,
+        };
+        assert_eq!(properties.arch(), expected);
+    }
+
+    #[rstest]
+    #[case::nul_terminated(b"gfx90a\0junk", "gfx90a")]
+    #[case::unterminated(b"gfx90a", "gfx90a")]
+    fn test_c_string(#[case] bytes: &[u8], #[case] expected: &str) {
+        let chars: Vec<c_char> = bytes.iter().map(|b| *b as c_char).collect();
+        assert_eq!(c_string(&chars), expected);
+    }
+}
@@ -0,0 +1,70 @@
+use std::{ffi::CStr, fmt};
+
+use crate::bindings::*;
+
+/// Result type returned by the safe HIP wrappers.
+pub type HipResult<T> = Result<T, HipError>;
+
+/// Error returned by the safe HIP wrappers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HipError {
+    /// A HIP runtime call returned a status other than `hipSuccess`.
+    Runtime(hipError_t),
+    /// A hiprtc call returned a status other than `HIPRTC_SUCCESS`.
+    Rtc(hiprtcResult),
+    /// The arguments were rejected on the Rust side before calling HIP.
+    InvalidArgument(String),
+    /// A code object or an offload bundle could not be decoded.
+    InvalidCodeObject(String),
+}
+
+impl HipError {
+    /// Return the raw HIP status if this error comes from the HIP runtime.
+    pub fn status(&self) -> Option<hipError_t> {
+        match self {
+            HipError::Runtime(status) => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HipError::Runtime(status) => {
+                let description = unsafe { CStr::from_ptr(hipGetErrorString(*status)) };
+                write!(f, "HIP error {status}: {}", description.to_string_lossy())
+            }
+            HipError::Rtc(status) => {
+                let description = unsafe { CStr::from_ptr(hiprtcGetErrorString(*status)) };
+                write!(
+                    f,
+                    "hiprtc error {status}: {}",
+                    description.to_string_lossy()
+                )
+            }
+            HipError::InvalidArgument(reason) => write!(f, "Invalid argument: {reason}"),
+            HipError::InvalidCodeObject(reason) => write!(f, "Invalid code object: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for HipError {}
+
+/// Convert a HIP runtime status into a [`HipResult`].
+pub(crate) fn check(status: hipError_t) -> HipResult<()> {
+    if status == hipError_t_hipSuccess {
+        Ok(())
+    } else {
+        Err(HipError::Runtime(status))
+    }
+}
+
+/// Convert a hiprtc status into a [`HipResult`].
+pub(crate) fn check_rtc(status: hiprtcResult) -> HipResult<()> {
+    if status == hiprtcResult_HIPRTC_SUCCESS {
+        Ok(())
+    } else {
+        Err(HipError::Rtc(status))
+    }
+}
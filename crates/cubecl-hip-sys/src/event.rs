@@ -0,0 +1,74 @@
+use std::ptr;
+
+use crate::bindings::*;
+use crate::error::{check, HipResult};
+use crate::stream::Stream;
+
+/// Owned HIP event, destroyed on drop.
+#[derive(Debug)]
+pub struct Event {
+    raw: hipEvent_t,
+}
+
+impl Event {
+    /// Create a new event on the current device with `hipEventCreate`.
+    pub fn new() -> HipResult<Self> {
+        let mut raw: hipEvent_t = ptr::null_mut();
+        unsafe { check(hipEventCreate(&mut raw))? };
+        Ok(Self { raw })
+    }
+
+    /// Create a new event with `hipEventCreateWithFlags`, e.g. `hipEventDisableTiming`.
+    pub fn with_flags(flags: u32) -> HipResult<Self> {
+        let mut raw: hipEvent_t = ptr::null_mut();
+        unsafe { check(hipEventCreateWithFlags(&mut raw, flags))? };
+        Ok(Self { raw })
+    }
+
+    /// Take ownership of a raw event, destroyed when the returned value is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `raw` should be a valid event that is not destroyed elsewhere.
+    pub unsafe fn from_raw(raw: hipEvent_t) -> Self {
+        Self { raw }
+    }
+
+    /// Return the underlying `hipEvent_t`.
+    pub fn as_raw(&self) -> hipEvent_t {
+        self.raw
+    }
+
+    /// Record the event after the work currently queued on `stream`.
+    pub fn record(&self, stream: &Stream) -> HipResult<()> {
+        unsafe { check(hipEventRecord(self.raw, stream.as_raw())) }
+    }
+
+    /// Block the host until the work captured by the last record is complete.
+    pub fn synchronize(&self) -> HipResult<()> {
+        unsafe { check(hipEventSynchronize(self.raw)) }
+    }
+
+    /// Return true if the work captured by the last record is complete.
+    pub fn query(&self) -> HipResult<bool> {
+        match unsafe { hipEventQuery(self.raw) } {
+            hipError_t_hipErrorNotReady => Ok(false),
+            status => check(status).map(|_| true),
+        }
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe {
+            hipEventDestroy(self.raw);
+        }
+    }
+}
+
+impl Stream {
+    /// Make all the future work queued on the stream wait for `event` to complete.
+    pub fn wait_event(&self, event: &Event) -> HipResult<()> {
+        unsafe { check(hipStreamWaitEvent(self.as_raw(), event.as_raw(), 0)) }
+    }
+}
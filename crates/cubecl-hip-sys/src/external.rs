@@ -0,0 +1,342 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+
+use crate::bindings::*;
+use crate::device::current_device;
+use crate::device_buffer::DeviceBuffer;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::KernelArgValue;
+use crate::launch::KernelArg;
+use crate::mem_pool::byte_size;
+use crate::stream::Stream;
+
+/// Memory allocated by another API, such as Vulkan, imported with `hipImportExternalMemory`
+/// and destroyed on drop.
+#[derive(Debug)]
+pub struct ExternalMemory {
+    raw: hipExternalMemory_t,
+    size: usize,
+}
+
+impl ExternalMemory {
+    fn import(desc: &hipExternalMemoryHandleDesc, size: usize) -> HipResult<Self> {
+        let mut raw: hipExternalMemory_t = std::ptr::null_mut();
+        unsafe { check(hipImportExternalMemory(&mut raw, desc))? };
+        Ok(Self { raw, size })
+    }
+
+    /// Map `len` elements starting `offset` bytes into the memory with
+    /// `hipExternalMemoryGetMappedBuffer`.
+    ///
+    /// The returned buffer is freed before the memory can be destroyed.
+    pub fn mapped_buffer<T: KernelArgValue>(
+        &self,
+        offset: usize,
+        len: usize,
+    ) -> HipResult<ExternalBuffer<'_, T>> {
+        let size = check_mapping::<T>(self.size, offset, len)?;
+        let device = current_device()?;
+        let desc = hipExternalMemoryBufferDesc {
+            offset: offset as u64,
+            size: size as u64,
+            flags: 0,
+            reserved: [0; 16],
+        };
+        let mut ptr: *mut c_void = std::ptr::null_mut();
+        unsafe { check(hipExternalMemoryGetMappedBuffer(&mut ptr, self.raw, &desc))? };
+        Ok(ExternalBuffer {
+            // Mapped buffers are released with `hipFree`, like the allocations of a
+            // `DeviceBuffer`.
+            buffer: unsafe { DeviceBuffer::from_raw(ptr as *mut T, len, device) },
+            _memory: PhantomData,
+        })
+    }
+
+    /// Return the underlying `hipExternalMemory_t`.
+    pub fn as_raw(&self) -> hipExternalMemory_t {
+        self.raw
+    }
+
+    /// Return the size of the memory in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for ExternalMemory {
+    fn drop(&mut self) {
+        unsafe {
+            hipDestroyExternalMemory(self.raw);
+        }
+    }
+}
+
+/// Buffer mapped in [`ExternalMemory`] with [`ExternalMemory::mapped_buffer`], read as a
+/// [`DeviceBuffer`] and freed on drop.
+///
+/// Only shared access to the inner buffer is given out, so that it cannot be swapped with a
+/// buffer that would outlive the memory; the writing methods are forwarded instead.
+#[derive(Debug)]
+pub struct ExternalBuffer<'a, T: KernelArgValue> {
+    buffer: DeviceBuffer<T>,
+    _memory: PhantomData<&'a ExternalMemory>,
+}
+
+impl<T: KernelArgValue> Deref for ExternalBuffer<'_, T> {
+    type Target = DeviceBuffer<T>;
+
+    fn deref(&self) -> &DeviceBuffer<T> {
+        &self.buffer
+    }
+}
+
+impl<T: KernelArgValue> ExternalBuffer<'_, T> {
+    /// Copy `data` from the host into the buffer, see [`DeviceBuffer::copy_from_host`].
+    pub fn copy_from_host(&mut self, data: &[T]) -> HipResult<()> {
+        self.buffer.copy_from_host(data)
+    }
+
+    /// Copy `src` into the buffer, see [`DeviceBuffer::copy_from_peer`].
+    pub fn copy_from_peer(&mut self, src: &DeviceBuffer<T>) -> HipResult<()> {
+        self.buffer.copy_from_peer(src)
+    }
+
+    /// Queue a copy of `src` into the buffer on `stream`, see
+    /// [`DeviceBuffer::copy_from_peer_async`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`DeviceBuffer::copy_from_peer_async`].
+    pub unsafe fn copy_from_peer_async(
+        &mut self,
+        src: &DeviceBuffer<T>,
+        stream: &Stream,
+    ) -> HipResult<()> {
+        self.buffer.copy_from_peer_async(src, stream)
+    }
+
+    /// Set every element to `value`, see [`DeviceBuffer::fill`].
+    pub fn fill(&mut self, value: T) -> HipResult<()> {
+        self.buffer.fill(value)
+    }
+
+    /// Queue a fill of every element with `value` on `stream`, see
+    /// [`DeviceBuffer::fill_async`].
+    pub fn fill_async(&mut self, value: T, stream: &Stream) -> HipResult<()> {
+        self.buffer.fill_async(value, stream)
+    }
+}
+
+unsafe impl<T: KernelArgValue> KernelArg for ExternalBuffer<'_, T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        self.buffer.as_kernel_param()
+    }
+}
+
+/// Check that `len` elements starting `offset` bytes into external memory of `size` bytes
+/// fit and are aligned, and return their size in bytes.
+fn check_mapping<T>(size: usize, offset: usize, len: usize) -> HipResult<usize> {
+    let bytes = byte_size::<T>(len)?;
+    if offset % mem::align_of::<T>() != 0 {
+        return Err(HipError::InvalidArgument(format!(
+            "offset {offset} is not aligned to the {} bytes of the elements",
+            mem::align_of::<T>()
+        )));
+    }
+    match offset.checked_add(bytes) {
+        Some(end) if end <= size => Ok(bytes),
+        _ => Err(HipError::InvalidArgument(format!(
+            "{bytes} bytes at offset {offset} exceed the {size} bytes of the external memory"
+        ))),
+    }
+}
+
+/// Synchronization primitive of another API, such as a Vulkan semaphore, imported with
+/// `hipImportExternalSemaphore` and destroyed on drop.
+///
+/// The streams waiting on or signaling the semaphore must be done with it before it is
+/// dropped.
+#[derive(Debug)]
+pub struct ExternalSemaphore {
+    raw: hipExternalSemaphore_t,
+}
+
+impl ExternalSemaphore {
+    fn import(desc: &hipExternalSemaphoreHandleDesc) -> HipResult<Self> {
+        let mut raw: hipExternalSemaphore_t = std::ptr::null_mut();
+        unsafe { check(hipImportExternalSemaphore(&mut raw, desc))? };
+        Ok(Self { raw })
+    }
+
+    /// Queue a signal of the semaphore on `stream`, setting timeline semaphores to `value`.
+    pub fn signal_async(&self, value: u64, stream: &Stream) -> HipResult<()> {
+        signal_external_semaphores_async(&[(self, value)], stream)
+    }
+
+    /// Queue a wait on `stream` until the semaphore is signaled, or for timeline semaphores
+    /// until it reaches `value`.
+    pub fn wait_async(&self, value: u64, stream: &Stream) -> HipResult<()> {
+        wait_external_semaphores_async(&[(self, value)], stream)
+    }
+
+    /// Return the underlying `hipExternalSemaphore_t`.
+    pub fn as_raw(&self) -> hipExternalSemaphore_t {
+        self.raw
+    }
+}
+
+impl Drop for ExternalSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            hipDestroyExternalSemaphore(self.raw);
+        }
+    }
+}
+
+/// Queue a signal of each semaphore with its value on `stream` with
+/// `hipSignalExternalSemaphoresAsync`.
+pub fn signal_external_semaphores_async(
+    semaphores: &[(&ExternalSemaphore, u64)],
+    stream: &Stream,
+) -> HipResult<()> {
+    let raw: Vec<_> = semaphores
+        .iter()
+        .map(|(semaphore, _)| semaphore.raw)
+        .collect();
+    let params: Vec<_> = semaphores
+        .iter()
+        .map(|&(_, value)| {
+            let mut params: hipExternalSemaphoreSignalParams = unsafe { zeroed_raw() };
+            params.params.fence.value = value;
+            params
+        })
+        .collect();
+    unsafe {
+        check(hipSignalExternalSemaphoresAsync(
+            raw.as_ptr(),
+            params.as_ptr(),
+            raw.len() as u32,
+            stream.as_raw(),
+        ))
+    }
+}
+
+/// Queue a wait on `stream` for each semaphore to reach its value with
+/// `hipWaitExternalSemaphoresAsync`.
+pub fn wait_external_semaphores_async(
+    semaphores: &[(&ExternalSemaphore, u64)],
+    stream: &Stream,
+) -> HipResult<()> {
+    let raw: Vec<_> = semaphores
+        .iter()
+        .map(|(semaphore, _)| semaphore.raw)
+        .collect();
+    let params: Vec<_> = semaphores
+        .iter()
+        .map(|&(_, value)| {
+            let mut params: hipExternalSemaphoreWaitParams = unsafe { zeroed_raw() };
+            params.params.fence.value = value;
+            params
+        })
+        .collect();
+    unsafe {
+        check(hipWaitExternalSemaphoresAsync(
+            raw.as_ptr(),
+            params.as_ptr(),
+            raw.len() as u32,
+            stream.as_raw(),
+        ))
+    }
+}
+
+#[cfg(unix)]
+mod fd {
+    use std::os::fd::{AsRawFd, IntoRawFd, OwnedFd};
+
+    use crate::bindings::*;
+    use crate::error::HipResult;
+
+    use super::{ExternalMemory, ExternalSemaphore};
+
+    impl ExternalMemory {
+        /// Import `size` bytes of memory exported as an opaque file descriptor, for instance
+        /// by `vkGetMemoryFdKHR`.
+        ///
+        /// The runtime takes ownership of `fd` once the import succeeds, and it is closed
+        /// when the import fails. `dedicated` should be set for dedicated Vulkan
+        /// allocations.
+        pub fn import_fd(fd: OwnedFd, size: usize, dedicated: bool) -> HipResult<Self> {
+            let mut desc: hipExternalMemoryHandleDesc = unsafe { zeroed_raw() };
+            desc.type_ = hipExternalMemoryHandleType_enum_hipExternalMemoryHandleTypeOpaqueFd;
+            desc.handle.fd = fd.as_raw_fd();
+            desc.size = size as u64;
+            desc.flags = if dedicated {
+                hipExternalMemoryDedicated
+            } else {
+                0
+            };
+            let memory = Self::import(&desc, size)?;
+            let _ = fd.into_raw_fd();
+            Ok(memory)
+        }
+    }
+
+    impl ExternalSemaphore {
+        /// Import a binary semaphore exported as an opaque file descriptor, for instance by
+        /// `vkGetSemaphoreFdKHR`.
+        ///
+        /// The runtime takes ownership of `fd` once the import succeeds, and it is closed
+        /// when the import fails.
+        pub fn import_fd(fd: OwnedFd) -> HipResult<Self> {
+            Self::import_fd_of_type(
+                fd,
+                hipExternalSemaphoreHandleType_enum_hipExternalSemaphoreHandleTypeOpaqueFd,
+            )
+        }
+
+        /// Import a timeline semaphore exported as an opaque file descriptor, whose
+        /// signals and waits use the values passed to them.
+        pub fn import_timeline_fd(fd: OwnedFd) -> HipResult<Self> {
+            Self::import_fd_of_type(
+                fd,
+                hipExternalSemaphoreHandleType_enum_hipExternalSemaphoreHandleTypeTimelineSemaphoreFd,
+            )
+        }
+
+        fn import_fd_of_type(
+            fd: OwnedFd,
+            type_: hipExternalSemaphoreHandleType,
+        ) -> HipResult<Self> {
+            let mut desc: hipExternalSemaphoreHandleDesc = unsafe { zeroed_raw() };
+            desc.type_ = type_;
+            desc.handle.fd = fd.as_raw_fd();
+            let semaphore = Self::import(&desc)?;
+            let _ = fd.into_raw_fd();
+            Ok(semaphore)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::whole(64, 0, 16, Some(64))]
+    #[case::tail(64, 32, 8, Some(32))]
+    #[case::too_long(64, 32, 9, None)]
+    #[case::misaligned(64, 2, 1, None)]
+    #[case::offset_past_end(64, 68, 0, None)]
+    #[case::empty(64, 64, 0, Some(0))]
+    fn test_check_mapping(
+        #[case] size: usize,
+        #[case] offset: usize,
+        #[case] len: usize,
+        #[case] expected: Option<usize>,
+    ) {
+        assert_eq!(check_mapping::<f32>(size, offset, len).ok(), expected);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::{mem, ptr, slice};
+
+use crate::bindings::*;
+use crate::device::{current_device, DeviceGuard};
+use crate::device_buffer::DeviceBuffer;
+use crate::error::{check, HipResult};
+use crate::kernel_args::{KernelArgValue, KernelArgsBuffer};
+use crate::launch::LaunchConfig;
+use crate::module::Module;
+use crate::program::Program;
+use crate::stream::Stream;
+
+const FILL_KERNEL: &str = "fill_pattern";
+const FILL_BLOCK_SIZE: usize = 256;
+const MAX_FILL_BLOCKS: usize = 4096;
+
+impl<T: KernelArgValue> DeviceBuffer<T> {
+    /// Set every element to `value`, blocking until the buffer is filled.
+    ///
+    /// Elements of 1, 2 and 4 bytes are set with `hipMemsetD8`, `hipMemsetD16` and
+    /// `hipMemsetD32`, other sizes with a built-in kernel compiled with hiprtc the first
+    /// time it is needed on a device.
+    pub fn fill(&mut self, value: T) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let dst = self.as_ptr() as hipDeviceptr_t;
+        unsafe {
+            match fill_pattern(&value) {
+                FillPattern::D8(pattern) => check(hipMemsetD8(dst, pattern, self.len())),
+                FillPattern::D16(pattern) => check(hipMemsetD16(dst, pattern, self.len())),
+                FillPattern::D32(pattern) => check(hipMemsetD32(dst, pattern as i32, self.len())),
+                FillPattern::Kernel => self.launch_fill_kernel(value, None),
+            }
+        }
+    }
+
+    /// Queue a fill of every element with `value` on `stream`, see [`DeviceBuffer::fill`].
+    ///
+    /// The buffer should not be dropped or accessed before the fill completes.
+    pub fn fill_async(&mut self, value: T, stream: &Stream) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let dst = self.as_ptr() as hipDeviceptr_t;
+        let raw_stream = stream.as_raw();
+        unsafe {
+            match fill_pattern(&value) {
+                FillPattern::D8(pattern) => {
+                    check(hipMemsetD8Async(dst, pattern, self.len(), raw_stream))
+                }
+                FillPattern::D16(pattern) => {
+                    check(hipMemsetD16Async(dst, pattern, self.len(), raw_stream))
+                }
+                FillPattern::D32(pattern) => check(hipMemsetD32Async(
+                    dst,
+                    pattern as i32,
+                    self.len(),
+                    raw_stream,
+                )),
+                FillPattern::Kernel => self.launch_fill_kernel(value, Some(stream)),
+            }
+        }
+    }
+
+    /// Launch the fill kernel on `stream`, or on the null stream of the device of the buffer
+    /// and wait for it before the previous device is restored.
+    fn launch_fill_kernel(&mut self, value: T, stream: Option<&Stream>) -> HipResult<()> {
+        // The kernel module is loaded on the device of the buffer.
+        let _guard = DeviceGuard::new(self.device())?;
+        let key = FillKernelKey {
+            device: current_device()?,
+            size: mem::size_of::<T>(),
+            align: mem::align_of::<T>(),
+        };
+        let function = fill_module(key)?.function(FILL_KERNEL)?;
+        let mut args = KernelArgsBuffer::new();
+        args.push_device_ptr(self.as_ptr())
+            .push(value)
+            .push(self.len() as u64);
+        let blocks = self.len().div_ceil(FILL_BLOCK_SIZE).min(MAX_FILL_BLOCKS);
+        let mut config = LaunchConfig::new(blocks as u32, FILL_BLOCK_SIZE as u32);
+        if let Some(stream) = stream {
+            config = config.with_stream(stream);
+        }
+        unsafe { function.launch_with_buffer(&config, &args)? };
+        if stream.is_none() {
+            unsafe { check(hipStreamSynchronize(ptr::null_mut()))? };
+        }
+        Ok(())
+    }
+}
+
+/// Way to fill a buffer with a value, depending on its size in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FillPattern {
+    D8(u8),
+    D16(u16),
+    D32(u32),
+    Kernel,
+}
+
+fn fill_pattern<T: KernelArgValue>(value: &T) -> FillPattern {
+    // Kernel argument values have no padding, every byte is initialized.
+    let bytes =
+        unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) };
+    match *bytes {
+        [byte] => FillPattern::D8(byte),
+        [b0, b1] => FillPattern::D16(u16::from_ne_bytes([b0, b1])),
+        [b0, b1, b2, b3] => FillPattern::D32(u32::from_ne_bytes([b0, b1, b2, b3])),
+        _ => FillPattern::Kernel,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FillKernelKey {
+    device: i32,
+    size: usize,
+    align: usize,
+}
+
+/// Fill kernel module, shared by all threads and kept loaded until the process exits.
+struct FillModule(Module);
+
+// Loaded modules are process wide, any thread can look up their functions.
+unsafe impl Send for FillModule {}
+unsafe impl Sync for FillModule {}
+
+fn fill_modules() -> &'static Mutex<HashMap<FillKernelKey, &'static FillModule>> {
+    static MODULES: OnceLock<Mutex<HashMap<FillKernelKey, &'static FillModule>>> = OnceLock::new();
+    MODULES.get_or_init(Default::default)
+}
+
+/// Return the fill module of `key`, compiling and loading it the first time.
+///
+/// The lock is only held to look up and insert modules, so a compilation does not block the
+/// fills of other element types. Threads compiling the same module at once keep the first
+/// one inserted.
+fn fill_module(key: FillKernelKey) -> HipResult<&'static Module> {
+    let lock = || {
+        fill_modules()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    };
+    if let Some(module) = lock().get(&key) {
+        return Ok(&module.0);
+    }
+    let module = load_fill_module(key)?;
+    let module = *lock()
+        .entry(key)
+        .or_insert_with(|| Box::leak(Box::new(FillModule(module))));
+    Ok(&module.0)
+}
+
+fn load_fill_module(key: FillKernelKey) -> HipResult<Module> {
+    let mut program = Program::new(&fill_kernel_source(key.size, key.align), "fill.hip")?;
+    program.compile::<&str>(&[])?;
+    Module::from_program(&program)
+}
+
+/// Return the source of a kernel copying an element of `size` bytes aligned to `align`
+/// bytes to every element of a buffer, with a grid-stride loop.
+fn fill_kernel_source(size: usize, align: usize) -> String {
+    format!(
+        r#"
+struct __attribute__((aligned({align}))) Pattern {{
+  unsigned char bytes[{size}];
+}};
+
+extern "C" __global__ void {FILL_KERNEL}(Pattern *dst, Pattern value, unsigned long long len) {{
+  unsigned long long stride = (unsigned long long)gridDim.x * blockDim.x;
+  for (unsigned long long i = (unsigned long long)blockIdx.x * blockDim.x + threadIdx.x; i < len;
+       i += stride) {{
+    dst[i] = value;
+  }}
+}}
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_pattern_by_size() {
+        assert_eq!(fill_pattern(&0xabu8), FillPattern::D8(0xab));
+        assert_eq!(fill_pattern(&-1i16), FillPattern::D16(0xffff));
+        assert_eq!(fill_pattern(&1.0f32), FillPattern::D32(1.0f32.to_bits()));
+        assert_eq!(
+            fill_pattern(&[1u8, 2, 3, 4]),
+            FillPattern::D32(u32::from_ne_bytes([1, 2, 3, 4]))
+        );
+        assert_eq!(fill_pattern(&1.0f64), FillPattern::Kernel);
+        assert_eq!(fill_pattern(&[1u8, 2, 3]), FillPattern::Kernel);
+    }
+
+    #[test]
+    fn test_fill_kernel_source_matches_element_layout() {
+        let source = fill_kernel_source(12, 4);
+        assert!(source.contains("aligned(4)"));
+        assert!(source.contains("unsigned char bytes[12];"));
+        assert!(source.contains("void fill_pattern("));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_fill_kernel_fallback_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        // More elements than a block, so that several blocks write the buffer.
+        let mut doubles = DeviceBuffer::<f64>::zeroed(1000).expect("Should allocate");
+        doubles.fill(2.5).expect("Should fill with the kernel");
+        assert_eq!(doubles.to_vec().unwrap(), vec![2.5; 1000]);
+
+        let mut rgb = DeviceBuffer::<[u8; 3]>::zeroed(333).expect("Should allocate");
+        rgb.fill([1, 2, 3]).expect("Should fill with the kernel");
+        assert_eq!(rgb.to_vec().unwrap(), vec![[1, 2, 3]; 333]);
+
+        let stream = Stream::new().expect("Should create a stream");
+        rgb.fill_async([4, 5, 6], &stream)
+            .expect("Should queue the fill kernel");
+        stream.synchronize().unwrap();
+        assert_eq!(rgb.to_vec().unwrap(), vec![[4, 5, 6]; 333]);
+    }
+}
@@ -0,0 +1,459 @@
+use std::ffi::c_void;
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::launch::LaunchConfig;
+use crate::module::Function;
+
+/// Attribute of a loaded kernel, see `hipFunction_attribute`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionAttribute {
+    /// Most threads a block launching the kernel can have.
+    MaxThreadsPerBlock,
+    /// Statically allocated shared memory per block in bytes.
+    SharedSizeBytes,
+    /// Constant memory used by the kernel in bytes.
+    ConstSizeBytes,
+    /// Private memory per thread in bytes.
+    LocalSizeBytes,
+    /// Registers used by each thread.
+    NumRegs,
+    /// Compute capability of the PTX the kernel was compiled from, as `major * 10 + minor`,
+    /// only meaningful on the NVIDIA platform.
+    PtxVersion,
+    /// Compute capability of the kernel binary, as `major * 10 + minor`, only meaningful on
+    /// the NVIDIA platform.
+    BinaryVersion,
+    /// 1 if global loads are cached in L1, 0 otherwise.
+    CacheModeCa,
+    /// Most dynamic shared memory per block in bytes a launch can request.
+    MaxDynamicSharedSizeBytes,
+    /// Preferred shared memory carveout in percent, -1 for the default.
+    PreferredSharedMemoryCarveout,
+}
+
+impl FunctionAttribute {
+    fn to_raw(self) -> hipFunction_attribute {
+        match self {
+            FunctionAttribute::MaxThreadsPerBlock => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_MAX_THREADS_PER_BLOCK
+            }
+            FunctionAttribute::SharedSizeBytes => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_SHARED_SIZE_BYTES
+            }
+            FunctionAttribute::ConstSizeBytes => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_CONST_SIZE_BYTES
+            }
+            FunctionAttribute::LocalSizeBytes => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_LOCAL_SIZE_BYTES
+            }
+            FunctionAttribute::NumRegs => hipFunction_attribute_HIP_FUNC_ATTRIBUTE_NUM_REGS,
+            FunctionAttribute::PtxVersion => hipFunction_attribute_HIP_FUNC_ATTRIBUTE_PTX_VERSION,
+            FunctionAttribute::BinaryVersion => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_BINARY_VERSION
+            }
+            FunctionAttribute::CacheModeCa => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_CACHE_MODE_CA
+            }
+            FunctionAttribute::MaxDynamicSharedSizeBytes => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_MAX_DYNAMIC_SHARED_SIZE_BYTES
+            }
+            FunctionAttribute::PreferredSharedMemoryCarveout => {
+                hipFunction_attribute_HIP_FUNC_ATTRIBUTE_PREFERRED_SHARED_MEMORY_CARVEOUT
+            }
+        }
+    }
+}
+
+/// Preferred split between L1 cache and shared memory of a kernel, see `hipFuncCache_t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheConfig {
+    /// No preference.
+    PreferNone,
+    /// Prefer more shared memory and less L1 cache.
+    PreferShared,
+    /// Prefer more L1 cache and less shared memory.
+    PreferL1,
+    /// Prefer as much L1 cache as shared memory.
+    PreferEqual,
+}
+
+impl CacheConfig {
+    fn to_raw(self) -> hipFuncCache_t {
+        match self {
+            CacheConfig::PreferNone => hipFuncCache_t_hipFuncCachePreferNone,
+            CacheConfig::PreferShared => hipFuncCache_t_hipFuncCachePreferShared,
+            CacheConfig::PreferL1 => hipFuncCache_t_hipFuncCachePreferL1,
+            CacheConfig::PreferEqual => hipFuncCache_t_hipFuncCachePreferEqual,
+        }
+    }
+}
+
+/// Shared memory bank size of a kernel, see `hipSharedMemConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedMemConfig {
+    /// Bank size of the device.
+    Default,
+    /// Banks of 4 bytes.
+    FourByte,
+    /// Banks of 8 bytes.
+    EightByte,
+}
+
+impl SharedMemConfig {
+    fn to_raw(self) -> hipSharedMemConfig {
+        match self {
+            SharedMemConfig::Default => hipSharedMemConfig_hipSharedMemBankSizeDefault,
+            SharedMemConfig::FourByte => hipSharedMemConfig_hipSharedMemBankSizeFourByte,
+            SharedMemConfig::EightByte => hipSharedMemConfig_hipSharedMemBankSizeEightByte,
+        }
+    }
+}
+
+/// Resource usage and launch limits of a kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionAttributes {
+    /// Most threads a block launching the kernel can have.
+    pub max_threads_per_block: u32,
+    /// Statically allocated shared memory per block in bytes.
+    pub shared_size_bytes: usize,
+    /// Constant memory used by the kernel in bytes.
+    pub const_size_bytes: usize,
+    /// Private memory per thread in bytes.
+    pub local_size_bytes: usize,
+    /// Registers used by each thread.
+    pub num_regs: u32,
+    /// Most dynamic shared memory per block in bytes a launch can request.
+    pub max_dynamic_shared_size_bytes: u32,
+    /// Preferred shared memory carveout in percent, -1 for the default.
+    pub preferred_shared_carveout: i32,
+    /// See [`FunctionAttribute::PtxVersion`].
+    pub ptx_version: i32,
+    /// See [`FunctionAttribute::BinaryVersion`].
+    pub binary_version: i32,
+    /// Whether global loads are cached in L1.
+    pub cache_mode_ca: bool,
+}
+
+impl FunctionAttributes {
+    /// Return the limits a launch of the kernel must fit.
+    pub fn launch_limits(&self) -> LaunchLimits {
+        LaunchLimits {
+            max_threads_per_block: self.max_threads_per_block,
+            max_dynamic_shared_size_bytes: Some(self.max_dynamic_shared_size_bytes),
+        }
+    }
+
+    /// Check that a launch with `config` fits the limits of the kernel, see
+    /// [`LaunchLimits::check`].
+    pub fn validate_launch(&self, config: &LaunchConfig) -> HipResult<()> {
+        self.launch_limits().check(config)
+    }
+}
+
+/// Attributes returned by `hipFuncGetAttributes`, see [`HostFunction::attributes`].
+impl From<hipFuncAttributes> for FunctionAttributes {
+    fn from(raw: hipFuncAttributes) -> Self {
+        Self {
+            max_threads_per_block: raw.maxThreadsPerBlock.max(0) as u32,
+            shared_size_bytes: raw.sharedSizeBytes,
+            const_size_bytes: raw.constSizeBytes,
+            local_size_bytes: raw.localSizeBytes,
+            num_regs: raw.numRegs.max(0) as u32,
+            max_dynamic_shared_size_bytes: raw.maxDynamicSharedSizeBytes.max(0) as u32,
+            preferred_shared_carveout: raw.preferredShmemCarveout,
+            ptx_version: raw.ptxVersion,
+            binary_version: raw.binaryVersion,
+            cache_mode_ca: raw.cacheModeCA != 0,
+        }
+    }
+}
+
+/// Limits a launch configuration must fit, from the attributes of a loaded kernel or from
+/// its code object metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LaunchLimits {
+    /// Most threads a block can have.
+    pub max_threads_per_block: u32,
+    /// Most dynamic shared memory per block in bytes, `None` when unknown.
+    pub max_dynamic_shared_size_bytes: Option<u32>,
+}
+
+impl LaunchLimits {
+    /// Check that a launch with `config` fits the limits, so that it does not fail with
+    /// `hipErrorInvalidConfiguration`.
+    pub fn check(&self, config: &LaunchConfig) -> HipResult<()> {
+        config.validate()?;
+        let block = config.block;
+        let threads = block.x as u64 * block.y as u64 * block.z as u64;
+        if threads > self.max_threads_per_block as u64 {
+            return Err(HipError::InvalidArgument(format!(
+                "block of {threads} threads ({}, {}, {}) exceeds the {} threads per block of \
+                 the kernel",
+                block.x, block.y, block.z, self.max_threads_per_block
+            )));
+        }
+        if let Some(max_shared_mem) = self.max_dynamic_shared_size_bytes {
+            if config.shared_mem > max_shared_mem {
+                return Err(HipError::InvalidArgument(format!(
+                    "{} bytes of dynamic shared memory exceed the {max_shared_mem} bytes the \
+                     kernel can use",
+                    config.shared_mem
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check `config` like [`LaunchLimits::check`], naming `kernel` in the error.
+    pub(crate) fn check_kernel(&self, kernel: &str, config: &LaunchConfig) -> HipResult<()> {
+        self.check(config).map_err(|err| match err {
+            HipError::InvalidArgument(message) => {
+                HipError::InvalidArgument(format!("kernel '{kernel}': {message}"))
+            }
+            err => err,
+        })
+    }
+}
+
+impl Function<'_> {
+    /// Return an attribute of the kernel with `hipFuncGetAttribute`.
+    pub fn attribute(&self, attribute: FunctionAttribute) -> HipResult<i32> {
+        let mut value = 0;
+        unsafe {
+            check(hipFuncGetAttribute(
+                &mut value,
+                attribute.to_raw(),
+                self.as_raw(),
+            ))?
+        };
+        Ok(value)
+    }
+
+    /// Return the attributes of the kernel, each queried with `hipFuncGetAttribute`.
+    ///
+    /// The attributes of module functions are fixed when the module is loaded, HIP only
+    /// changes them for kernels registered by hipcc host stubs, see [`HostFunction`].
+    pub fn attributes(&self) -> HipResult<FunctionAttributes> {
+        // `hipFuncGetAttributes` takes host stubs, modules only answer the attribute queries.
+        let size =
+            |attribute| -> HipResult<usize> { Ok(self.attribute(attribute)?.max(0) as usize) };
+        let count = |attribute| -> HipResult<u32> { Ok(self.attribute(attribute)?.max(0) as u32) };
+        Ok(FunctionAttributes {
+            max_threads_per_block: count(FunctionAttribute::MaxThreadsPerBlock)?,
+            shared_size_bytes: size(FunctionAttribute::SharedSizeBytes)?,
+            const_size_bytes: size(FunctionAttribute::ConstSizeBytes)?,
+            local_size_bytes: size(FunctionAttribute::LocalSizeBytes)?,
+            num_regs: count(FunctionAttribute::NumRegs)?,
+            max_dynamic_shared_size_bytes: count(FunctionAttribute::MaxDynamicSharedSizeBytes)?,
+            preferred_shared_carveout: self
+                .attribute(FunctionAttribute::PreferredSharedMemoryCarveout)?,
+            ptx_version: self.attribute(FunctionAttribute::PtxVersion)?,
+            binary_version: self.attribute(FunctionAttribute::BinaryVersion)?,
+            cache_mode_ca: self.attribute(FunctionAttribute::CacheModeCa)? != 0,
+        })
+    }
+
+    /// Return the limits a launch of the kernel must fit, queried the first time any
+    /// function of the module looks them up for this kernel and kept by the module.
+    pub fn launch_limits(&self) -> HipResult<LaunchLimits> {
+        let cache = || {
+            self.module
+                .launch_limits
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+        };
+        if let Some(limits) = cache().get(&self.as_raw()) {
+            return Ok(*limits);
+        }
+        let count = |attribute| -> HipResult<u32> { Ok(self.attribute(attribute)?.max(0) as u32) };
+        let limits = LaunchLimits {
+            max_threads_per_block: count(FunctionAttribute::MaxThreadsPerBlock)?,
+            max_dynamic_shared_size_bytes: Some(count(
+                FunctionAttribute::MaxDynamicSharedSizeBytes,
+            )?),
+        };
+        cache().insert(self.as_raw(), limits);
+        Ok(limits)
+    }
+
+    /// Check that a launch with `config` fits the limits of the kernel, see
+    /// [`LaunchLimits::check`].
+    ///
+    /// Every launch of the function runs this check first.
+    pub fn validate_launch(&self, config: &LaunchConfig) -> HipResult<()> {
+        self.launch_limits()?.check_kernel(self.name(), config)
+    }
+}
+
+/// Kernel registered by a hipcc host stub, the only kind of kernel whose attributes and
+/// cache configuration HIP can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostFunction {
+    ptr: *const c_void,
+}
+
+impl HostFunction {
+    /// Wrap the address of the host stub of a kernel.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be the host stub of a kernel registered in this process, for instance a
+    /// `__global__` function compiled by hipcc and linked into the program.
+    pub unsafe fn from_ptr(ptr: *const c_void) -> Self {
+        Self { ptr }
+    }
+
+    /// Return the address of the host stub.
+    pub fn as_ptr(&self) -> *const c_void {
+        self.ptr
+    }
+
+    /// Return the attributes of the kernel with `hipFuncGetAttributes`.
+    pub fn attributes(&self) -> HipResult<FunctionAttributes> {
+        let mut raw: hipFuncAttributes = unsafe { zeroed_raw() };
+        unsafe { check(hipFuncGetAttributes(&mut raw, self.ptr))? };
+        Ok(raw.into())
+    }
+
+    /// Set the most dynamic shared memory per block in bytes launches can request, with
+    /// `hipFuncSetAttribute`.
+    pub fn set_max_dynamic_shared_mem(&self, bytes: u32) -> HipResult<()> {
+        self.set_attribute(
+            hipFuncAttribute_hipFuncAttributeMaxDynamicSharedMemorySize,
+            to_int(bytes)?,
+        )
+    }
+
+    /// Set the preferred shared memory carveout in percent of the maximum, -1 for the
+    /// default, with `hipFuncSetAttribute`.
+    pub fn set_preferred_shared_carveout(&self, percent: i32) -> HipResult<()> {
+        if !(-1..=100).contains(&percent) {
+            return Err(HipError::InvalidArgument(format!(
+                "shared memory carveout {percent} should be a percentage or -1"
+            )));
+        }
+        self.set_attribute(
+            hipFuncAttribute_hipFuncAttributePreferredSharedMemoryCarveout,
+            percent,
+        )
+    }
+
+    fn set_attribute(&self, attribute: hipFuncAttribute, value: i32) -> HipResult<()> {
+        unsafe { check(hipFuncSetAttribute(self.ptr, attribute, value)) }
+    }
+
+    /// Set the preferred split between L1 cache and shared memory with
+    /// `hipFuncSetCacheConfig`.
+    ///
+    /// AMD devices have no configurable split, this is a no-op there and is accepted for
+    /// portability.
+    pub fn set_cache_config(&self, config: CacheConfig) -> HipResult<()> {
+        unsafe { check(hipFuncSetCacheConfig(self.ptr, config.to_raw())) }
+    }
+
+    /// Set the shared memory bank size with `hipFuncSetSharedMemConfig`.
+    ///
+    /// AMD devices have a fixed bank size, this is a no-op there and is accepted for
+    /// portability.
+    pub fn set_shared_mem_config(&self, config: SharedMemConfig) -> HipResult<()> {
+        unsafe { check(hipFuncSetSharedMemConfig(self.ptr, config.to_raw())) }
+    }
+}
+
+fn to_int(bytes: u32) -> HipResult<i32> {
+    i32::try_from(bytes).map_err(|_| {
+        HipError::InvalidArgument(format!("{bytes} bytes of shared memory is too large"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn attributes() -> FunctionAttributes {
+        FunctionAttributes {
+            max_threads_per_block: 256,
+            shared_size_bytes: 1024,
+            const_size_bytes: 0,
+            local_size_bytes: 0,
+            num_regs: 32,
+            max_dynamic_shared_size_bytes: 64 * 1024 - 1024,
+            preferred_shared_carveout: -1,
+            ptx_version: 0,
+            binary_version: 0,
+            cache_mode_ca: false,
+        }
+    }
+
+    #[rstest]
+    #[case::fits((256, 1, 1), 0, true)]
+    #[case::fits_2d((16, 16, 1), 63 * 1024, true)]
+    #[case::too_many_threads((16, 16, 2), 0, false)]
+    #[case::too_much_shared_memory((64, 1, 1), 63 * 1024 + 1, false)]
+    #[case::zero_block((0, 1, 1), 0, false)]
+    fn test_validate_launch(
+        #[case] block: (u32, u32, u32),
+        #[case] shared_mem: u32,
+        #[case] valid: bool,
+    ) {
+        let config = LaunchConfig::new(8, block).with_shared_mem(shared_mem);
+        assert_eq!(attributes().validate_launch(&config).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_validate_launch_message() {
+        let config = LaunchConfig::new(1, (32, 32, 1));
+        let Err(HipError::InvalidArgument(message)) = attributes().validate_launch(&config) else {
+            panic!("launch should be rejected");
+        };
+        assert_eq!(
+            message,
+            "block of 1024 threads (32, 32, 1) exceeds the 256 threads per block of the kernel"
+        );
+    }
+
+    #[test]
+    fn test_unknown_shared_mem_limit_is_not_checked() {
+        let limits = LaunchLimits {
+            max_threads_per_block: 256,
+            max_dynamic_shared_size_bytes: None,
+        };
+        let config = LaunchConfig::new(1, 256).with_shared_mem(u32::MAX);
+        assert!(limits.check(&config).is_ok());
+    }
+
+    #[test]
+    fn test_check_kernel_names_the_kernel() {
+        let config = LaunchConfig::new(1, 64).with_shared_mem(u32::MAX);
+        let Err(HipError::InvalidArgument(message)) =
+            attributes().launch_limits().check_kernel("axpb", &config)
+        else {
+            panic!("launch should be rejected");
+        };
+        assert!(message.starts_with("kernel 'axpb': "));
+    }
+
+    #[test]
+    fn test_from_raw_attributes() {
+        let raw = hipFuncAttributes {
+            binaryVersion: 90,
+            cacheModeCA: 1,
+            constSizeBytes: 16,
+            localSizeBytes: 8,
+            maxDynamicSharedSizeBytes: 65536,
+            maxThreadsPerBlock: 1024,
+            numRegs: 40,
+            preferredShmemCarveout: -1,
+            ptxVersion: 0,
+            sharedSizeBytes: 512,
+        };
+        let attributes = FunctionAttributes::from(raw);
+        assert_eq!(attributes.max_threads_per_block, 1024);
+        assert_eq!(attributes.num_regs, 40);
+        assert_eq!(attributes.max_dynamic_shared_size_bytes, 65536);
+        assert_eq!(attributes.shared_size_bytes, 512);
+        assert!(attributes.cache_mode_ca);
+    }
+}
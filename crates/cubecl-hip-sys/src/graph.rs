@@ -0,0 +1,552 @@
+use std::{ffi::c_void, marker::PhantomData, ptr};
+
+use crate::bindings::*;
+use crate::device_buffer::DeviceBuffer;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::KernelArgValue;
+use crate::launch::{KernelArgs, LaunchConfig};
+use crate::module::Function;
+use crate::stream::Stream;
+
+/// Node of a graph, of any kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GraphNode {
+    raw: hipGraphNode_t,
+}
+
+impl GraphNode {
+    /// Return the underlying `hipGraphNode_t`.
+    pub fn as_raw(&self) -> hipGraphNode_t {
+        self.raw
+    }
+}
+
+macro_rules! typed_graph_node {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct $name(GraphNode);
+
+        impl $name {
+            /// Return the underlying `hipGraphNode_t`.
+            pub fn as_raw(&self) -> hipGraphNode_t {
+                self.0.raw
+            }
+        }
+
+        impl From<$name> for GraphNode {
+            fn from(node: $name) -> Self {
+                node.0
+            }
+        }
+    };
+}
+
+typed_graph_node!(
+    /// Kernel launch node, whose parameters can be updated in an [`ExecGraph`].
+    KernelNode
+);
+typed_graph_node!(
+    /// Device to device copy node.
+    MemcpyNode
+);
+typed_graph_node!(
+    /// Memset node.
+    MemsetNode
+);
+typed_graph_node!(
+    /// Host callback node.
+    HostNode
+);
+typed_graph_node!(
+    /// Empty node, used to join dependencies.
+    EmptyNode
+);
+#[cfg(has_batch_mem_op)]
+typed_graph_node!(
+    /// Batch of stream memory operations node.
+    BatchMemOpNode
+);
+
+type HostCallback<'a> = Box<dyn Fn() + Send + Sync + 'a>;
+
+/// Graph under construction, destroyed on drop.
+///
+/// The lifetime `'a` covers the functions, buffers and callbacks the nodes reference, which
+/// must outlive the graph and every [`ExecGraph`] instantiated from it.
+pub struct GraphBuilder<'a> {
+    raw: hipGraph_t,
+    // Host nodes point to the inner boxes, which must not move when the vector grows.
+    #[allow(clippy::vec_box)]
+    callbacks: Vec<Box<HostCallback<'a>>>,
+    _borrows: PhantomData<&'a ()>,
+}
+
+impl<'a> GraphBuilder<'a> {
+    /// Create an empty graph with `hipGraphCreate`.
+    pub fn new() -> HipResult<Self> {
+        let mut raw: hipGraph_t = ptr::null_mut();
+        unsafe { check(hipGraphCreate(&mut raw, 0))? };
+        Ok(Self {
+            raw,
+            callbacks: Vec::new(),
+            _borrows: PhantomData,
+        })
+    }
+
+    /// Take ownership of a raw graph, destroyed when the returned value is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `raw` should be a valid graph that is not destroyed elsewhere, and the resources its
+    /// nodes reference should outlive `'a`.
+    pub unsafe fn from_raw(raw: hipGraph_t) -> Self {
+        Self {
+            raw,
+            callbacks: Vec::new(),
+            _borrows: PhantomData,
+        }
+    }
+
+    /// Return the underlying `hipGraph_t`.
+    pub fn as_raw(&self) -> hipGraph_t {
+        self.raw
+    }
+
+    /// Add a kernel launch node depending on `deps` with `hipGraphAddKernelNode`.
+    ///
+    /// The argument values are copied into the node when this call returns.
+    ///
+    /// # Safety
+    ///
+    /// The arguments must match the kernel signature in number, order and layout, and any
+    /// device memory they reference must remain valid while the graph can be launched.
+    pub unsafe fn add_kernel<A: KernelArgs + ?Sized>(
+        &mut self,
+        deps: &[GraphNode],
+        function: &'a Function<'_>,
+        config: &LaunchConfig,
+        args: &A,
+    ) -> HipResult<KernelNode> {
+        function.validate_launch(config)?;
+        let mut kernel_params = args.as_kernel_params();
+        let params = kernel_node_params(function, config, &mut kernel_params);
+        let deps = raw_nodes(deps);
+        let mut raw: hipGraphNode_t = ptr::null_mut();
+        check(hipGraphAddKernelNode(
+            &mut raw,
+            self.raw,
+            deps.as_ptr(),
+            deps.len(),
+            &params,
+        ))?;
+        Ok(KernelNode(GraphNode { raw }))
+    }
+
+    /// Add a node copying `src` into `dst` depending on `deps` with `hipGraphAddMemcpyNode1D`.
+    pub fn add_memcpy<T: KernelArgValue>(
+        &mut self,
+        deps: &[GraphNode],
+        dst: &'a DeviceBuffer<T>,
+        src: &'a DeviceBuffer<T>,
+    ) -> HipResult<MemcpyNode> {
+        if dst.len() != src.len() {
+            return Err(HipError::InvalidArgument(format!(
+                "cannot copy {} elements into a buffer of {} elements",
+                src.len(),
+                dst.len()
+            )));
+        }
+        let deps = raw_nodes(deps);
+        let mut raw: hipGraphNode_t = ptr::null_mut();
+        unsafe {
+            check(hipGraphAddMemcpyNode1D(
+                &mut raw,
+                self.raw,
+                deps.as_ptr(),
+                deps.len(),
+                dst.as_ptr() as *mut c_void,
+                src.as_ptr() as *const c_void,
+                dst.size_in_bytes(),
+                hipMemcpyKind_hipMemcpyDeviceToDevice,
+            ))?
+        };
+        Ok(MemcpyNode(GraphNode { raw }))
+    }
+
+    /// Add a node setting every byte of `dst` to `value` depending on `deps` with
+    /// `hipGraphAddMemsetNode`.
+    pub fn add_memset<T: KernelArgValue>(
+        &mut self,
+        deps: &[GraphNode],
+        dst: &'a DeviceBuffer<T>,
+        value: u8,
+    ) -> HipResult<MemsetNode> {
+        let params = hipMemsetParams {
+            dst: dst.as_ptr() as *mut c_void,
+            elementSize: 1,
+            height: 1,
+            pitch: 0,
+            value: value as u32,
+            width: dst.size_in_bytes(),
+        };
+        let deps = raw_nodes(deps);
+        let mut raw: hipGraphNode_t = ptr::null_mut();
+        unsafe {
+            check(hipGraphAddMemsetNode(
+                &mut raw,
+                self.raw,
+                deps.as_ptr(),
+                deps.len(),
+                &params,
+            ))?
+        };
+        Ok(MemsetNode(GraphNode { raw }))
+    }
+
+    /// Add a node calling `callback` on the host depending on `deps` with
+    /// `hipGraphAddHostNode`.
+    ///
+    /// The callback runs on a runtime thread and must not call any HIP function.
+    pub fn add_host<F>(&mut self, deps: &[GraphNode], callback: F) -> HipResult<HostNode>
+    where
+        F: Fn() + Send + Sync + 'a,
+    {
+        let callback: Box<HostCallback<'a>> = Box::new(Box::new(callback));
+        let params = hipHostNodeParams {
+            fn_: Some(host_trampoline),
+            userData: &*callback as *const HostCallback<'a> as *mut c_void,
+        };
+        let deps = raw_nodes(deps);
+        let mut raw: hipGraphNode_t = ptr::null_mut();
+        unsafe {
+            check(hipGraphAddHostNode(
+                &mut raw,
+                self.raw,
+                deps.as_ptr(),
+                deps.len(),
+                &params,
+            ))?
+        };
+        self.callbacks.push(callback);
+        Ok(HostNode(GraphNode { raw }))
+    }
+
+    /// Add an empty node depending on `deps` with `hipGraphAddEmptyNode`.
+    pub fn add_empty(&mut self, deps: &[GraphNode]) -> HipResult<EmptyNode> {
+        let deps = raw_nodes(deps);
+        let mut raw: hipGraphNode_t = ptr::null_mut();
+        unsafe {
+            check(hipGraphAddEmptyNode(
+                &mut raw,
+                self.raw,
+                deps.as_ptr(),
+                deps.len(),
+            ))?
+        };
+        Ok(EmptyNode(GraphNode { raw }))
+    }
+
+    /// Add a node running the waits and writes of `ops` in order depending on `deps` with
+    /// `hipGraphAddBatchMemOpNode`, in the context current on the calling thread.
+    ///
+    /// The operations are copied into the node when this call returns.
+    #[cfg(has_batch_mem_op)]
+    pub fn add_batch_mem_ops(
+        &mut self,
+        deps: &[GraphNode],
+        ops: &crate::stream_mem_op::BatchMemOps<'a>,
+    ) -> HipResult<BatchMemOpNode> {
+        ops.check_len()?;
+        let params = hipBatchMemOpNodeParams {
+            ctx: crate::context::current_context()?.unwrap_or(ptr::null_mut()),
+            count: ops.len() as u32,
+            // The runtime only reads the operations.
+            paramArray: ops.as_raw().as_ptr() as *mut hipStreamBatchMemOpParams,
+            flags: 0,
+        };
+        let deps = raw_nodes(deps);
+        let mut raw: hipGraphNode_t = ptr::null_mut();
+        unsafe {
+            check(hipGraphAddBatchMemOpNode(
+                &mut raw,
+                self.raw,
+                deps.as_ptr(),
+                deps.len(),
+                &params,
+            ))?
+        };
+        Ok(BatchMemOpNode(GraphNode { raw }))
+    }
+
+    /// Make `to` depend on `from` with `hipGraphAddDependencies`.
+    pub fn add_dependency(
+        &mut self,
+        from: impl Into<GraphNode>,
+        to: impl Into<GraphNode>,
+    ) -> HipResult<()> {
+        let from = from.into().raw;
+        let to = to.into().raw;
+        unsafe { check(hipGraphAddDependencies(self.raw, &from, &to, 1)) }
+    }
+
+    /// Instantiate the graph with `hipGraphInstantiateWithFlags`.
+    pub fn instantiate(&self) -> HipResult<ExecGraph<'_>> {
+        let mut raw: hipGraphExec_t = ptr::null_mut();
+        unsafe { check(hipGraphInstantiateWithFlags(&mut raw, self.raw, 0))? };
+        Ok(ExecGraph {
+            raw,
+            _graph: PhantomData,
+        })
+    }
+}
+
+impl Drop for GraphBuilder<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            hipGraphDestroy(self.raw);
+        }
+    }
+}
+
+impl std::fmt::Debug for GraphBuilder<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GraphBuilder")
+            .field("raw", &self.raw)
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
+}
+
+/// Instantiated graph, destroyed on drop.
+///
+/// It borrows the graph it was instantiated from, which keeps the resources of the nodes alive.
+#[derive(Debug)]
+pub struct ExecGraph<'g> {
+    raw: hipGraphExec_t,
+    _graph: PhantomData<&'g ()>,
+}
+
+impl<'g> ExecGraph<'g> {
+    /// Return the underlying `hipGraphExec_t`.
+    pub fn as_raw(&self) -> hipGraphExec_t {
+        self.raw
+    }
+
+    /// Launch the whole graph on `stream` with `hipGraphLaunch`.
+    pub fn launch(&self, stream: &Stream) -> HipResult<()> {
+        unsafe { check(hipGraphLaunch(self.raw, stream.as_raw())) }
+    }
+
+    /// Upload the graph to the device ahead of its first launch with `hipGraphUpload`.
+    pub fn upload(&self, stream: &Stream) -> HipResult<()> {
+        unsafe { check(hipGraphUpload(self.raw, stream.as_raw())) }
+    }
+
+    /// Change the function, configuration and arguments of a kernel node for the next
+    /// launches with `hipGraphExecKernelNodeSetParams`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`GraphBuilder::add_kernel`], `function` must outlive the executable graph.
+    pub unsafe fn set_kernel_params<A: KernelArgs + ?Sized>(
+        &mut self,
+        node: KernelNode,
+        function: &'g Function<'_>,
+        config: &LaunchConfig,
+        args: &A,
+    ) -> HipResult<()> {
+        function.validate_launch(config)?;
+        let mut kernel_params = args.as_kernel_params();
+        let params = kernel_node_params(function, config, &mut kernel_params);
+        check(hipGraphExecKernelNodeSetParams(
+            self.raw,
+            node.as_raw(),
+            &params,
+        ))
+    }
+
+    /// Update the parameters of all the nodes from `graph`, which must have the same topology
+    /// as the instantiated graph, with `hipGraphExecUpdate`.
+    pub fn update(&mut self, graph: &'g GraphBuilder<'_>) -> HipResult<()> {
+        let mut error_node: hipGraphNode_t = ptr::null_mut();
+        let mut result = hipGraphExecUpdateResult_hipGraphExecUpdateSuccess;
+        let status =
+            unsafe { hipGraphExecUpdate(self.raw, graph.raw, &mut error_node, &mut result) };
+        if result != hipGraphExecUpdateResult_hipGraphExecUpdateSuccess {
+            return Err(HipError::InvalidArgument(format!(
+                "cannot update executable graph: {}",
+                update_failure_reason(result)
+            )));
+        }
+        check(status)
+    }
+}
+
+impl Drop for ExecGraph<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            hipGraphExecDestroy(self.raw);
+        }
+    }
+}
+
+fn kernel_node_params(
+    function: &Function<'_>,
+    config: &LaunchConfig,
+    kernel_params: &mut [*mut c_void],
+) -> hipKernelNodeParams {
+    hipKernelNodeParams {
+        blockDim: config.block,
+        extra: ptr::null_mut(),
+        func: function.as_raw() as *mut c_void,
+        gridDim: config.grid,
+        kernelParams: kernel_params.as_mut_ptr(),
+        sharedMemBytes: config.shared_mem,
+    }
+}
+
+fn raw_nodes(nodes: &[GraphNode]) -> Vec<hipGraphNode_t> {
+    nodes.iter().map(GraphNode::as_raw).collect()
+}
+
+unsafe extern "C" fn host_trampoline(user_data: *mut c_void) {
+    let callback = &*(user_data as *const HostCallback<'_>);
+    // Unwinding into the runtime is undefined behavior.
+    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(callback)).is_err() {
+        std::process::abort();
+    }
+}
+
+/// Return a description of a failed `hipGraphExecUpdate`.
+fn update_failure_reason(result: hipGraphExecUpdateResult) -> &'static str {
+    match result {
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorTopologyChanged => "the topology changed",
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorNodeTypeChanged => {
+            "the type of a node changed"
+        }
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorFunctionChanged => {
+            "the function of a kernel node changed"
+        }
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorParametersChanged => {
+            "the parameters of a node changed in an unsupported way"
+        }
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorNotSupported => {
+            "the update is not supported"
+        }
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorUnsupportedFunctionChange => {
+            "the function of a kernel node changed in an unsupported way"
+        }
+        _ => "unexpected error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorTopologyChanged,
+        "the topology changed"
+    )]
+    #[case(
+        hipGraphExecUpdateResult_hipGraphExecUpdateErrorFunctionChanged,
+        "the function of a kernel node changed"
+    )]
+    #[case(hipGraphExecUpdateResult_hipGraphExecUpdateError, "unexpected error")]
+    fn test_update_failure_reason(
+        #[case] result: hipGraphExecUpdateResult,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(update_failure_reason(result), expected);
+    }
+
+    #[test]
+    fn test_host_trampoline_calls_callback() {
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let callback: HostCallback<'_> = Box::new(|| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        });
+        unsafe {
+            host_trampoline(&callback as *const HostCallback<'_> as *mut c_void);
+            host_trampoline(&callback as *const HostCallback<'_> as *mut c_void);
+        }
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_graph_launch_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let src = DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+        let dst = DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let mut graph = GraphBuilder::new().expect("Should create the graph");
+        let memset = graph.add_memset(&[], &src, 0xab).unwrap();
+        let memcpy = graph.add_memcpy(&[memset.into()], &dst, &src).unwrap();
+        graph
+            .add_host(&[memcpy.into()], || {
+                calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            })
+            .unwrap();
+
+        let exec = graph.instantiate().expect("Should instantiate the graph");
+        let stream = Stream::new().expect("Should create a stream");
+        exec.launch(&stream).expect("Should launch the graph");
+        exec.launch(&stream).expect("Should launch the graph again");
+        stream.synchronize().unwrap();
+
+        assert_eq!(dst.to_vec().unwrap(), vec![0xabababab; 64]);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_kernel_node_params_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let source = r#"
+extern "C" __global__ void set(unsigned int *data, unsigned int value) {
+  data[threadIdx.x] = value;
+}
+"#;
+        let mut program = crate::Program::new(source, "set.hip").expect("Should create");
+        program
+            .compile::<&str>(&[])
+            .expect("Should compile the program");
+        let module = crate::Module::from_program(&program).expect("Should load the module");
+        let function = module.function("set").unwrap();
+        let config = LaunchConfig::new(1, 64);
+        let first = DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+        let second = DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+
+        let mut graph = GraphBuilder::new().expect("Should create the graph");
+        let node = unsafe { graph.add_kernel(&[], &function, &config, &(&first, 1u32)) }
+            .expect("Should add the kernel node");
+        // Same topology as `graph` with other arguments, for `hipGraphExecUpdate`.
+        let mut updated = GraphBuilder::new().expect("Should create the graph");
+        unsafe { updated.add_kernel(&[], &function, &config, &(&first, 3u32)) }
+            .expect("Should add the kernel node");
+
+        let mut exec = graph.instantiate().expect("Should instantiate the graph");
+        let stream = Stream::new().expect("Should create a stream");
+        exec.launch(&stream).expect("Should launch the graph");
+        stream.synchronize().unwrap();
+        assert_eq!(first.to_vec().unwrap(), vec![1; 64]);
+
+        unsafe { exec.set_kernel_params(node, &function, &config, &(&second, 2u32)) }
+            .expect("Should swap the kernel arguments");
+        exec.launch(&stream).expect("Should launch the graph again");
+        stream.synchronize().unwrap();
+        assert_eq!(first.to_vec().unwrap(), vec![1; 64]);
+        assert_eq!(second.to_vec().unwrap(), vec![2; 64]);
+
+        exec.update(&updated).expect("Should update the graph");
+        exec.launch(&stream)
+            .expect("Should launch the updated graph");
+        stream.synchronize().unwrap();
+        assert_eq!(first.to_vec().unwrap(), vec![3; 64]);
+        assert_eq!(second.to_vec().unwrap(), vec![2; 64]);
+    }
+}
@@ -0,0 +1,505 @@
+//! Description of a graph, exported to Graphviz DOT or JSON in pure Rust for inspection and diffing.
+
+mod json;
+
+use std::{
+    ffi::{c_void, CStr},
+    fmt::Write,
+    ptr,
+};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::graph::GraphBuilder;
+use json::Value;
+
+/// Operation performed by a graph node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Kernel {
+        /// Kernel name, `None` when the runtime cannot resolve it.
+        name: Option<String>,
+        grid: [u32; 3],
+        block: [u32; 3],
+        /// Dynamic shared memory size per block in bytes.
+        shared_mem: u32,
+    },
+    Memcpy {
+        /// Number of bytes copied.
+        bytes: usize,
+    },
+    Memset {
+        /// Number of bytes set.
+        bytes: usize,
+        value: u32,
+    },
+    Host,
+    Empty,
+    /// Any other node type, by name, e.g. `event_record`.
+    Other(String),
+}
+
+impl NodeKind {
+    fn type_name(&self) -> &str {
+        match self {
+            NodeKind::Kernel { .. } => "kernel",
+            NodeKind::Memcpy { .. } => "memcpy",
+            NodeKind::Memset { .. } => "memset",
+            NodeKind::Host => "host",
+            NodeKind::Empty => "empty",
+            NodeKind::Other(name) => name,
+        }
+    }
+}
+
+/// Nodes and edges of a graph.
+///
+/// Nodes are identified by their index in [`GraphDescription::nodes`], in the order returned
+/// by `hipGraphGetNodes`. Edges are `(from, to)` pairs of node indices, sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphDescription {
+    pub nodes: Vec<NodeKind>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl GraphDescription {
+    /// Describe `graph` with `hipGraphGetNodes`, `hipGraphGetEdges` and the parameters of each
+    /// node.
+    pub fn from_graph(graph: &GraphBuilder<'_>) -> HipResult<Self> {
+        let raw_graph = graph.as_raw();
+        let nodes = unsafe {
+            let mut count = 0;
+            check(hipGraphGetNodes(raw_graph, ptr::null_mut(), &mut count))?;
+            let mut nodes = vec![ptr::null_mut(); count];
+            check(hipGraphGetNodes(raw_graph, nodes.as_mut_ptr(), &mut count))?;
+            nodes.truncate(count);
+            nodes
+        };
+        let mut edges = unsafe {
+            let mut count = 0;
+            check(hipGraphGetEdges(
+                raw_graph,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut count,
+            ))?;
+            let mut from = vec![ptr::null_mut(); count];
+            let mut to = vec![ptr::null_mut(); count];
+            check(hipGraphGetEdges(
+                raw_graph,
+                from.as_mut_ptr(),
+                to.as_mut_ptr(),
+                &mut count,
+            ))?;
+            let index = |node: hipGraphNode_t| {
+                nodes.iter().position(|n| *n == node).ok_or_else(|| {
+                    HipError::InvalidArgument("graph edge references an unknown node".to_string())
+                })
+            };
+            from.iter()
+                .zip(&to)
+                .take(count)
+                .map(|(from, to)| Ok((index(*from)?, index(*to)?)))
+                .collect::<HipResult<Vec<_>>>()?
+        };
+        edges.sort_unstable();
+        Ok(Self {
+            nodes: nodes
+                .into_iter()
+                .map(describe_node)
+                .collect::<HipResult<_>>()?,
+            edges,
+        })
+    }
+
+    /// Return the graph in the Graphviz DOT language.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        for (index, node) in self.nodes.iter().enumerate() {
+            let (shape, label) = match node {
+                NodeKind::Kernel {
+                    name,
+                    grid,
+                    block,
+                    shared_mem,
+                } => (
+                    "box",
+                    format!(
+                        "{}\\ngrid {grid:?} block {block:?}\\nshared {shared_mem} B",
+                        dot_escape(name.as_deref().unwrap_or("<unknown kernel>"))
+                    ),
+                ),
+                NodeKind::Memcpy { bytes } => ("ellipse", format!("memcpy {bytes} B")),
+                NodeKind::Memset { bytes, value } => {
+                    ("ellipse", format!("memset {bytes} B to {value}"))
+                }
+                other => ("ellipse", dot_escape(other.type_name())),
+            };
+            let _ = writeln!(out, "  n{index} [shape={shape}, label=\"{label}\"];");
+        }
+        for (from, to) in &self.edges {
+            let _ = writeln!(out, "  n{from} -> n{to};");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Return the graph as a JSON document, read back with [`GraphDescription::from_json`].
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"nodes\":[");
+        for (index, node) in self.nodes.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"kind\":");
+            json::write_str(&mut out, node.type_name());
+            match node {
+                NodeKind::Kernel {
+                    name,
+                    grid,
+                    block,
+                    shared_mem,
+                } => {
+                    out.push_str(",\"name\":");
+                    match name {
+                        Some(name) => json::write_str(&mut out, name),
+                        None => out.push_str("null"),
+                    }
+                    let _ = write!(
+                        out,
+                        ",\"grid\":[{},{},{}],\"block\":[{},{},{}],\"shared_mem\":{shared_mem}",
+                        grid[0], grid[1], grid[2], block[0], block[1], block[2]
+                    );
+                }
+                NodeKind::Memcpy { bytes } => {
+                    let _ = write!(out, ",\"bytes\":{bytes}");
+                }
+                NodeKind::Memset { bytes, value } => {
+                    let _ = write!(out, ",\"bytes\":{bytes},\"value\":{value}");
+                }
+                _ => {}
+            }
+            out.push('}');
+        }
+        out.push_str("],\"edges\":[");
+        for (index, (from, to)) in self.edges.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "[{from},{to}]");
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Read a description written by [`GraphDescription::to_json`].
+    pub fn from_json(text: &str) -> HipResult<Self> {
+        let document = json::parse(text)?;
+        let nodes = field(&document, "nodes")?
+            .as_array()
+            .ok_or_else(|| invalid("'nodes' should be an array"))?
+            .iter()
+            .map(node_from_json)
+            .collect::<HipResult<Vec<_>>>()?;
+        let edges = field(&document, "edges")?
+            .as_array()
+            .ok_or_else(|| invalid("'edges' should be an array"))?
+            .iter()
+            .map(|edge| {
+                let [from, to] = u64_array(edge, "edge")?;
+                let (from, to) = (from as usize, to as usize);
+                if from >= nodes.len() || to >= nodes.len() {
+                    return Err(invalid(&format!("edge ({from}, {to}) is out of bounds")));
+                }
+                Ok((from, to))
+            })
+            .collect::<HipResult<Vec<_>>>()?;
+        Ok(Self { nodes, edges })
+    }
+
+    /// Return a human readable list of the differences with `other`, empty when both are equal.
+    pub fn diff(&self, other: &GraphDescription) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.nodes.len() != other.nodes.len() {
+            differences.push(format!(
+                "node count: {} != {}",
+                self.nodes.len(),
+                other.nodes.len()
+            ));
+        }
+        for (index, (left, right)) in self.nodes.iter().zip(&other.nodes).enumerate() {
+            if left != right {
+                differences.push(format!("node {index}: {left:?} != {right:?}"));
+            }
+        }
+        for edge in &self.edges {
+            if !other.edges.contains(edge) {
+                differences.push(format!("edge {edge:?} only in left graph"));
+            }
+        }
+        for edge in &other.edges {
+            if !self.edges.contains(edge) {
+                differences.push(format!("edge {edge:?} only in right graph"));
+            }
+        }
+        differences
+    }
+}
+
+impl GraphBuilder<'_> {
+    /// Describe the nodes and edges of the graph, see [`GraphDescription::from_graph`].
+    pub fn describe(&self) -> HipResult<GraphDescription> {
+        GraphDescription::from_graph(self)
+    }
+}
+
+fn describe_node(node: hipGraphNode_t) -> HipResult<NodeKind> {
+    let mut node_type = 0;
+    unsafe { check(hipGraphNodeGetType(node, &mut node_type))? };
+    let kind = match node_type {
+        hipGraphNodeType_hipGraphNodeTypeKernel => {
+            let mut params: hipKernelNodeParams = unsafe { zeroed_raw() };
+            unsafe { check(hipGraphKernelNodeGetParams(node, &mut params))? };
+            NodeKind::Kernel {
+                name: kernel_name(params.func),
+                grid: [params.gridDim.x, params.gridDim.y, params.gridDim.z],
+                block: [params.blockDim.x, params.blockDim.y, params.blockDim.z],
+                shared_mem: params.sharedMemBytes,
+            }
+        }
+        hipGraphNodeType_hipGraphNodeTypeMemcpy => {
+            let mut params: hipMemcpy3DParms = unsafe { zeroed_raw() };
+            unsafe { check(hipGraphMemcpyNodeGetParams(node, &mut params))? };
+            let extent = params.extent;
+            NodeKind::Memcpy {
+                bytes: extent.width * extent.height.max(1) * extent.depth.max(1),
+            }
+        }
+        hipGraphNodeType_hipGraphNodeTypeMemset => {
+            let mut params: hipMemsetParams = unsafe { zeroed_raw() };
+            unsafe { check(hipGraphMemsetNodeGetParams(node, &mut params))? };
+            NodeKind::Memset {
+                bytes: params.width * params.height.max(1) * params.elementSize as usize,
+                value: params.value,
+            }
+        }
+        hipGraphNodeType_hipGraphNodeTypeHost => NodeKind::Host,
+        hipGraphNodeType_hipGraphNodeTypeEmpty => NodeKind::Empty,
+        other => NodeKind::Other(node_type_name(other).to_string()),
+    };
+    Ok(kind)
+}
+
+/// Return the name of the kernel of a kernel node, launched either from a module or from a
+/// registered host function.
+///
+/// `hipKernelNameRefByPtr` looks `func` up in the registry of host functions and returns null
+/// for module functions, which only then go through `hipKernelNameRef`: it reads its argument
+/// as a module function and must not be given a host function.
+fn kernel_name(func: *mut c_void) -> Option<String> {
+    if func.is_null() {
+        return None;
+    }
+    unsafe {
+        let mut name = hipKernelNameRefByPtr(func, ptr::null_mut());
+        if name.is_null() {
+            name = hipKernelNameRef(func as hipFunction_t);
+        }
+        (!name.is_null()).then(|| CStr::from_ptr(name).to_string_lossy().into_owned())
+    }
+}
+
+fn node_type_name(node_type: hipGraphNodeType) -> &'static str {
+    match node_type {
+        hipGraphNodeType_hipGraphNodeTypeKernel => "kernel",
+        hipGraphNodeType_hipGraphNodeTypeMemcpy => "memcpy",
+        hipGraphNodeType_hipGraphNodeTypeMemset => "memset",
+        hipGraphNodeType_hipGraphNodeTypeHost => "host",
+        hipGraphNodeType_hipGraphNodeTypeGraph => "graph",
+        hipGraphNodeType_hipGraphNodeTypeEmpty => "empty",
+        hipGraphNodeType_hipGraphNodeTypeWaitEvent => "wait_event",
+        hipGraphNodeType_hipGraphNodeTypeEventRecord => "event_record",
+        hipGraphNodeType_hipGraphNodeTypeExtSemaphoreSignal => "ext_semaphore_signal",
+        hipGraphNodeType_hipGraphNodeTypeExtSemaphoreWait => "ext_semaphore_wait",
+        hipGraphNodeType_hipGraphNodeTypeMemAlloc => "mem_alloc",
+        hipGraphNodeType_hipGraphNodeTypeMemFree => "mem_free",
+        hipGraphNodeType_hipGraphNodeTypeMemcpyFromSymbol => "memcpy_from_symbol",
+        hipGraphNodeType_hipGraphNodeTypeMemcpyToSymbol => "memcpy_to_symbol",
+        _ => "unknown",
+    }
+}
+
+fn node_from_json(node: &Value) -> HipResult<NodeKind> {
+    let kind = field(node, "kind")?
+        .as_str()
+        .ok_or_else(|| invalid("'kind' should be a string"))?;
+    Ok(match kind {
+        "kernel" => NodeKind::Kernel {
+            name: match field(node, "name")? {
+                Value::Null => None,
+                name => Some(
+                    name.as_str()
+                        .ok_or_else(|| invalid("'name' should be a string or null"))?
+                        .to_string(),
+                ),
+            },
+            grid: u64_array(field(node, "grid")?, "grid")?.map(|v| v as u32),
+            block: u64_array(field(node, "block")?, "block")?.map(|v| v as u32),
+            shared_mem: u64_field(node, "shared_mem")? as u32,
+        },
+        "memcpy" => NodeKind::Memcpy {
+            bytes: u64_field(node, "bytes")? as usize,
+        },
+        "memset" => NodeKind::Memset {
+            bytes: u64_field(node, "bytes")? as usize,
+            value: u64_field(node, "value")? as u32,
+        },
+        "host" => NodeKind::Host,
+        "empty" => NodeKind::Empty,
+        other => NodeKind::Other(other.to_string()),
+    })
+}
+
+fn field<'v>(value: &'v Value, key: &str) -> HipResult<&'v Value> {
+    value
+        .get(key)
+        .ok_or_else(|| invalid(&format!("missing field '{key}'")))
+}
+
+fn u64_field(value: &Value, key: &str) -> HipResult<u64> {
+    field(value, key)?
+        .as_u64()
+        .ok_or_else(|| invalid(&format!("'{key}' should be an unsigned integer")))
+}
+
+fn u64_array<const N: usize>(value: &Value, what: &str) -> HipResult<[u64; N]> {
+    value
+        .as_array()
+        .and_then(|items| items.iter().map(Value::as_u64).collect::<Option<Vec<_>>>())
+        .and_then(|items| items.try_into().ok())
+        .ok_or_else(|| {
+            invalid(&format!(
+                "'{what}' should be an array of {N} unsigned integers"
+            ))
+        })
+}
+
+fn dot_escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn invalid(reason: &str) -> HipError {
+    HipError::InvalidArgument(format!("invalid graph description: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn sample() -> GraphDescription {
+        GraphDescription {
+            nodes: vec![
+                NodeKind::Memset {
+                    bytes: 4096,
+                    value: 0,
+                },
+                NodeKind::Kernel {
+                    name: Some("axpb".to_string()),
+                    grid: [4, 1, 1],
+                    block: [256, 1, 1],
+                    shared_mem: 1024,
+                },
+                NodeKind::Kernel {
+                    name: None,
+                    grid: [1, 2, 3],
+                    block: [64, 2, 1],
+                    shared_mem: 0,
+                },
+                NodeKind::Memcpy { bytes: 4096 },
+                NodeKind::Host,
+                NodeKind::Other("event_record".to_string()),
+            ],
+            edges: vec![(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (4, 5)],
+        }
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let description = sample();
+        let json = description.to_json();
+        assert_eq!(GraphDescription::from_json(&json).unwrap(), description);
+    }
+
+    #[test]
+    fn test_json_layout() {
+        let description = GraphDescription {
+            nodes: vec![NodeKind::Memcpy { bytes: 16 }, NodeKind::Empty],
+            edges: vec![(0, 1)],
+        };
+        assert_eq!(
+            description.to_json(),
+            r#"{"nodes":[{"kind":"memcpy","bytes":16},{"kind":"empty"}],"edges":[[0,1]]}"#
+        );
+    }
+
+    #[test]
+    fn test_dot_output() {
+        let dot = sample().to_dot();
+        assert!(dot.starts_with("digraph {\n"), "{dot}");
+        assert!(
+            dot.contains(
+                "n1 [shape=box, label=\"axpb\\ngrid [4, 1, 1] block [256, 1, 1]\\nshared 1024 B\"];"
+            ),
+            "{dot}"
+        );
+        assert!(
+            dot.contains("n2 [shape=box, label=\"<unknown kernel>"),
+            "{dot}"
+        );
+        assert!(
+            dot.contains("n0 [shape=ellipse, label=\"memset 4096 B to 0\"];"),
+            "{dot}"
+        );
+        assert!(
+            dot.contains("n5 [shape=ellipse, label=\"event_record\"];"),
+            "{dot}"
+        );
+        assert!(dot.contains("  n2 -> n3;\n"), "{dot}");
+        assert!(dot.ends_with("}\n"), "{dot}");
+    }
+
+    #[rstest]
+    #[case::missing_nodes(r#"{"edges":[]}"#, "missing field 'nodes'")]
+    #[case::bad_grid(
+        r#"{"nodes":[{"kind":"kernel","name":null,"grid":[1,1],"block":[1,1,1],"shared_mem":0}],"edges":[]}"#,
+        "'grid' should be an array of 3 unsigned integers"
+    )]
+    #[case::edge_out_of_bounds(
+        r#"{"nodes":[{"kind":"empty"}],"edges":[[0,1]]}"#,
+        "edge (0, 1) is out of bounds"
+    )]
+    fn test_from_json_errors(#[case] json: &str, #[case] expected: &str) {
+        let err = GraphDescription::from_json(json).unwrap_err();
+        assert!(err.to_string().contains(expected), "{err}");
+    }
+
+    #[test]
+    fn test_diff() {
+        let left = sample();
+        assert!(left.diff(&left).is_empty());
+
+        let mut right = sample();
+        right.nodes[3] = NodeKind::Memcpy { bytes: 8192 };
+        right.edges.retain(|edge| *edge != (4, 5));
+        right.edges.push((1, 4));
+        assert_eq!(
+            left.diff(&right),
+            vec![
+                "node 3: Memcpy { bytes: 4096 } != Memcpy { bytes: 8192 }".to_string(),
+                "edge (4, 5) only in left graph".to_string(),
+                "edge (1, 4) only in right graph".to_string(),
+            ]
+        );
+    }
+}
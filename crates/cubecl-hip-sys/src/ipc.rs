@@ -0,0 +1,484 @@
+use std::ffi::{c_char, c_void};
+use std::ptr;
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::event::Event;
+use crate::launch::KernelArg;
+use crate::mem_pool::MemPool;
+
+/// Size in bytes of the serialized IPC handles.
+pub const IPC_HANDLE_SIZE: usize = 64;
+
+/// Handle to a device allocation that can be opened by another process.
+#[derive(Debug, Clone, Copy)]
+pub struct IpcMemHandle {
+    raw: hipIpcMemHandle_t,
+}
+
+impl IpcMemHandle {
+    /// Export the allocation starting at `ptr` with `hipIpcGetMemHandle`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be the start of a live allocation made with `hipMalloc`.
+    pub unsafe fn from_ptr(ptr: *mut c_void) -> HipResult<Self> {
+        let mut raw = hipIpcMemHandle_t {
+            reserved: [0; IPC_HANDLE_SIZE],
+        };
+        check(hipIpcGetMemHandle(&mut raw, ptr))?;
+        Ok(Self { raw })
+    }
+
+    /// Read a handle serialized with [`IpcMemHandle::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> HipResult<Self> {
+        let reserved = handle_bytes::<IPC_HANDLE_SIZE>(bytes, "IPC memory handle")?;
+        Ok(Self {
+            raw: hipIpcMemHandle_t {
+                reserved: reserved.map(|b| b as c_char),
+            },
+        })
+    }
+
+    /// Serialize the handle to send it to another process.
+    pub fn to_bytes(&self) -> [u8; IPC_HANDLE_SIZE] {
+        self.raw.reserved.map(|b| b as u8)
+    }
+
+    /// Map the exported allocation in the current process with `hipIpcOpenMemHandle`.
+    ///
+    /// The handle cannot be opened by the process that exported it.
+    pub fn open(&self) -> HipResult<IpcMemMapping> {
+        let mut ptr: *mut c_void = ptr::null_mut();
+        unsafe {
+            check(hipIpcOpenMemHandle(
+                &mut ptr,
+                self.raw,
+                hipIpcMemLazyEnablePeerAccess,
+            ))?
+        };
+        Ok(IpcMemMapping { ptr })
+    }
+}
+
+/// Allocation of another process mapped with [`IpcMemHandle::open`], closed on drop.
+#[derive(Debug)]
+pub struct IpcMemMapping {
+    ptr: *mut c_void,
+}
+
+impl IpcMemMapping {
+    /// Return the device pointer to the mapped allocation.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+}
+
+impl Drop for IpcMemMapping {
+    fn drop(&mut self) {
+        unsafe {
+            hipIpcCloseMemHandle(self.ptr);
+        }
+    }
+}
+
+unsafe impl KernelArg for IpcMemMapping {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut c_void as *mut c_void
+    }
+}
+
+/// Handle to an event that can be opened by another process.
+#[derive(Debug, Clone, Copy)]
+pub struct IpcEventHandle {
+    raw: hipIpcEventHandle_t,
+}
+
+impl IpcEventHandle {
+    /// Export `event`, created with [`Event::interprocess`], with `hipIpcGetEventHandle`.
+    pub fn from_event(event: &Event) -> HipResult<Self> {
+        let mut raw = hipIpcEventHandle_t {
+            reserved: [0; IPC_HANDLE_SIZE],
+        };
+        unsafe { check(hipIpcGetEventHandle(&mut raw, event.as_raw()))? };
+        Ok(Self { raw })
+    }
+
+    /// Read a handle serialized with [`IpcEventHandle::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> HipResult<Self> {
+        let reserved = handle_bytes::<IPC_HANDLE_SIZE>(bytes, "IPC event handle")?;
+        Ok(Self {
+            raw: hipIpcEventHandle_t {
+                reserved: reserved.map(|b| b as c_char),
+            },
+        })
+    }
+
+    /// Serialize the handle to send it to another process.
+    pub fn to_bytes(&self) -> [u8; IPC_HANDLE_SIZE] {
+        self.raw.reserved.map(|b| b as u8)
+    }
+
+    /// Open the exported event in the current process with `hipIpcOpenEventHandle`.
+    ///
+    /// The handle cannot be opened by the process that exported it.
+    pub fn open(&self) -> HipResult<Event> {
+        let mut raw: hipEvent_t = ptr::null_mut();
+        unsafe {
+            check(hipIpcOpenEventHandle(&mut raw, self.raw))?;
+            Ok(Event::from_raw(raw))
+        }
+    }
+}
+
+impl Event {
+    /// Create an event that can be shared with other processes, timing is disabled.
+    pub fn interprocess() -> HipResult<Self> {
+        Self::with_flags(hipEventDisableTiming | hipEventInterprocess)
+    }
+}
+
+/// Export data of an allocation from a [`MemPool`], imported with [`MemPool::import_pointer`]
+/// in another process that imported the pool.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolPtrExport {
+    raw: hipMemPoolPtrExportData,
+}
+
+impl PoolPtrExport {
+    /// Export the pool allocation starting at `ptr` with `hipMemPoolExportPointer`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` should be the start of a live allocation from a pool created with
+    /// [`MemPool::new_shareable`].
+    pub unsafe fn from_ptr(ptr: *mut c_void) -> HipResult<Self> {
+        let mut raw = hipMemPoolPtrExportData {
+            reserved: [0; IPC_HANDLE_SIZE],
+        };
+        check(hipMemPoolExportPointer(&mut raw, ptr))?;
+        Ok(Self { raw })
+    }
+
+    /// Read export data serialized with [`PoolPtrExport::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> HipResult<Self> {
+        Ok(Self {
+            raw: hipMemPoolPtrExportData {
+                reserved: handle_bytes::<IPC_HANDLE_SIZE>(bytes, "pool pointer export")?,
+            },
+        })
+    }
+
+    /// Serialize the export data to send it to another process.
+    pub fn to_bytes(&self) -> [u8; IPC_HANDLE_SIZE] {
+        self.raw.reserved
+    }
+}
+
+/// Allocation of another process imported from a shared [`MemPool`], freed on drop.
+///
+/// The importing process should drop it before the exporting process frees the allocation.
+#[derive(Debug)]
+pub struct PoolImport {
+    ptr: *mut c_void,
+}
+
+impl PoolImport {
+    /// Return the device pointer to the imported allocation.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+}
+
+impl Drop for PoolImport {
+    fn drop(&mut self) {
+        unsafe {
+            hipFree(self.ptr);
+        }
+    }
+}
+
+unsafe impl KernelArg for PoolImport {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut c_void as *mut c_void
+    }
+}
+
+impl MemPool {
+    /// Import an allocation exported by another process from the pool with
+    /// `hipMemPoolImportPointer`.
+    pub fn import_pointer(&self, export: &PoolPtrExport) -> HipResult<PoolImport> {
+        let mut raw = export.raw;
+        let mut ptr: *mut c_void = ptr::null_mut();
+        unsafe { check(hipMemPoolImportPointer(&mut ptr, self.as_raw(), &mut raw))? };
+        Ok(PoolImport { ptr })
+    }
+}
+
+#[cfg(unix)]
+mod fd {
+    use std::ffi::{c_int, c_void};
+    use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+    use std::ptr;
+
+    use crate::bindings::*;
+    use crate::error::{check, HipResult};
+    use crate::mem_pool::{pool_props, MemPool};
+    use crate::virtual_buffer::VirtualBuffer;
+
+    const POSIX_FD: hipMemAllocationHandleType =
+        hipMemAllocationHandleType_hipMemHandleTypePosixFileDescriptor;
+
+    /// Physical chunk of a [`VirtualBuffer`] exported as a file descriptor.
+    #[derive(Debug)]
+    pub struct SharedChunk {
+        /// File descriptor of the physical allocation, sent to other processes over a Unix socket.
+        pub fd: OwnedFd,
+        /// Size of the physical allocation in bytes.
+        pub size: usize,
+    }
+
+    impl MemPool {
+        /// Create a pool on `device` that can be exported to other processes as a file descriptor.
+        pub fn new_shareable(device: i32, max_size: usize) -> HipResult<Self> {
+            Self::with_props(&pool_props(device, max_size, POSIX_FD))
+        }
+
+        /// Export a pool created with [`MemPool::new_shareable`] with
+        /// `hipMemPoolExportToShareableHandle`.
+        pub fn export_fd(&self) -> HipResult<OwnedFd> {
+            let mut fd: c_int = -1;
+            unsafe {
+                check(hipMemPoolExportToShareableHandle(
+                    &mut fd as *mut c_int as *mut c_void,
+                    self.as_raw(),
+                    POSIX_FD,
+                    0,
+                ))?;
+                Ok(OwnedFd::from_raw_fd(fd))
+            }
+        }
+
+        /// Import a pool exported by another process with `hipMemPoolImportFromShareableHandle`.
+        ///
+        /// The file descriptor is not consumed, it can be closed once the pool is imported.
+        pub fn import_fd(fd: BorrowedFd<'_>) -> HipResult<Self> {
+            let mut raw: hipMemPool_t = ptr::null_mut();
+            unsafe {
+                check(hipMemPoolImportFromShareableHandle(
+                    &mut raw,
+                    fd_handle(fd),
+                    POSIX_FD,
+                    0,
+                ))?
+            };
+            Ok(Self::from_raw(raw, true))
+        }
+    }
+
+    impl VirtualBuffer {
+        /// Reserve `max_size` bytes of virtual addresses for `device`, backed by chunks that can
+        /// be exported to other processes as file descriptors.
+        pub fn new_shareable(device: i32, max_size: usize) -> HipResult<Self> {
+            Self::reserve(device, max_size, POSIX_FD)
+        }
+
+        /// Export the mapped chunks of a buffer created with [`VirtualBuffer::new_shareable`]
+        /// with `hipMemExportToShareableHandle`, in address order.
+        pub fn export_fds(&self) -> HipResult<Vec<SharedChunk>> {
+            self.chunks()
+                .map(|(handle, size)| {
+                    let mut fd: c_int = -1;
+                    unsafe {
+                        check(hipMemExportToShareableHandle(
+                            &mut fd as *mut c_int as *mut c_void,
+                            handle,
+                            POSIX_FD,
+                            0,
+                        ))?;
+                        Ok(SharedChunk {
+                            fd: OwnedFd::from_raw_fd(fd),
+                            size,
+                        })
+                    }
+                })
+                .collect()
+        }
+
+        /// Map chunks exported by another process with [`VirtualBuffer::export_fds`] in a new
+        /// buffer for `device`, reserving exactly their total size.
+        pub fn import_fds(device: i32, chunks: &[SharedChunk]) -> HipResult<Self> {
+            let size = chunks.iter().map(|chunk| chunk.size).sum();
+            let mut buffer = Self::reserve(device, size, POSIX_FD)?;
+            for chunk in chunks {
+                let mut handle: hipMemGenericAllocationHandle_t = ptr::null_mut();
+                unsafe {
+                    check(hipMemImportFromShareableHandle(
+                        &mut handle,
+                        fd_handle(chunk.fd.as_fd()),
+                        POSIX_FD,
+                    ))?
+                };
+                buffer.map_chunk(handle, chunk.size)?;
+            }
+            Ok(buffer)
+        }
+    }
+
+    /// Return the OS handle of `fd` as expected by the HIP import functions, the file
+    /// descriptor value itself cast to a pointer.
+    fn fd_handle(fd: BorrowedFd<'_>) -> *mut c_void {
+        fd.as_raw_fd() as isize as *mut c_void
+    }
+}
+
+#[cfg(unix)]
+pub use fd::*;
+
+/// Read a serialized handle of exactly `N` bytes.
+fn handle_bytes<const N: usize>(bytes: &[u8], what: &str) -> HipResult<[u8; N]> {
+    bytes.try_into().map_err(|_| {
+        HipError::InvalidArgument(format!(
+            "{what} should be {N} bytes long, got {} bytes",
+            bytes.len()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn sample_bytes() -> [u8; IPC_HANDLE_SIZE] {
+        std::array::from_fn(|i| (i * 37) as u8)
+    }
+
+    #[test]
+    fn test_mem_handle_bytes_roundtrip() {
+        let bytes = sample_bytes();
+        let handle = IpcMemHandle::from_bytes(&bytes).unwrap();
+        assert_eq!(handle.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_event_handle_bytes_roundtrip() {
+        let bytes = sample_bytes();
+        let handle = IpcEventHandle::from_bytes(&bytes).unwrap();
+        assert_eq!(handle.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_pool_ptr_export_bytes_roundtrip() {
+        let bytes = sample_bytes();
+        let export = PoolPtrExport::from_bytes(&bytes).unwrap();
+        assert_eq!(export.to_bytes(), bytes);
+    }
+
+    #[rstest]
+    #[case::empty(0)]
+    #[case::short(63)]
+    #[case::long(65)]
+    fn test_handle_from_bytes_wrong_length(#[case] len: usize) {
+        let bytes = vec![0u8; len];
+        let err = IpcMemHandle::from_bytes(&bytes).unwrap_err();
+        assert!(matches!(err, HipError::InvalidArgument(_)));
+        assert!(err.to_string().contains("64 bytes"), "{err}");
+    }
+
+    /// Environment variable passing the serialized handle to the child process of
+    /// `test_mem_handle_shares_memory_end_to_end`.
+    #[cfg(target_os = "linux")]
+    const CHILD_HANDLE_ENV: &str = "CUBECL_HIP_TEST_IPC_HANDLE";
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mem_handle_shares_memory_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let buffer = crate::DeviceBuffer::<u32>::zeroed(256).expect("Should allocate");
+        let handle = unsafe { IpcMemHandle::from_ptr(buffer.as_ptr() as *mut c_void) }
+            .expect("Should export the allocation");
+        let hex: String = handle
+            .to_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        // The exporting process cannot open its own handle, a child process does.
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "--ignored",
+                "ipc::tests::test_mem_handle_child_end_to_end",
+            ])
+            .env(CHILD_HANDLE_ENV, hex)
+            .status()
+            .expect("Should run the child process");
+        assert!(status.success(), "child should write through the handle");
+        let values = buffer.to_vec().expect("Should read the buffer back");
+        assert!(values.iter().all(|&value| value == 0xc0ffee));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "spawned by test_mem_handle_shares_memory_end_to_end"]
+    fn test_mem_handle_child_end_to_end() {
+        let hex = std::env::var(CHILD_HANDLE_ENV).expect("Should be spawned with a handle");
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let mapping = IpcMemHandle::from_bytes(&bytes)
+            .unwrap()
+            .open()
+            .expect("Should open the handle");
+        unsafe {
+            check(hipMemsetD32(
+                mapping.as_ptr() as hipDeviceptr_t,
+                0xc0ffee,
+                256,
+            ))
+            .expect("Should write through the mapping");
+            check(hipDeviceSynchronize()).unwrap();
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_virtual_buffer_fds_share_memory_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let granularity = crate::VirtualBuffer::allocation_granularity(0).unwrap();
+        let size = 2 * granularity;
+        let mut exported =
+            crate::VirtualBuffer::new_shareable(0, size).expect("Should reserve the buffer");
+        exported.grow(granularity).unwrap();
+        exported.grow(size).unwrap();
+        let chunks = exported.export_fds().expect("Should export the chunks");
+        assert_eq!(chunks.len(), 2);
+        // Importing in the same process goes through the same file descriptor handles as
+        // another process would.
+        let imported =
+            crate::VirtualBuffer::import_fds(0, &chunks).expect("Should import the chunks");
+        assert_ne!(imported.as_ptr(), exported.as_ptr());
+        assert_eq!(imported.len(), size);
+
+        let words = size / std::mem::size_of::<u32>();
+        let mut read = vec![0u32; words];
+        unsafe {
+            check(hipMemsetD32(
+                exported.as_ptr() as hipDeviceptr_t,
+                0x5eed,
+                words,
+            ))
+            .expect("Should write the exported buffer");
+            check(hipMemcpy(
+                read.as_mut_ptr() as *mut c_void,
+                imported.as_ptr(),
+                size,
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+            ))
+            .expect("Should read the imported buffer");
+        }
+        assert!(read.iter().all(|&value| value == 0x5eed));
+    }
+}
@@ -0,0 +1,154 @@
+use std::{ffi::c_void, mem, ptr};
+
+/// Tag of the `extra` launch argument followed by a pointer to the packed argument buffer.
+pub const HIP_LAUNCH_PARAM_BUFFER_POINTER: *mut c_void = 0x01 as *mut c_void;
+/// Tag of the `extra` launch argument followed by a pointer to the buffer size as `usize`.
+pub const HIP_LAUNCH_PARAM_BUFFER_SIZE: *mut c_void = 0x02 as *mut c_void;
+/// Tag terminating the `extra` launch argument list.
+pub const HIP_LAUNCH_PARAM_END: *mut c_void = 0x03 as *mut c_void;
+
+/// Plain value that can be copied byte for byte into a [`KernelArgsBuffer`].
+///
+/// # Safety
+///
+/// The type must be `#[repr(C)]` (or a primitive) with the same layout as the kernel
+/// parameter on the device, and must not contain any padding bytes. Add explicit padding
+/// fields to structs that need them.
+pub unsafe trait KernelArgValue: Copy {}
+
+macro_rules! impl_kernel_arg_value {
+    ($($ty:ty),*) => {
+        $(unsafe impl KernelArgValue for $ty {})*
+    };
+}
+
+impl_kernel_arg_value!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+unsafe impl<T> KernelArgValue for *const T {}
+unsafe impl<T> KernelArgValue for *mut T {}
+unsafe impl<T: KernelArgValue, const N: usize> KernelArgValue for [T; N] {}
+
+/// Packed kernel argument buffer laid out like the AMDGPU kernarg segment.
+///
+/// Each argument is placed at the next offset aligned to its natural alignment, as the
+/// compiler does for the explicit kernel arguments. The buffer is passed to the kernel through
+/// the `extra` argument of `hipModuleLaunchKernel`, see [`crate::Function::launch_with_buffer`].
+#[derive(Debug, Default, Clone)]
+pub struct KernelArgsBuffer {
+    bytes: Vec<u8>,
+    offsets: Vec<usize>,
+    align: usize,
+}
+
+impl KernelArgsBuffer {
+    /// Create an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty buffer able to hold `bytes` bytes of arguments without reallocating.
+    pub fn with_capacity(bytes: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(bytes),
+            ..Self::default()
+        }
+    }
+
+    /// Append a scalar, a pointer or a `#[repr(C)]` struct aligned to its natural alignment.
+    pub fn push<T: KernelArgValue>(&mut self, value: T) -> &mut Self {
+        let align = mem::align_of::<T>();
+        let size = mem::size_of::<T>();
+        let offset = self.bytes.len().next_multiple_of(align);
+        self.bytes.resize(offset + size, 0);
+        unsafe {
+            ptr::copy_nonoverlapping(
+                &value as *const T as *const u8,
+                self.bytes.as_mut_ptr().add(offset),
+                size,
+            );
+        }
+        self.offsets.push(offset);
+        self.align = self.align.max(align);
+        self
+    }
+
+    /// Append a device pointer.
+    pub fn push_device_ptr<T>(&mut self, ptr: *const T) -> &mut Self {
+        self.push(ptr)
+    }
+
+    /// Return the offset of each argument in the buffer, in push order.
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Return the largest alignment among the pushed arguments.
+    pub fn align(&self) -> usize {
+        self.align.max(1)
+    }
+
+    /// Return the size of the packed arguments in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Return true if no argument has been pushed.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Return the packed arguments.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Shape {
+        rank: u32,
+        dims: [u32; 3],
+    }
+
+    unsafe impl KernelArgValue for Shape {}
+
+    #[test]
+    fn test_push_aligns_each_argument() {
+        let mut buffer = KernelArgsBuffer::new();
+        buffer
+            .push(1u8)
+            .push(2.0f64)
+            .push(3i32)
+            .push_device_ptr(0x1000 as *const f32)
+            .push(1u16)
+            .push(Shape {
+                rank: 3,
+                dims: [4, 5, 6],
+            });
+        assert_eq!(buffer.offsets(), &[0, 8, 16, 24, 32, 36]);
+        assert_eq!(buffer.len(), 52);
+        assert_eq!(buffer.align(), 8);
+    }
+
+    #[test]
+    fn test_push_copies_values() {
+        let mut buffer = KernelArgsBuffer::with_capacity(16);
+        buffer.push(0x0102_0304u32).push(-1i64);
+        let bytes = buffer.as_bytes();
+        assert_eq!(&bytes[0..4], &0x0102_0304u32.to_ne_bytes());
+        assert_eq!(&bytes[4..8], &[0; 4], "padding should be zeroed");
+        assert_eq!(&bytes[8..16], &(-1i64).to_ne_bytes());
+    }
+
+    #[test]
+    fn test_empty_buffer() {
+        let buffer = KernelArgsBuffer::new();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.align(), 1);
+        assert!(buffer.offsets().is_empty());
+    }
+}
@@ -0,0 +1,474 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use crate::bindings::{hipDriverGetVersion, hipRuntimeGetVersion, HIP_VERSION_GITHASH};
+use crate::error::{check, HipResult};
+
+/// Environment variable overriding the directory returned by [`KernelCache::default_dir`].
+pub const KERNEL_CACHE_DIR_ENV: &str = "CUBECL_HIP_CACHE_DIR";
+
+/// Extension of the cache entry files.
+const ENTRY_EXTENSION: &str = "hipco";
+/// Magic number at the start of every cache entry file.
+const ENTRY_MAGIC: &[u8; 8] = b"CCLHIPK1";
+
+/// Counter making temporary file names unique within the process.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Key of a compiled kernel in the [`KernelCache`].
+///
+/// Any change to the source, the headers, the compile options, the target architecture, the
+/// HIP git hash or the HIP runtime and driver versions produces a different key, so upgrading
+/// ROCm invalidates the cache even when the crate is not rebuilt, and two builds of the same
+/// release never share entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelCacheKey {
+    source: String,
+    headers: Vec<(String, String)>,
+    options: Vec<String>,
+    arch: String,
+    hip_githash: String,
+    runtime_version: i32,
+    driver_version: i32,
+}
+
+impl KernelCacheKey {
+    /// Create a key for `source` compiled for the device `gcnArchName`, for instance
+    /// `gfx90a:sramecc+:xnack-`, with the HIP git hash the bindings were generated for and
+    /// the versions `hipRuntimeGetVersion` and `hipDriverGetVersion` return.
+    pub fn new(source: impl Into<String>, arch: impl Into<String>) -> HipResult<Self> {
+        let mut runtime_version = 0;
+        let mut driver_version = 0;
+        unsafe {
+            check(hipRuntimeGetVersion(&mut runtime_version))?;
+            check(hipDriverGetVersion(&mut driver_version))?;
+        }
+        Ok(Self::with_versions(
+            source,
+            arch,
+            runtime_version,
+            driver_version,
+        ))
+    }
+
+    /// Create a key for `source` compiled for the device `gcnArchName` with the given HIP
+    /// runtime and driver versions and the HIP git hash the bindings were generated for.
+    pub fn with_versions(
+        source: impl Into<String>,
+        arch: impl Into<String>,
+        runtime_version: i32,
+        driver_version: i32,
+    ) -> Self {
+        let githash = HIP_VERSION_GITHASH
+            .strip_suffix(&[0])
+            .unwrap_or(HIP_VERSION_GITHASH);
+        Self {
+            source: source.into(),
+            headers: Vec::new(),
+            options: Vec::new(),
+            arch: arch.into(),
+            hip_githash: String::from_utf8_lossy(githash).into_owned(),
+            runtime_version,
+            driver_version,
+        }
+    }
+
+    /// Add a header passed to `hiprtcCreateProgram`.
+    pub fn with_header(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        self.headers.push((name.into(), source.into()));
+        self
+    }
+
+    /// Add compile options passed to `hiprtcCompileProgram`, order matters.
+    pub fn with_options<I, S>(mut self, options: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.extend(options.into_iter().map(Into::into));
+        self
+    }
+
+    /// Override the HIP git hash, for instance with the one of the installed runtime.
+    pub fn with_githash(mut self, githash: impl Into<String>) -> Self {
+        self.hip_githash = githash.into();
+        self
+    }
+
+    /// Serialize all the fields of the key with length prefixes, so that no two keys share
+    /// the same encoding.
+    fn encode(&self) -> Vec<u8> {
+        fn field(bytes: &mut Vec<u8>, value: &[u8]) {
+            bytes.extend_from_slice(&(value.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(value);
+        }
+        let mut bytes = Vec::new();
+        field(&mut bytes, self.source.as_bytes());
+        bytes.extend_from_slice(&(self.headers.len() as u64).to_le_bytes());
+        for (name, source) in &self.headers {
+            field(&mut bytes, name.as_bytes());
+            field(&mut bytes, source.as_bytes());
+        }
+        bytes.extend_from_slice(&(self.options.len() as u64).to_le_bytes());
+        for option in &self.options {
+            field(&mut bytes, option.as_bytes());
+        }
+        field(&mut bytes, self.arch.as_bytes());
+        field(&mut bytes, self.hip_githash.as_bytes());
+        bytes.extend_from_slice(&self.runtime_version.to_le_bytes());
+        bytes.extend_from_slice(&self.driver_version.to_le_bytes());
+        bytes
+    }
+
+    /// Return the hex digest identifying the key, stable across runs and Rust versions.
+    pub fn digest(&self) -> String {
+        format!("{:032x}", fnv1a_128(&self.encode()))
+    }
+}
+
+/// FNV-1a hash on 128 bits.
+fn fnv1a_128(bytes: &[u8]) -> u128 {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u128).wrapping_mul(PRIME)
+    })
+}
+
+/// Persistent on-disk cache of compiled code objects, for instance the output of `hiprtcGetCode`.
+///
+/// Entries are written atomically, so concurrent processes sharing the directory never read
+/// a partial code object. When the total size of the entries exceeds the maximum size, the
+/// least recently used ones are evicted.
+#[derive(Debug, Clone)]
+pub struct KernelCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl KernelCache {
+    /// Open the cache at `dir`, creating the directory if needed.
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, max_size })
+    }
+
+    /// Return the default cache directory: `$CUBECL_HIP_CACHE_DIR`, or `cubecl-hip` in
+    /// `$XDG_CACHE_HOME` or in `$HOME/.cache`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let non_empty = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+        if let Some(dir) = non_empty(KERNEL_CACHE_DIR_ENV) {
+            return Some(PathBuf::from(dir));
+        }
+        non_empty("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|home| Path::new(&home).join(".cache")))
+            .map(|dir| dir.join("cubecl-hip"))
+    }
+
+    /// Return the cache directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Return the maximum total size of the entries in bytes.
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    fn entry_path(&self, key: &KernelCacheKey) -> PathBuf {
+        self.dir.join(format!("{}.{ENTRY_EXTENSION}", key.digest()))
+    }
+
+    /// Return the cached code object for `key`, if any, and mark it as recently used.
+    pub fn get(&self, key: &KernelCacheKey) -> io::Result<Option<Vec<u8>>> {
+        let path = self.entry_path(key);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let encoded_key = key.encode();
+        let header_size = ENTRY_MAGIC.len() + 8 + encoded_key.len();
+        let matches = contents.len() >= header_size
+            && contents.starts_with(ENTRY_MAGIC)
+            && contents[ENTRY_MAGIC.len()..ENTRY_MAGIC.len() + 8]
+                == (encoded_key.len() as u64).to_le_bytes()
+            && contents[ENTRY_MAGIC.len() + 8..header_size] == encoded_key[..];
+        if !matches {
+            // Corrupted entry or digest collision, the caller will overwrite it.
+            return Ok(None);
+        }
+        if let Ok(file) = fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Ok(Some(contents[header_size..].to_vec()))
+    }
+
+    /// Store `code` for `key`, then evict the least recently used entries if the cache is
+    /// larger than its maximum size.
+    pub fn insert(&self, key: &KernelCacheKey, code: &[u8]) -> io::Result<()> {
+        let path = self.entry_path(key);
+        let tmp_path = self.dir.join(format!(
+            ".{}.{}.{}.tmp",
+            key.digest(),
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let encoded_key = key.encode();
+        let write = || -> io::Result<()> {
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(ENTRY_MAGIC)?;
+            file.write_all(&(encoded_key.len() as u64).to_le_bytes())?;
+            file.write_all(&encoded_key)?;
+            file.write_all(code)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, &path)
+        };
+        if let Err(e) = write() {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+        self.evict(Some(&path))
+    }
+
+    /// Return the cached code object for `key`, or compile it with `compile` and store it.
+    ///
+    /// The cache is best effort: I/O errors are treated as misses and failing to store the
+    /// compiled code does not fail the call.
+    pub fn get_or_insert_with<E>(
+        &self,
+        key: &KernelCacheKey,
+        compile: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, E> {
+        if let Ok(Some(code)) = self.get(key) {
+            return Ok(code);
+        }
+        let code = compile()?;
+        let _ = self.insert(key, &code);
+        Ok(code)
+    }
+
+    /// Return the total size of the entries in bytes.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|entry| entry.1).sum())
+    }
+
+    /// Remove all the entries.
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, _, _) in self.entries()? {
+            remove_if_exists(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the least recently used entries until the cache fits in its maximum size,
+    /// never removing `keep`.
+    fn evict(&self, keep: Option<&Path>) -> io::Result<()> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|entry| entry.1).sum();
+        entries.sort_by_key(|entry| entry.2);
+        for (path, size, _) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            if Some(path.as_path()) == keep {
+                continue;
+            }
+            remove_if_exists(&path)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// Return the path, size and modification time of every entry.
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = Vec::new();
+        for item in fs::read_dir(&self.dir)? {
+            let item = item?;
+            let path = item.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            // Entries removed concurrently by another process are skipped.
+            let Ok(metadata) = item.metadata() else {
+                continue;
+            };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((path, metadata.len(), modified));
+        }
+        Ok(entries)
+    }
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::time::Duration;
+
+    /// Cache in a fresh temporary directory, removed on drop.
+    struct TestCache(KernelCache);
+
+    impl TestCache {
+        fn new(name: &str, max_size: u64) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("cubecl-hip-cache-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self(KernelCache::new(dir, max_size).unwrap())
+        }
+    }
+
+    impl Drop for TestCache {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.dir());
+        }
+    }
+
+    const SOURCE: &str = "extern \"C\" __global__ void k() {}";
+
+    fn key() -> KernelCacheKey {
+        KernelCacheKey::with_versions(SOURCE, "gfx90a", 60443484, 60443484).with_options(["-O3"])
+    }
+
+    #[rstest]
+    #[case::source(KernelCacheKey::with_versions("other", "gfx90a", 60443484, 60443484).with_options(["-O3"]))]
+    #[case::arch(KernelCacheKey::with_versions(SOURCE, "gfx942", 60443484, 60443484).with_options(["-O3"]))]
+    #[case::options(key().with_options(["-ffast-math"]))]
+    #[case::header(key().with_header("a.h", "#define A 1"))]
+    #[case::runtime_version(KernelCacheKey::with_versions(SOURCE, "gfx90a", 70051831, 60443484).with_options(["-O3"]))]
+    #[case::driver_version(KernelCacheKey::with_versions(SOURCE, "gfx90a", 60443484, 70051831).with_options(["-O3"]))]
+    #[case::githash(key().with_githash("cafebabe"))]
+    fn test_key_digest_changes(#[case] other: KernelCacheKey) {
+        assert_ne!(key().digest(), other.digest());
+    }
+
+    #[test]
+    fn test_key_fields_are_length_prefixed() {
+        let a = key().with_options(["ab", "c"]);
+        let b = key().with_options(["a", "bc"]);
+        assert_ne!(a.digest(), b.digest());
+        assert_eq!(key().digest(), key().digest());
+        assert_eq!(key().digest().len(), 32);
+    }
+
+    #[test]
+    fn test_default_key_uses_compiled_githash() {
+        let key = key();
+        assert!(!key.hip_githash.ends_with('\0'));
+        assert_eq!(
+            key.hip_githash.as_bytes(),
+            HIP_VERSION_GITHASH.strip_suffix(&[0]).unwrap()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_default_key_uses_runtime_versions_end_to_end() {
+        let key = KernelCacheKey::new("src", "gfx90a").expect("Should query the HIP versions");
+        let mut runtime_version = 0;
+        unsafe { check(hipRuntimeGetVersion(&mut runtime_version)).unwrap() };
+        assert_eq!(key.runtime_version, runtime_version);
+        assert!(key.driver_version > 0);
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let cache = TestCache::new("insert", 1 << 20);
+        assert_eq!(cache.0.get(&key()).unwrap(), None);
+        cache.0.insert(&key(), b"code object").unwrap();
+        assert_eq!(
+            cache.0.get(&key()).unwrap().as_deref(),
+            Some(&b"code object"[..])
+        );
+        let upgraded = KernelCacheKey::with_versions(SOURCE, "gfx90a", 70051831, 70051831)
+            .with_options(["-O3"]);
+        assert_eq!(cache.0.get(&upgraded).unwrap(), None, "upgrade should miss");
+        let leftovers: Vec<_> = fs::read_dir(cache.0.dir())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty(), "temporary files should be renamed");
+    }
+
+    #[test]
+    fn test_corrupted_entry_is_a_miss() {
+        let cache = TestCache::new("corrupted", 1 << 20);
+        cache.0.insert(&key(), b"code object").unwrap();
+        fs::write(cache.0.entry_path(&key()), b"garbage").unwrap();
+        assert_eq!(cache.0.get(&key()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_or_insert_with_compiles_once() {
+        let cache = TestCache::new("get-or-insert", 1 << 20);
+        let mut compiled = 0;
+        for _ in 0..2 {
+            let code = cache
+                .0
+                .get_or_insert_with(&key(), || {
+                    compiled += 1;
+                    Ok::<_, ()>(b"compiled".to_vec())
+                })
+                .unwrap();
+            assert_eq!(code, b"compiled");
+        }
+        assert_eq!(compiled, 1);
+        let err = cache
+            .0
+            .get_or_insert_with(&key().with_options(["-g"]), || Err("failed"));
+        assert_eq!(err, Err("failed"));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let keys: Vec<KernelCacheKey> = (0..3)
+            .map(|i| key().with_options([format!("-DN={i}")]))
+            .collect();
+        let entry_size = (ENTRY_MAGIC.len() + 8 + keys[0].encode().len() + 100) as u64;
+        let cache = TestCache::new("evict", entry_size * 2);
+
+        cache.0.insert(&keys[0], &[0; 100]).unwrap();
+        let old = SystemTime::now() - Duration::from_secs(60);
+        cache.0.insert(&keys[1], &[1; 100]).unwrap();
+        assert_eq!(cache.0.size().unwrap(), entry_size * 2);
+        for key in &keys[..2] {
+            let file = fs::File::options()
+                .append(true)
+                .open(cache.0.entry_path(key))
+                .unwrap();
+            file.set_modified(old).unwrap();
+        }
+        // Reading the first entry makes the second one the least recently used.
+        assert!(cache.0.get(&keys[0]).unwrap().is_some());
+        cache.0.insert(&keys[2], &[2; 100]).unwrap();
+
+        assert!(cache.0.get(&keys[0]).unwrap().is_some());
+        assert!(cache.0.get(&keys[1]).unwrap().is_none());
+        assert!(cache.0.get(&keys[2]).unwrap().is_some());
+        assert!(cache.0.size().unwrap() <= cache.0.max_size());
+        cache.0.clear().unwrap();
+        assert_eq!(cache.0.size().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_keeps_new_entry_larger_than_max_size() {
+        let cache = TestCache::new("oversized", 10);
+        cache.0.insert(&key(), &[0; 100]).unwrap();
+        assert!(cache.0.get(&key()).unwrap().is_some());
+    }
+}
@@ -0,0 +1,312 @@
+use std::{ffi::c_void, ptr};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::{
+    KernelArgsBuffer, HIP_LAUNCH_PARAM_BUFFER_POINTER, HIP_LAUNCH_PARAM_BUFFER_SIZE,
+    HIP_LAUNCH_PARAM_END,
+};
+use crate::module::Function;
+use crate::stream::Stream;
+
+impl From<u32> for dim3 {
+    fn from(x: u32) -> Self {
+        dim3 { x, y: 1, z: 1 }
+    }
+}
+
+impl From<(u32, u32)> for dim3 {
+    fn from((x, y): (u32, u32)) -> Self {
+        dim3 { x, y, z: 1 }
+    }
+}
+
+impl From<(u32, u32, u32)> for dim3 {
+    fn from((x, y, z): (u32, u32, u32)) -> Self {
+        dim3 { x, y, z }
+    }
+}
+
+/// Launch configuration of a kernel.
+#[derive(Debug, Clone, Copy)]
+pub struct LaunchConfig<'a> {
+    /// Number of blocks in the grid.
+    pub grid: dim3,
+    /// Number of threads in a block.
+    pub block: dim3,
+    /// Dynamic shared memory size per block in bytes.
+    pub shared_mem: u32,
+    /// Stream to launch on, `None` for the null stream.
+    pub stream: Option<&'a Stream>,
+}
+
+impl<'a> LaunchConfig<'a> {
+    /// Create a configuration on the null stream without dynamic shared memory.
+    pub fn new(grid: impl Into<dim3>, block: impl Into<dim3>) -> Self {
+        Self {
+            grid: grid.into(),
+            block: block.into(),
+            shared_mem: 0,
+            stream: None,
+        }
+    }
+
+    /// Set the dynamic shared memory size per block in bytes.
+    pub fn with_shared_mem(mut self, bytes: u32) -> Self {
+        self.shared_mem = bytes;
+        self
+    }
+
+    /// Set the stream to launch on.
+    pub fn with_stream(mut self, stream: &'a Stream) -> Self {
+        self.stream = Some(stream);
+        self
+    }
+
+    /// Return the raw stream, the null stream if none was set.
+    pub fn raw_stream(&self) -> hipStream_t {
+        self.stream.map_or(ptr::null_mut(), Stream::as_raw)
+    }
+
+    /// Check that no dimension of the grid or the block is zero.
+    pub fn validate(&self) -> HipResult<()> {
+        let dims = [("grid", self.grid), ("block", self.block)];
+        for (label, dim) in dims {
+            if dim.x == 0 || dim.y == 0 || dim.z == 0 {
+                return Err(HipError::InvalidArgument(format!(
+                    "{label} dimensions ({}, {}, {}) should all be greater than zero",
+                    dim.x, dim.y, dim.z
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Value that can be passed as a kernel argument through the `kernelParams` array.
+///
+/// # Safety
+///
+/// `as_kernel_param` must return a pointer to a value whose layout matches the kernel
+/// parameter it is bound to, and which stays valid as long as `self` is borrowed.
+pub unsafe trait KernelArg {
+    /// Return a pointer to the argument value.
+    fn as_kernel_param(&self) -> *mut c_void;
+}
+
+macro_rules! impl_kernel_arg_by_value {
+    ($($ty:ty),*) => {
+        $(
+            unsafe impl KernelArg for $ty {
+                fn as_kernel_param(&self) -> *mut c_void {
+                    self as *const Self as *mut c_void
+                }
+            }
+        )*
+    };
+}
+
+impl_kernel_arg_by_value!(bool, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+unsafe impl<T> KernelArg for *const T {
+    fn as_kernel_param(&self) -> *mut c_void {
+        self as *const Self as *mut c_void
+    }
+}
+
+unsafe impl<T> KernelArg for *mut T {
+    fn as_kernel_param(&self) -> *mut c_void {
+        self as *const Self as *mut c_void
+    }
+}
+
+unsafe impl<T: KernelArg + ?Sized> KernelArg for &T {
+    fn as_kernel_param(&self) -> *mut c_void {
+        (**self).as_kernel_param()
+    }
+}
+
+/// Ordered list of kernel arguments.
+///
+/// Implemented for tuples of [`KernelArg`] and for slices of `&dyn KernelArg`.
+pub trait KernelArgs {
+    /// Return the pointers to each argument, in declaration order.
+    fn as_kernel_params(&self) -> Vec<*mut c_void>;
+}
+
+impl KernelArgs for [&dyn KernelArg] {
+    fn as_kernel_params(&self) -> Vec<*mut c_void> {
+        self.iter().map(|arg| arg.as_kernel_param()).collect()
+    }
+}
+
+impl KernelArgs for () {
+    fn as_kernel_params(&self) -> Vec<*mut c_void> {
+        Vec::new()
+    }
+}
+
+macro_rules! impl_kernel_args_for_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: KernelArg),+> KernelArgs for ($($name,)+) {
+            fn as_kernel_params(&self) -> Vec<*mut c_void> {
+                #[allow(non_snake_case)]
+                let ($($name,)+) = self;
+                vec![$($name.as_kernel_param()),+]
+            }
+        }
+    };
+}
+
+impl_kernel_args_for_tuple!(A0);
+impl_kernel_args_for_tuple!(A0, A1);
+impl_kernel_args_for_tuple!(A0, A1, A2);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12, A13);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12, A13, A14);
+impl_kernel_args_for_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12, A13, A14, A15);
+
+impl Function<'_> {
+    /// Launch the kernel with `hipModuleLaunchKernel`.
+    ///
+    /// The argument pointers are built from `args`, which stays borrowed until the launch
+    /// call returns.
+    ///
+    /// # Safety
+    ///
+    /// The arguments must match the kernel signature in number, order and layout, and any
+    /// device memory they reference must remain valid until the kernel completes.
+    pub unsafe fn launch<A: KernelArgs + ?Sized>(
+        &self,
+        config: &LaunchConfig,
+        args: &A,
+    ) -> HipResult<()> {
+        self.validate_launch(config)?;
+        let mut params = args.as_kernel_params();
+        check(hipModuleLaunchKernel(
+            self.as_raw(),
+            config.grid.x,
+            config.grid.y,
+            config.grid.z,
+            config.block.x,
+            config.block.y,
+            config.block.z,
+            config.shared_mem,
+            config.raw_stream(),
+            params.as_mut_ptr(),
+            ptr::null_mut(),
+        ))
+    }
+
+    /// Launch the kernel with `hipModuleLaunchKernel`, passing the packed arguments through
+    /// the `extra` argument with `HIP_LAUNCH_PARAM_BUFFER_POINTER`.
+    ///
+    /// # Safety
+    ///
+    /// The buffer layout must match the kernel signature, and any device memory it references
+    /// must remain valid until the kernel completes.
+    pub unsafe fn launch_with_buffer(
+        &self,
+        config: &LaunchConfig,
+        args: &KernelArgsBuffer,
+    ) -> HipResult<()> {
+        self.validate_launch(config)?;
+        let mut size = args.len();
+        let mut extra = [
+            HIP_LAUNCH_PARAM_BUFFER_POINTER,
+            args.as_bytes().as_ptr() as *mut c_void,
+            HIP_LAUNCH_PARAM_BUFFER_SIZE,
+            &mut size as *mut usize as *mut c_void,
+            HIP_LAUNCH_PARAM_END,
+        ];
+        check(hipModuleLaunchKernel(
+            self.as_raw(),
+            config.grid.x,
+            config.grid.y,
+            config.grid.z,
+            config.block.x,
+            config.block.y,
+            config.block.z,
+            config.shared_mem,
+            config.raw_stream(),
+            ptr::null_mut(),
+            extra.as_mut_ptr(),
+        ))
+    }
+}
+
+/// Launch a [`Function`] with a [`LaunchConfig`] and a list of [`KernelArg`] values.
+///
+/// Expands to an unsafe call to [`Function::launch`], so it must be used in an `unsafe` block.
+///
+/// ```ignore
+/// unsafe { launch!(function, config, a, device_x, device_b, device_out, n) }?;
+/// ```
+#[macro_export]
+macro_rules! launch {
+    ($function:expr, $config:expr $(, $arg:expr)* $(,)?) => {
+        $function.launch(&$config, &($($arg,)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::scalar(dim3::from(64), (64, 1, 1))]
+    #[case::pair(dim3::from((8, 4)), (8, 4, 1))]
+    #[case::triple(dim3::from((8, 4, 2)), (8, 4, 2))]
+    fn test_dim3_from(#[case] dim: dim3, #[case] expected: (u32, u32, u32)) {
+        assert_eq!((dim.x, dim.y, dim.z), expected);
+    }
+
+    #[rstest]
+    #[case::valid((16, 1, 1), (64, 1, 1), true)]
+    #[case::zero_grid((0, 1, 1), (64, 1, 1), false)]
+    #[case::zero_block((16, 1, 1), (64, 0, 1), false)]
+    fn test_launch_config_validate(
+        #[case] grid: (u32, u32, u32),
+        #[case] block: (u32, u32, u32),
+        #[case] valid: bool,
+    ) {
+        let config = LaunchConfig::new(grid, block);
+        assert_eq!(config.validate().is_ok(), valid);
+    }
+
+    #[test]
+    fn test_kernel_params_point_to_arguments_in_order() {
+        let a = 2.0f32;
+        let x: *mut c_void = 0x1000 as *mut c_void;
+        let n = 1024i32;
+        let args = (a, x, &n);
+        let params = args.as_kernel_params();
+        assert_eq!(params.len(), 3);
+        unsafe {
+            assert_eq!(*(params[0] as *const f32), a);
+            assert_eq!(*(params[1] as *const *mut c_void), x);
+            assert_eq!(*(params[2] as *const i32), n);
+        }
+        assert_eq!(params[2], &n as *const i32 as *mut c_void);
+    }
+
+    #[test]
+    fn test_dyn_kernel_params() {
+        let a = 1u64;
+        let b = 3u8;
+        let args: [&dyn KernelArg; 2] = [&a, &b];
+        let params = args[..].as_kernel_params();
+        assert_eq!(params[0], &a as *const u64 as *mut c_void);
+        assert_eq!(params[1], &b as *const u8 as *mut c_void);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::bindings::*;
+use crate::error::{HipError, HipResult};
+use crate::launch::{KernelArgs, LaunchConfig};
+use crate::module::Function;
+
+/// Launch configuration with extended launch attributes.
+///
+/// With HIP 7.0 the kernel is launched by `hipDrvLaunchKernelEx` with a list of
+/// `hipLaunchAttribute`. Older runtimes do not have it, so the kernel is launched by
+/// `hipModuleLaunchKernel` and requesting any attribute is an error.
+#[derive(Debug, Clone, Copy)]
+pub struct LaunchConfigEx<'a> {
+    pub config: LaunchConfig<'a>,
+    /// Launch the kernel as a cooperative kernel, see [`Function::launch_cooperative`].
+    pub cooperative: bool,
+    /// Execution priority of the kernel.
+    pub priority: Option<i32>,
+    /// Dimensions of the thread block clusters, which no HIP runtime supports yet.
+    pub cluster_dim: Option<dim3>,
+}
+
+impl<'a> LaunchConfigEx<'a> {
+    /// Create a configuration without any launch attribute.
+    pub fn new(config: LaunchConfig<'a>) -> Self {
+        Self {
+            config,
+            cooperative: false,
+            priority: None,
+            cluster_dim: None,
+        }
+    }
+
+    /// Set whether the kernel is launched as a cooperative kernel.
+    pub fn with_cooperative(mut self, cooperative: bool) -> Self {
+        self.cooperative = cooperative;
+        self
+    }
+
+    /// Set the execution priority of the kernel.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Set the dimensions of the thread block clusters.
+    pub fn with_cluster_dim(mut self, cluster_dim: impl Into<dim3>) -> Self {
+        self.cluster_dim = Some(cluster_dim.into());
+        self
+    }
+
+    /// Return the launch attributes of the configuration, in the order of the fields.
+    #[cfg(has_launch_attributes)]
+    fn attributes(&self) -> HipResult<Vec<hipLaunchAttribute>> {
+        if self.cluster_dim.is_some() {
+            return Err(unsupported_cluster_dim());
+        }
+        let mut attributes = Vec::new();
+        if self.cooperative {
+            let mut attribute =
+                launch_attribute(hipLaunchAttributeID_hipLaunchAttributeCooperative);
+            attribute.__bindgen_anon_1.val.cooperative = 1;
+            attributes.push(attribute);
+        }
+        if let Some(priority) = self.priority {
+            let mut attribute = launch_attribute(hipLaunchAttributeID_hipLaunchAttributePriority);
+            attribute.__bindgen_anon_1.val.priority = priority;
+            attributes.push(attribute);
+        }
+        Ok(attributes)
+    }
+
+    /// Check that no attribute is requested, since none is supported by `hipModuleLaunchKernel`.
+    #[cfg(not(has_launch_attributes))]
+    fn check_no_attributes(&self) -> HipResult<()> {
+        if self.cooperative {
+            return Err(HipError::InvalidArgument(
+                "the cooperative attribute needs HIP 7.0, use Function::launch_cooperative instead"
+                    .to_string(),
+            ));
+        }
+        if self.priority.is_some() {
+            return Err(HipError::InvalidArgument(
+                "the priority attribute needs HIP 7.0".to_string(),
+            ));
+        }
+        if self.cluster_dim.is_some() {
+            return Err(unsupported_cluster_dim());
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<LaunchConfig<'a>> for LaunchConfigEx<'a> {
+    fn from(config: LaunchConfig<'a>) -> Self {
+        Self::new(config)
+    }
+}
+
+impl Function<'_> {
+    /// Launch the kernel with the launch attributes of `config`, with `hipDrvLaunchKernelEx`
+    /// on HIP 7.0 and with `hipModuleLaunchKernel` on older runtimes.
+    ///
+    /// `hipLaunchKernelExC` is the same launch for kernels registered by hipcc host stubs,
+    /// which modules do not have.
+    ///
+    /// # Safety
+    ///
+    /// The arguments must match the kernel signature in number, order and layout, and any
+    /// device memory they reference must remain valid until the kernel completes.
+    pub unsafe fn launch_ex<A: KernelArgs + ?Sized>(
+        &self,
+        config: &LaunchConfigEx,
+        args: &A,
+    ) -> HipResult<()> {
+        #[cfg(has_launch_attributes)]
+        {
+            let base = &config.config;
+            self.validate_launch(base)?;
+            let mut attributes = config.attributes()?;
+            let raw = HIP_LAUNCH_CONFIG {
+                gridDimX: base.grid.x,
+                gridDimY: base.grid.y,
+                gridDimZ: base.grid.z,
+                blockDimX: base.block.x,
+                blockDimY: base.block.y,
+                blockDimZ: base.block.z,
+                sharedMemBytes: base.shared_mem,
+                hStream: base.raw_stream(),
+                attrs: attributes.as_mut_ptr(),
+                numAttrs: attributes.len() as u32,
+            };
+            let mut params = args.as_kernel_params();
+            crate::error::check(hipDrvLaunchKernelEx(
+                &raw,
+                self.as_raw(),
+                params.as_mut_ptr(),
+                std::ptr::null_mut(),
+            ))
+        }
+        #[cfg(not(has_launch_attributes))]
+        {
+            config.check_no_attributes()?;
+            self.launch(&config.config, args)
+        }
+    }
+}
+
+#[cfg(has_launch_attributes)]
+fn launch_attribute(id: hipLaunchAttributeID) -> hipLaunchAttribute {
+    let mut attribute: hipLaunchAttribute = unsafe { zeroed_raw() };
+    attribute.id = id;
+    attribute
+}
+
+/// No patch of the bindings has a launch attribute for cluster dimensions.
+fn unsupported_cluster_dim() -> HipError {
+    HipError::InvalidArgument("cluster dimensions are not supported by HIP".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(has_launch_attributes))]
+    use rstest::*;
+
+    #[test]
+    fn test_cluster_dim_is_unsupported() {
+        let config = LaunchConfigEx::new(LaunchConfig::new(1, 64)).with_cluster_dim((2, 1, 1));
+        #[cfg(has_launch_attributes)]
+        let result = config.attributes().map(|_| ());
+        #[cfg(not(has_launch_attributes))]
+        let result = config.check_no_attributes();
+        assert!(matches!(result, Err(HipError::InvalidArgument(_))));
+    }
+
+    #[cfg(has_launch_attributes)]
+    #[test]
+    fn test_attributes() {
+        let config = LaunchConfigEx::from(LaunchConfig::new(1, 64))
+            .with_cooperative(true)
+            .with_priority(-1);
+        let attributes = config.attributes().unwrap();
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(
+            attributes[0].id,
+            hipLaunchAttributeID_hipLaunchAttributeCooperative
+        );
+        assert_eq!(
+            attributes[1].id,
+            hipLaunchAttributeID_hipLaunchAttributePriority
+        );
+        unsafe {
+            assert_eq!(attributes[0].__bindgen_anon_1.val.cooperative, 1);
+            assert_eq!(attributes[1].__bindgen_anon_1.val.priority, -1);
+        }
+        let plain = LaunchConfigEx::new(LaunchConfig::new(1, 64));
+        assert!(plain.attributes().unwrap().is_empty());
+    }
+
+    #[cfg(not(has_launch_attributes))]
+    #[rstest]
+    #[case::none(LaunchConfigEx::new(LaunchConfig::new(1, 64)), true)]
+    #[case::cooperative(LaunchConfigEx::new(LaunchConfig::new(1, 64)).with_cooperative(true), false)]
+    #[case::priority(LaunchConfigEx::new(LaunchConfig::new(1, 64)).with_priority(1), false)]
+    fn test_older_runtimes_reject_attributes(
+        #[case] config: LaunchConfigEx<'static>,
+        #[case] valid: bool,
+    ) {
+        assert_eq!(config.check_no_attributes().is_ok(), valid);
+    }
+}
@@ -9,6 +9,98 @@
 pub mod hipconfig;
 pub use hipconfig::*;
 
+pub mod capture;
+pub use capture::*;
+
+pub mod code_object;
+pub use code_object::*;
+
+pub mod context;
+pub use context::*;
+
+pub mod cooperative;
+pub use cooperative::*;
+
+pub mod device;
+pub use device::*;
+
+pub mod device_buffer;
+pub use device_buffer::*;
+
+pub mod device_properties;
+pub use device_properties::*;
+
+pub mod error;
+pub use error::*;
+
+pub mod event;
+pub use event::*;
+
+pub mod external;
+pub use external::*;
+
+mod fill;
+
+pub mod function_attributes;
+pub use function_attributes::*;
+
+pub mod graph;
+pub use graph::*;
+
+pub mod graph_description;
+pub use graph_description::*;
+
+pub mod ipc;
+pub use ipc::*;
+
+pub mod kernel_args;
+pub use kernel_args::*;
+
+pub mod kernel_cache;
+pub use kernel_cache::*;
+
+pub mod launch;
+pub use launch::*;
+
+pub mod launch_ex;
+pub use launch_ex::*;
+
+pub mod managed_buffer;
+pub use managed_buffer::*;
+
+pub mod mem_pool;
+pub use mem_pool::*;
+
+pub mod module;
+pub use module::*;
+
+pub mod occupancy;
+pub use occupancy::*;
+
+pub mod pitched_buffer;
+pub use pitched_buffer::*;
+
+pub mod pointer_info;
+pub use pointer_info::*;
+
+pub mod program;
+pub use program::*;
+
+pub mod stream;
+pub use stream::*;
+
+pub mod stream_mem_op;
+pub use stream_mem_op::*;
+
+pub mod texture;
+pub use texture::*;
+
+pub mod topology;
+pub use topology::*;
+
+pub mod virtual_buffer;
+pub use virtual_buffer::*;
+
 mod bindings;
 #[allow(unused)]
 pub use bindings::*;
@@ -231,10 +323,10 @@ extern "C" __global__ void kernel(float a, float *x, float *b, float *out, int n
         unsafe {
             let status_launch = hipModuleLaunchKernel(
                 function, // Kernel function
-                block_dim_x as u32,
+                grid_dim_x as u32,
                 1,
                 1, // Grid dimensions (group of blocks)
-                grid_dim_x as u32,
+                block_dim_x as u32,
                 1,
                 1,                 // Block dimensions (group of threads)
                 0,                 // Shared memory size
@@ -279,4 +371,115 @@ extern "C" __global__ void kernel(float a, float *x, float *b, float *out, int n
             assert_eq!(status, HIP_SUCCESS, "Should free device_out successfully");
         }
     }
+
+    /// Compile `source` with hiprtc and return the code object.
+    fn compile_kernel(source: &std::ffi::CStr) -> Vec<u8> {
+        let mut program: hiprtcProgram = ptr::null_mut();
+        unsafe {
+            let status = hiprtcCreateProgram(
+                &mut program,
+                source.as_ptr(),
+                ptr::null(),
+                0,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            assert_eq!(
+                status, hiprtcResult_HIPRTC_SUCCESS,
+                "Should create the program"
+            );
+            let status = hiprtcCompileProgram(program, 0, ptr::null_mut());
+            assert_eq!(
+                status, hiprtcResult_HIPRTC_SUCCESS,
+                "Should compile the program"
+            );
+            let mut code_size: usize = 0;
+            let status = hiprtcGetCodeSize(program, &mut code_size);
+            assert_eq!(
+                status, hiprtcResult_HIPRTC_SUCCESS,
+                "Should get the code size"
+            );
+            let mut code: Vec<u8> = vec![0; code_size];
+            let status = hiprtcGetCode(program, code.as_mut_ptr() as *mut _);
+            assert_eq!(status, hiprtcResult_HIPRTC_SUCCESS, "Should get the code");
+            let status = hiprtcDestroyProgram(&mut program);
+            assert_eq!(
+                status, hiprtcResult_HIPRTC_SUCCESS,
+                "Should destroy the program"
+            );
+            code
+        }
+    }
+
+    #[test]
+    fn test_launch_kernel_with_module_api() {
+        let source = CString::new(
+            r#"
+extern "C" __global__ void kernel(float a, float *x, float *out, int n) {
+  int tid = blockIdx.x * blockDim.x + threadIdx.x;
+  if (tid < n) {
+    out[tid] = x[tid] * a;
+  }
+}
+ "#,
+        )
+        .unwrap();
+        unsafe {
+            assert_eq!(hipSetDevice(0), HIP_SUCCESS, "Should set the GPU device");
+        }
+        let code = compile_kernel(&source);
+        let module = crate::Module::from_code_object(&code).expect("Should load the module");
+        let function = module.function("kernel").expect("Should find the kernel");
+        assert_eq!(function.name(), "kernel");
+        assert!(module.function("missing").is_err());
+
+        let n = 256i32;
+        let a = 3.0f32;
+        let size = n as usize * std::mem::size_of::<f32>();
+        let x: Vec<f32> = (0..n).map(|i| i as f32).collect();
+        let mut out: Vec<f32> = vec![0.0; n as usize];
+        let mut device_x: *mut libc::c_void = ptr::null_mut();
+        let mut device_out: *mut libc::c_void = ptr::null_mut();
+        unsafe {
+            assert_eq!(hipMalloc(&mut device_x, size), HIP_SUCCESS);
+            assert_eq!(hipMalloc(&mut device_out, size), HIP_SUCCESS);
+            let status = hipMemcpy(
+                device_x,
+                x.as_ptr() as *const libc::c_void,
+                size,
+                hipMemcpyKind_hipMemcpyHostToDevice,
+            );
+            assert_eq!(status, HIP_SUCCESS);
+        }
+
+        let stream = crate::Stream::new().expect("Should create a stream");
+        let config = crate::LaunchConfig::new(n as u32 / 64, 64).with_stream(&stream);
+        unsafe { crate::launch!(function, config, a, device_x, device_out, n) }
+            .expect("Should launch the kernel");
+        // Launch a second time with the packed argument buffer, which must give the same result
+        let mut buffer = crate::KernelArgsBuffer::new();
+        buffer
+            .push(a)
+            .push_device_ptr(device_x)
+            .push_device_ptr(device_out)
+            .push(n);
+        unsafe { function.launch_with_buffer(&config, &buffer) }
+            .expect("Should launch the kernel with the packed arguments");
+        stream.synchronize().expect("Should sync the stream");
+
+        unsafe {
+            let status = hipMemcpy(
+                out.as_mut_ptr() as *mut libc::c_void,
+                device_out,
+                size,
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+            );
+            assert_eq!(status, HIP_SUCCESS);
+            assert_eq!(hipFree(device_x), HIP_SUCCESS);
+            assert_eq!(hipFree(device_out), HIP_SUCCESS);
+        }
+        for i in 0..n as usize {
+            assert_eq!(out[i], a * x[i], "Output mismatch at index {}", i);
+        }
+    }
 }
@@ -0,0 +1,449 @@
+use std::{ffi::c_void, mem, ptr, slice};
+
+use crate::bindings::*;
+use crate::device::device_count;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::KernelArgValue;
+use crate::launch::KernelArg;
+use crate::mem_pool::byte_size;
+use crate::stream::Stream;
+
+/// Device id of the host in managed memory calls, `hipCpuDeviceId`.
+const CPU_DEVICE_ID: i32 = -1;
+/// Device id returned when a range has no location, `hipInvalidDeviceId`.
+const INVALID_DEVICE_ID: i32 = -2;
+
+/// Location of managed memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemLocation {
+    /// System memory of the host.
+    Host,
+    /// Memory of the device with the given id.
+    Device(i32),
+}
+
+impl MemLocation {
+    fn from_raw(device: i32) -> Option<Self> {
+        match device {
+            CPU_DEVICE_ID => Some(MemLocation::Host),
+            device if device >= 0 => Some(MemLocation::Device(device)),
+            _ => None,
+        }
+    }
+
+    fn to_raw(self) -> i32 {
+        match self {
+            MemLocation::Host => CPU_DEVICE_ID,
+            MemLocation::Device(device) => device,
+        }
+    }
+}
+
+/// Usage hint for a managed range, see `hipMemoryAdvise`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemAdvice {
+    /// The range is mostly read, so devices may keep read-only copies.
+    SetReadMostly,
+    /// Undo [`MemAdvice::SetReadMostly`], dropping the read-only copies.
+    UnsetReadMostly,
+    /// Keep the pages at the location, migrating them only when necessary.
+    SetPreferredLocation(MemLocation),
+    /// Undo [`MemAdvice::SetPreferredLocation`].
+    UnsetPreferredLocation,
+    /// Keep the range mapped in the page tables of the device.
+    SetAccessedBy(i32),
+    /// Undo [`MemAdvice::SetAccessedBy`] for the device.
+    UnsetAccessedBy(i32),
+    /// Use coarse-grained coherency on the device, which is faster but only coherent at
+    /// synchronization points.
+    SetCoarseGrain(i32),
+    /// Restore fine-grained coherency on the device.
+    UnsetCoarseGrain(i32),
+}
+
+impl MemAdvice {
+    /// Return the raw advice and the device argument of `hipMemAdvise`.
+    fn to_raw(self) -> (hipMemoryAdvise, i32) {
+        match self {
+            MemAdvice::SetReadMostly => (hipMemoryAdvise_hipMemAdviseSetReadMostly, 0),
+            MemAdvice::UnsetReadMostly => (hipMemoryAdvise_hipMemAdviseUnsetReadMostly, 0),
+            MemAdvice::SetPreferredLocation(location) => (
+                hipMemoryAdvise_hipMemAdviseSetPreferredLocation,
+                location.to_raw(),
+            ),
+            MemAdvice::UnsetPreferredLocation => {
+                (hipMemoryAdvise_hipMemAdviseUnsetPreferredLocation, 0)
+            }
+            MemAdvice::SetAccessedBy(device) => (hipMemoryAdvise_hipMemAdviseSetAccessedBy, device),
+            MemAdvice::UnsetAccessedBy(device) => {
+                (hipMemoryAdvise_hipMemAdviseUnsetAccessedBy, device)
+            }
+            MemAdvice::SetCoarseGrain(device) => {
+                (hipMemoryAdvise_hipMemAdviseSetCoarseGrain, device)
+            }
+            MemAdvice::UnsetCoarseGrain(device) => {
+                (hipMemoryAdvise_hipMemAdviseUnsetCoarseGrain, device)
+            }
+        }
+    }
+}
+
+/// Coherency of a managed range, see `hipMemRangeCoherencyMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoherencyMode {
+    /// Writes are visible to the host and the devices during a kernel.
+    FineGrain,
+    /// Writes are only visible to others at kernel boundaries or synchronization.
+    CoarseGrain,
+    /// Parts of the range have different coherency modes.
+    Indeterminate,
+}
+
+impl From<hipMemRangeCoherencyMode> for CoherencyMode {
+    fn from(mode: hipMemRangeCoherencyMode) -> Self {
+        match mode {
+            hipMemRangeCoherencyMode_hipMemRangeCoherencyModeFineGrain => CoherencyMode::FineGrain,
+            hipMemRangeCoherencyMode_hipMemRangeCoherencyModeCoarseGrain => {
+                CoherencyMode::CoarseGrain
+            }
+            _ => CoherencyMode::Indeterminate,
+        }
+    }
+}
+
+/// Attributes of a managed range returned by [`ManagedBuffer::range_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemRangeInfo {
+    /// True if the whole range was advised with [`MemAdvice::SetReadMostly`].
+    pub read_mostly: bool,
+    /// Preferred location, `None` if unset or not the same for the whole range.
+    pub preferred_location: Option<MemLocation>,
+    /// Devices the range was advised to be accessed by.
+    pub accessed_by: Vec<i32>,
+    /// Location of the last prefetch, `None` if the range was not prefetched as a whole.
+    pub last_prefetch_location: Option<MemLocation>,
+    /// Coherency of the range, also returned by [`ManagedBuffer::coherency_mode`].
+    pub coherency_mode: CoherencyMode,
+}
+
+/// Typed managed allocation made with `hipMallocManaged`, accessible from the host and
+/// every device, freed on drop.
+///
+/// The host must not access the buffer while device work using it may be running, the
+/// stream or device has to be synchronized first.
+#[derive(Debug)]
+pub struct ManagedBuffer<T: KernelArgValue> {
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<T: KernelArgValue> ManagedBuffer<T> {
+    /// Allocate `len` elements of managed memory attached to every stream, with all their
+    /// bytes set to zero.
+    pub fn zeroed(len: usize) -> HipResult<Self> {
+        let size = byte_size::<T>(len)?;
+        let mut raw: *mut c_void = ptr::null_mut();
+        if size > 0 {
+            unsafe {
+                check(hipMallocManaged(&mut raw, size, hipMemAttachGlobal))?;
+                // Writing from the host leaves the pages on the host until a device touches them.
+                ptr::write_bytes(raw as *mut u8, 0, size);
+            }
+        }
+        Ok(Self {
+            ptr: raw as *mut T,
+            len,
+        })
+    }
+
+    /// Allocate a managed buffer and copy `data` into it from the host.
+    pub fn from_slice(data: &[T]) -> HipResult<Self> {
+        let mut buffer = Self::zeroed(data.len())?;
+        buffer.as_mut_slice().copy_from_slice(data);
+        Ok(buffer)
+    }
+
+    /// Return the buffer as a slice on the host.
+    pub fn as_slice(&self) -> &[T] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// Return the buffer as a mutable slice on the host.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// Queue a migration of the buffer to `device` on `stream` with `hipMemPrefetchAsync`.
+    pub fn prefetch_to(&self, device: i32, stream: &Stream) -> HipResult<()> {
+        self.prefetch(MemLocation::Device(device), stream)
+    }
+
+    /// Queue a migration of the buffer to the host on `stream` with `hipMemPrefetchAsync`.
+    pub fn prefetch_to_host(&self, stream: &Stream) -> HipResult<()> {
+        self.prefetch(MemLocation::Host, stream)
+    }
+
+    fn prefetch(&self, location: MemLocation, stream: &Stream) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            check(hipMemPrefetchAsync(
+                self.ptr as *const c_void,
+                self.size_in_bytes(),
+                location.to_raw(),
+                stream.as_raw(),
+            ))
+        }
+    }
+
+    /// Give a usage hint for the whole buffer with `hipMemAdvise`.
+    pub fn advise(&self, advice: MemAdvice) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let (advice, device) = advice.to_raw();
+        unsafe {
+            check(hipMemAdvise(
+                self.ptr as *const c_void,
+                self.size_in_bytes(),
+                advice,
+                device,
+            ))
+        }
+    }
+
+    /// Return the coherency mode of the buffer with `hipMemRangeGetAttribute`.
+    pub fn coherency_mode(&self) -> HipResult<CoherencyMode> {
+        self.check_not_empty()?;
+        let mut mode: hipMemRangeCoherencyMode = 0;
+        unsafe {
+            check(hipMemRangeGetAttribute(
+                &mut mode as *mut _ as *mut c_void,
+                mem::size_of_val(&mode),
+                hipMemRangeAttribute_hipMemRangeAttributeCoherencyMode,
+                self.ptr as *const c_void,
+                self.size_in_bytes(),
+            ))?
+        };
+        Ok(mode.into())
+    }
+
+    /// Return all the attributes of the buffer with `hipMemRangeGetAttributes`.
+    pub fn range_info(&self) -> HipResult<MemRangeInfo> {
+        self.check_not_empty()?;
+        let mut read_mostly = 0i32;
+        let mut preferred_location = INVALID_DEVICE_ID;
+        let mut last_prefetch_location = INVALID_DEVICE_ID;
+        let mut coherency_mode: hipMemRangeCoherencyMode = 0;
+        let mut accessed_by = vec![INVALID_DEVICE_ID; device_count()?.max(1) as usize];
+        let mut data = [
+            &mut read_mostly as *mut i32 as *mut c_void,
+            &mut preferred_location as *mut i32 as *mut c_void,
+            &mut last_prefetch_location as *mut i32 as *mut c_void,
+            &mut coherency_mode as *mut _ as *mut c_void,
+            accessed_by.as_mut_ptr() as *mut c_void,
+        ];
+        let mut sizes = [
+            mem::size_of::<i32>(),
+            mem::size_of::<i32>(),
+            mem::size_of::<i32>(),
+            mem::size_of::<hipMemRangeCoherencyMode>(),
+            mem::size_of_val(accessed_by.as_slice()),
+        ];
+        let mut attributes = [
+            hipMemRangeAttribute_hipMemRangeAttributeReadMostly,
+            hipMemRangeAttribute_hipMemRangeAttributePreferredLocation,
+            hipMemRangeAttribute_hipMemRangeAttributeLastPrefetchLocation,
+            hipMemRangeAttribute_hipMemRangeAttributeCoherencyMode,
+            hipMemRangeAttribute_hipMemRangeAttributeAccessedBy,
+        ];
+        unsafe {
+            check(hipMemRangeGetAttributes(
+                data.as_mut_ptr(),
+                sizes.as_mut_ptr(),
+                attributes.as_mut_ptr(),
+                attributes.len(),
+                self.ptr as *const c_void,
+                self.size_in_bytes(),
+            ))?
+        };
+        accessed_by.retain(|device| *device >= 0);
+        Ok(MemRangeInfo {
+            read_mostly: read_mostly != 0,
+            preferred_location: MemLocation::from_raw(preferred_location),
+            accessed_by,
+            last_prefetch_location: MemLocation::from_raw(last_prefetch_location),
+            coherency_mode: coherency_mode.into(),
+        })
+    }
+
+    fn check_not_empty(&self) -> HipResult<()> {
+        if self.is_empty() {
+            return Err(HipError::InvalidArgument(
+                "an empty managed buffer has no memory range".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Return the pointer, valid on the host and on every device.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// Return the number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the buffer has no element.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the size of the buffer in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        self.len * mem::size_of::<T>()
+    }
+}
+
+impl<T: KernelArgValue> Drop for ManagedBuffer<T> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                hipFree(self.ptr as *mut c_void);
+            }
+        }
+    }
+}
+
+unsafe impl<T: KernelArgValue> KernelArg for ManagedBuffer<T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut T as *mut c_void
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::host(-1, Some(MemLocation::Host))]
+    #[case::device(2, Some(MemLocation::Device(2)))]
+    #[case::invalid(-2, None)]
+    fn test_location_from_raw(#[case] raw: i32, #[case] expected: Option<MemLocation>) {
+        assert_eq!(MemLocation::from_raw(raw), expected);
+        if let Some(location) = expected {
+            assert_eq!(location.to_raw(), raw);
+        }
+    }
+
+    #[rstest]
+    #[case(MemAdvice::SetReadMostly, hipMemoryAdvise_hipMemAdviseSetReadMostly, 0)]
+    #[case(
+        MemAdvice::SetPreferredLocation(MemLocation::Host),
+        hipMemoryAdvise_hipMemAdviseSetPreferredLocation,
+        -1
+    )]
+    #[case(
+        MemAdvice::SetPreferredLocation(MemLocation::Device(1)),
+        hipMemoryAdvise_hipMemAdviseSetPreferredLocation,
+        1
+    )]
+    #[case(
+        MemAdvice::UnsetAccessedBy(3),
+        hipMemoryAdvise_hipMemAdviseUnsetAccessedBy,
+        3
+    )]
+    #[case(
+        MemAdvice::SetCoarseGrain(0),
+        hipMemoryAdvise_hipMemAdviseSetCoarseGrain,
+        0
+    )]
+    fn test_advice_to_raw(
+        #[case] advice: MemAdvice,
+        #[case] expected: hipMemoryAdvise,
+        #[case] device: i32,
+    ) {
+        assert_eq!(advice.to_raw(), (expected, device));
+    }
+
+    #[rstest]
+    #[case(
+        hipMemRangeCoherencyMode_hipMemRangeCoherencyModeFineGrain,
+        CoherencyMode::FineGrain
+    )]
+    #[case(
+        hipMemRangeCoherencyMode_hipMemRangeCoherencyModeCoarseGrain,
+        CoherencyMode::CoarseGrain
+    )]
+    #[case(
+        hipMemRangeCoherencyMode_hipMemRangeCoherencyModeIndeterminate,
+        CoherencyMode::Indeterminate
+    )]
+    fn test_coherency_mode_from_raw(
+        #[case] mode: hipMemRangeCoherencyMode,
+        #[case] expected: CoherencyMode,
+    ) {
+        assert_eq!(CoherencyMode::from(mode), expected);
+    }
+
+    #[test]
+    fn test_empty_buffer_needs_no_runtime() {
+        let mut buffer = ManagedBuffer::<f32>::zeroed(0).unwrap();
+        assert!(buffer.as_slice().is_empty());
+        assert!(buffer.as_mut_slice().is_empty());
+        assert!(buffer.advise(MemAdvice::SetReadMostly).is_ok());
+        assert!(buffer.range_info().is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_advise_and_prefetch_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        if crate::device::device_attribute(
+            hipDeviceAttribute_t_hipDeviceAttributeConcurrentManagedAccess,
+            0,
+        )
+        .unwrap()
+            == 0
+        {
+            // Advice and prefetches are ignored without concurrent managed access.
+            return;
+        }
+        let mut buffer = ManagedBuffer::<u32>::from_slice(&[7; 1024]).expect("Should allocate");
+        buffer.advise(MemAdvice::SetReadMostly).unwrap();
+        buffer
+            .advise(MemAdvice::SetPreferredLocation(MemLocation::Device(0)))
+            .unwrap();
+        buffer.advise(MemAdvice::SetAccessedBy(0)).unwrap();
+        let stream = Stream::new().expect("Should create a stream");
+        buffer.prefetch_to(0, &stream).expect("Should prefetch");
+        stream.synchronize().unwrap();
+
+        let info = buffer.range_info().expect("Should query the range");
+        assert!(info.read_mostly);
+        assert_eq!(info.preferred_location, Some(MemLocation::Device(0)));
+        assert_eq!(info.accessed_by, vec![0]);
+        assert_eq!(info.last_prefetch_location, Some(MemLocation::Device(0)));
+
+        buffer.advise(MemAdvice::UnsetReadMostly).unwrap();
+        buffer.advise(MemAdvice::UnsetPreferredLocation).unwrap();
+        buffer.prefetch_to_host(&stream).expect("Should prefetch");
+        stream.synchronize().unwrap();
+        let info = buffer.range_info().unwrap();
+        assert!(!info.read_mostly);
+        assert_eq!(info.preferred_location, None);
+        assert_eq!(info.last_prefetch_location, Some(MemLocation::Host));
+        buffer.as_mut_slice()[0] = 8;
+        assert_eq!(buffer.as_slice()[..2], [8, 7]);
+    }
+}
@@ -0,0 +1,343 @@
+use std::{ffi::c_void, mem, ptr};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::launch::KernelArg;
+use crate::stream::Stream;
+
+/// Reuse policy of a [`MemPool`], each one enabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemPoolReusePolicy {
+    /// Reuse memory freed on another stream when the allocating stream depends on the free.
+    FollowEventDependencies,
+    /// Reuse memory of completed frees even without a dependency on the free.
+    AllowOpportunistic,
+    /// Insert new stream dependencies to reuse memory freed on another stream.
+    AllowInternalDependencies,
+}
+
+impl MemPoolReusePolicy {
+    fn attribute(self) -> hipMemPoolAttr {
+        match self {
+            MemPoolReusePolicy::FollowEventDependencies => {
+                hipMemPoolAttr_hipMemPoolReuseFollowEventDependencies
+            }
+            MemPoolReusePolicy::AllowOpportunistic => {
+                hipMemPoolAttr_hipMemPoolReuseAllowOpportunistic
+            }
+            MemPoolReusePolicy::AllowInternalDependencies => {
+                hipMemPoolAttr_hipMemPoolReuseAllowInternalDependencies
+            }
+        }
+    }
+}
+
+/// Memory usage counter of a [`MemPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemPoolUsage {
+    /// Memory currently reserved by the pool from the OS.
+    ReservedCurrent,
+    /// High watermark of the reserved memory since the last reset.
+    ReservedHigh,
+    /// Memory currently allocated from the pool.
+    UsedCurrent,
+    /// High watermark of the allocated memory since the last reset.
+    UsedHigh,
+}
+
+impl MemPoolUsage {
+    fn attribute(self) -> hipMemPoolAttr {
+        match self {
+            MemPoolUsage::ReservedCurrent => hipMemPoolAttr_hipMemPoolAttrReservedMemCurrent,
+            MemPoolUsage::ReservedHigh => hipMemPoolAttr_hipMemPoolAttrReservedMemHigh,
+            MemPoolUsage::UsedCurrent => hipMemPoolAttr_hipMemPoolAttrUsedMemCurrent,
+            MemPoolUsage::UsedHigh => hipMemPoolAttr_hipMemPoolAttrUsedMemHigh,
+        }
+    }
+}
+
+/// Stream-ordered memory pool.
+///
+/// Pools created with [`MemPool::new`] are destroyed on drop, HIP releases their memory once
+/// all the allocations are freed. The default pool of a device is never destroyed.
+#[derive(Debug)]
+pub struct MemPool {
+    raw: hipMemPool_t,
+    owned: bool,
+}
+
+impl MemPool {
+    /// Create a pool of pinned device memory on `device` with `hipMemPoolCreate`.
+    ///
+    /// A `max_size` of 0 lets HIP pick a system dependent maximum.
+    pub fn new(device: i32, max_size: usize) -> HipResult<Self> {
+        Self::with_props(&pool_props(
+            device,
+            max_size,
+            hipMemAllocationHandleType_hipMemHandleTypeNone,
+        ))
+    }
+
+    /// Create a pool from raw properties with `hipMemPoolCreate`.
+    pub fn with_props(props: &hipMemPoolProps) -> HipResult<Self> {
+        let mut raw: hipMemPool_t = ptr::null_mut();
+        unsafe { check(hipMemPoolCreate(&mut raw, props))? };
+        Ok(Self { raw, owned: true })
+    }
+
+    /// Return the default pool of `device` with `hipDeviceGetDefaultMemPool`.
+    pub fn device_default(device: i32) -> HipResult<Self> {
+        let mut raw: hipMemPool_t = ptr::null_mut();
+        unsafe { check(hipDeviceGetDefaultMemPool(&mut raw, device))? };
+        Ok(Self { raw, owned: false })
+    }
+
+    /// Wrap a raw pool, destroyed on drop when `owned` is true.
+    pub(crate) fn from_raw(raw: hipMemPool_t, owned: bool) -> Self {
+        Self { raw, owned }
+    }
+
+    /// Return the underlying `hipMemPool_t`.
+    pub fn as_raw(&self) -> hipMemPool_t {
+        self.raw
+    }
+
+    fn set_attribute<V>(&self, attribute: hipMemPoolAttr, mut value: V) -> HipResult<()> {
+        unsafe {
+            check(hipMemPoolSetAttribute(
+                self.raw,
+                attribute,
+                &mut value as *mut V as *mut c_void,
+            ))
+        }
+    }
+
+    fn attribute<V: Default>(&self, attribute: hipMemPoolAttr) -> HipResult<V> {
+        let mut value = V::default();
+        unsafe {
+            check(hipMemPoolGetAttribute(
+                self.raw,
+                attribute,
+                &mut value as *mut V as *mut c_void,
+            ))?
+        };
+        Ok(value)
+    }
+
+    /// Set the amount of reserved memory in bytes the pool holds onto before releasing memory
+    /// back to the OS on the next synchronization.
+    pub fn set_release_threshold(&self, bytes: u64) -> HipResult<()> {
+        self.set_attribute(hipMemPoolAttr_hipMemPoolAttrReleaseThreshold, bytes)
+    }
+
+    /// Return the release threshold in bytes.
+    pub fn release_threshold(&self) -> HipResult<u64> {
+        self.attribute(hipMemPoolAttr_hipMemPoolAttrReleaseThreshold)
+    }
+
+    /// Enable or disable a reuse policy.
+    pub fn set_reuse_policy(&self, policy: MemPoolReusePolicy, enabled: bool) -> HipResult<()> {
+        self.set_attribute(policy.attribute(), enabled as i32)
+    }
+
+    /// Return true if the reuse policy is enabled.
+    pub fn reuse_policy(&self, policy: MemPoolReusePolicy) -> HipResult<bool> {
+        self.attribute::<i32>(policy.attribute())
+            .map(|value| value != 0)
+    }
+
+    /// Return a memory usage counter in bytes.
+    pub fn usage(&self, usage: MemPoolUsage) -> HipResult<u64> {
+        self.attribute(usage.attribute())
+    }
+
+    /// Reset a high watermark counter to the current value.
+    pub fn reset_usage_high(&self, usage: MemPoolUsage) -> HipResult<()> {
+        match usage {
+            MemPoolUsage::ReservedHigh | MemPoolUsage::UsedHigh => {
+                self.set_attribute(usage.attribute(), 0u64)
+            }
+            _ => Err(HipError::InvalidArgument(format!(
+                "only high watermarks can be reset, got {usage:?}"
+            ))),
+        }
+    }
+
+    /// Release memory back to the OS until the pool holds at most `min_bytes_to_keep` bytes.
+    pub fn trim_to(&self, min_bytes_to_keep: usize) -> HipResult<()> {
+        unsafe { check(hipMemPoolTrimTo(self.raw, min_bytes_to_keep)) }
+    }
+
+    /// Allocate `len` elements from the pool, ordered on `stream` with `hipMallocFromPoolAsync`.
+    ///
+    /// The memory can be used by work queued on `stream` after this call, and it is freed
+    /// on the same stream when the buffer is dropped.
+    pub fn alloc_async<'s, T>(
+        &self,
+        len: usize,
+        stream: &'s Stream,
+    ) -> HipResult<PoolBuffer<'s, T>> {
+        let size = byte_size::<T>(len)?;
+        let mut raw: *mut c_void = ptr::null_mut();
+        unsafe {
+            check(hipMallocFromPoolAsync(
+                &mut raw,
+                size,
+                self.raw,
+                stream.as_raw(),
+            ))?
+        };
+        Ok(PoolBuffer::from_raw(raw as *mut T, len, stream))
+    }
+}
+
+impl Drop for MemPool {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe {
+                hipMemPoolDestroy(self.raw);
+            }
+        }
+    }
+}
+
+impl Stream {
+    /// Allocate `len` elements from the current memory pool of the device, ordered on the
+    /// stream with `hipMallocAsync`.
+    pub fn alloc_async<T>(&self, len: usize) -> HipResult<PoolBuffer<'_, T>> {
+        let size = byte_size::<T>(len)?;
+        let mut raw: *mut c_void = ptr::null_mut();
+        unsafe { check(hipMallocAsync(&mut raw, size, self.as_raw()))? };
+        Ok(PoolBuffer::from_raw(raw as *mut T, len, self))
+    }
+}
+
+/// Device allocation ordered on a stream, freed with `hipFreeAsync` on drop.
+#[derive(Debug)]
+pub struct PoolBuffer<'s, T> {
+    ptr: *mut T,
+    len: usize,
+    stream: &'s Stream,
+}
+
+impl<'s, T> PoolBuffer<'s, T> {
+    fn from_raw(ptr: *mut T, len: usize, stream: &'s Stream) -> Self {
+        Self { ptr, len, stream }
+    }
+
+    /// Return the device pointer.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// Return the number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if the buffer has no element.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the size of the buffer in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        self.len * mem::size_of::<T>()
+    }
+
+    /// Return the stream the allocation and the free are ordered on.
+    pub fn stream(&self) -> &'s Stream {
+        self.stream
+    }
+}
+
+impl<T> Drop for PoolBuffer<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            hipFreeAsync(self.ptr as *mut c_void, self.stream.as_raw());
+        }
+    }
+}
+
+unsafe impl<T> KernelArg for PoolBuffer<'_, T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut T as *mut c_void
+    }
+}
+
+/// Return the properties of a pool of pinned device memory on `device`.
+pub(crate) fn pool_props(
+    device: i32,
+    max_size: usize,
+    handle_types: hipMemAllocationHandleType,
+) -> hipMemPoolProps {
+    let mut props: hipMemPoolProps = unsafe { zeroed_raw() };
+    props.allocType = hipMemAllocationType_hipMemAllocationTypePinned;
+    props.handleTypes = handle_types;
+    props.location = hipMemLocation {
+        type_: hipMemLocationType_hipMemLocationTypeDevice,
+        id: device,
+    };
+    props.maxSize = max_size;
+    props
+}
+
+/// Return the size in bytes of `len` elements of `T`.
+pub(crate) fn byte_size<T>(len: usize) -> HipResult<usize> {
+    len.checked_mul(mem::size_of::<T>()).ok_or_else(|| {
+        HipError::InvalidArgument(format!(
+            "{len} elements of {} bytes overflow usize",
+            mem::size_of::<T>()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::empty(0, Some(0))]
+    #[case::floats(256, Some(1024))]
+    #[case::overflow(usize::MAX / 2, None)]
+    fn test_byte_size(#[case] len: usize, #[case] expected: Option<usize>) {
+        assert_eq!(byte_size::<f32>(len).ok(), expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pool_alloc_fill_read_back_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let stream = Stream::new().expect("Should create a stream");
+        let pool = MemPool::new(0, 0).expect("Should create a pool");
+        let buffer = pool
+            .alloc_async::<u32>(1024, &stream)
+            .expect("Should allocate from the pool");
+        assert!(pool.usage(MemPoolUsage::UsedCurrent).unwrap() >= buffer.size_in_bytes() as u64);
+        let mut host = vec![0u32; buffer.len()];
+        unsafe {
+            check(hipMemsetD32Async(
+                buffer.as_ptr() as hipDeviceptr_t,
+                0x1234_5678,
+                buffer.len(),
+                stream.as_raw(),
+            ))
+            .expect("Should fill the buffer");
+            check(hipMemcpyAsync(
+                host.as_mut_ptr() as *mut c_void,
+                buffer.as_ptr() as *const c_void,
+                buffer.size_in_bytes(),
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+                stream.as_raw(),
+            ))
+            .expect("Should read the buffer back");
+        }
+        stream.synchronize().expect("Should sync the stream");
+        assert!(host.iter().all(|&value| value == 0x1234_5678));
+
+        drop(buffer);
+        stream.synchronize().expect("Should sync the free");
+        assert_eq!(pool.usage(MemPoolUsage::UsedCurrent).unwrap(), 0);
+    }
+}
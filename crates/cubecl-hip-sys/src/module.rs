@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::ffi::{c_void, CString};
+use std::mem::{self, MaybeUninit};
+use std::sync::Mutex;
+use std::{marker::PhantomData, ptr};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::function_attributes::LaunchLimits;
+use crate::kernel_args::KernelArgValue;
+use crate::launch::KernelArg;
+use crate::program::Program;
+
+/// Code object loaded on the current device, unloaded on drop.
+///
+/// Modules loaded from a [`Program`] resolve the name expressions registered in the program
+/// to their mangled names when looking up functions and globals.
+#[derive(Debug)]
+pub struct Module {
+    raw: hipModule_t,
+    lowered_names: HashMap<String, String>,
+    /// Launch limits of the functions of the module, queried on their first launch.
+    pub(crate) launch_limits: Mutex<HashMap<hipFunction_t, LaunchLimits>>,
+}
+
+impl Module {
+    /// Load a code object, typically the output of `hiprtcGetCode`, with `hipModuleLoadData`.
+    pub fn from_code_object(code: &[u8]) -> HipResult<Self> {
+        if code.is_empty() {
+            return Err(HipError::InvalidArgument(
+                "code object should not be empty".to_string(),
+            ));
+        }
+        let mut raw: hipModule_t = ptr::null_mut();
+        unsafe { check(hipModuleLoadData(&mut raw, code.as_ptr() as *const _))? };
+        Ok(Self {
+            raw,
+            lowered_names: HashMap::new(),
+            launch_limits: Mutex::default(),
+        })
+    }
+
+    /// Load the code object of a compiled program, along with the mangled names of its
+    /// name expressions.
+    pub fn from_program(program: &Program) -> HipResult<Self> {
+        let lowered_names = program.lowered_names()?;
+        let mut module = Self::from_code_object(&program.code()?)?;
+        module.lowered_names = lowered_names;
+        Ok(module)
+    }
+
+    /// Look up the kernel `name` in the module with `hipModuleGetFunction`.
+    ///
+    /// `name` is either the symbol name or a registered name expression.
+    pub fn function(&self, name: &str) -> HipResult<Function<'_>> {
+        let symbol = resolve_name(&self.lowered_names, name);
+        let c_name = CString::new(symbol).map_err(|_| {
+            HipError::InvalidArgument(format!("kernel name '{name}' contains a nul byte"))
+        })?;
+        let mut raw: hipFunction_t = ptr::null_mut();
+        unsafe { check(hipModuleGetFunction(&mut raw, self.raw, c_name.as_ptr()))? };
+        Ok(Function {
+            raw,
+            name: name.to_string(),
+            module: self,
+        })
+    }
+
+    /// Look up the `__device__` or `__constant__` variable `name` in the module with
+    /// `hipModuleGetGlobal`, checking that its size is the size of `T`.
+    ///
+    /// `name` is either the symbol name or a registered name expression.
+    pub fn global<T: KernelArgValue>(&self, name: &str) -> HipResult<DeviceGlobal<'_, T>> {
+        let symbol = resolve_name(&self.lowered_names, name);
+        let c_name = CString::new(symbol).map_err(|_| {
+            HipError::InvalidArgument(format!("global name '{name}' contains a nul byte"))
+        })?;
+        let mut ptr: hipDeviceptr_t = ptr::null_mut();
+        let mut size = 0;
+        unsafe {
+            check(hipModuleGetGlobal(
+                &mut ptr,
+                &mut size,
+                self.raw,
+                c_name.as_ptr(),
+            ))?
+        };
+        check_global_size::<T>(name, size)?;
+        Ok(DeviceGlobal {
+            ptr: ptr as *mut T,
+            _module: PhantomData,
+        })
+    }
+
+    /// Return the underlying `hipModule_t`.
+    pub fn as_raw(&self) -> hipModule_t {
+        self.raw
+    }
+}
+
+/// Return the mangled name of `name` if it is a registered name expression, taking the
+/// address of the symbol or not, or `name` itself otherwise.
+fn resolve_name<'a>(lowered_names: &'a HashMap<String, String>, name: &'a str) -> &'a str {
+    let address_of = format!("&{name}");
+    lowered_names
+        .get(name)
+        .or_else(|| lowered_names.get(&address_of))
+        .or_else(|| lowered_names.get(name.strip_prefix('&')?))
+        .map_or(name, String::as_str)
+}
+
+fn check_global_size<T>(name: &str, size: usize) -> HipResult<()> {
+    if size != mem::size_of::<T>() {
+        return Err(HipError::InvalidArgument(format!(
+            "global '{name}' has {size} bytes, expected {} bytes",
+            mem::size_of::<T>()
+        )));
+    }
+    Ok(())
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        unsafe {
+            hipModuleUnload(self.raw);
+        }
+    }
+}
+
+/// Kernel function of a loaded [`Module`].
+///
+/// The function borrows its module so that it cannot outlive the loaded code object.
+#[derive(Debug)]
+pub struct Function<'m> {
+    raw: hipFunction_t,
+    name: String,
+    pub(crate) module: &'m Module,
+}
+
+impl Function<'_> {
+    /// Return the name the function was looked up with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the underlying `hipFunction_t`.
+    pub fn as_raw(&self) -> hipFunction_t {
+        self.raw
+    }
+}
+
+/// Typed `__device__` or `__constant__` variable of a loaded [`Module`], see
+/// [`Module::global`].
+///
+/// Passed to a kernel, the global is its device address.
+#[derive(Debug)]
+pub struct DeviceGlobal<'m, T: KernelArgValue> {
+    ptr: *mut T,
+    _module: PhantomData<&'m Module>,
+}
+
+impl<T: KernelArgValue> DeviceGlobal<'_, T> {
+    /// Copy the value of the variable to the host, blocking until the copy is complete.
+    pub fn read(&self) -> HipResult<T> {
+        let mut value = MaybeUninit::<T>::uninit();
+        unsafe {
+            check(hipMemcpy(
+                value.as_mut_ptr() as *mut c_void,
+                self.ptr as *const c_void,
+                mem::size_of::<T>(),
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+            ))?;
+            Ok(value.assume_init())
+        }
+    }
+
+    /// Copy `value` from the host into the variable, blocking until the copy is complete.
+    pub fn write(&mut self, value: T) -> HipResult<()> {
+        unsafe {
+            check(hipMemcpy(
+                self.ptr as *mut c_void,
+                &value as *const T as *const c_void,
+                mem::size_of::<T>(),
+                hipMemcpyKind_hipMemcpyHostToDevice,
+            ))
+        }
+    }
+
+    /// Return the device address of the variable.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+}
+
+unsafe impl<T: KernelArgValue> KernelArg for DeviceGlobal<'_, T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut T as *mut c_void
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::expression("table<int>", "_Z5tableIiE")]
+    #[case::address_of_expression("scale<float>", "_Z5scaleIfEvPf")]
+    #[case::address_of_name("&table<int>", "_Z5tableIiE")]
+    #[case::plain_symbol("counter", "counter")]
+    fn test_resolve_name(#[case] name: &str, #[case] expected: &str) {
+        let lowered_names = HashMap::from([
+            ("table<int>".to_string(), "_Z5tableIiE".to_string()),
+            ("&scale<float>".to_string(), "_Z5scaleIfEvPf".to_string()),
+        ]);
+        assert_eq!(resolve_name(&lowered_names, name), expected);
+    }
+
+    #[test]
+    fn test_check_global_size() {
+        assert!(check_global_size::<[f32; 4]>("table", 16).is_ok());
+        assert!(matches!(
+            check_global_size::<u64>("counter", 4),
+            Err(HipError::InvalidArgument(_))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_global_name_expression_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let source = r#"
+template <typename T> __device__ T bias;
+
+template <typename T> __global__ void add_bias(T *data) {
+  data[threadIdx.x] += bias<T>;
+  bias<T> += 1;
+}
+"#;
+        let mut program = Program::new(source, "globals.hip").expect("Should create");
+        program.add_name_expression("&bias<int>").unwrap();
+        program.add_name_expression("add_bias<int>").unwrap();
+        program
+            .compile::<&str>(&[])
+            .expect("Should compile the program");
+        let module = Module::from_program(&program).expect("Should load the module");
+
+        let mut bias = module
+            .global::<i32>("bias<int>")
+            .expect("Should find the global by name expression");
+        assert_eq!(bias.read().unwrap(), 0);
+        bias.write(10).expect("Should write the global");
+        assert_eq!(bias.read().unwrap(), 10);
+
+        let data = crate::DeviceBuffer::from_slice(&[1i32]).expect("Should allocate");
+        let function = module.function("add_bias<int>").unwrap();
+        unsafe { function.launch(&crate::LaunchConfig::new(1, 1), &(&data,)) }
+            .expect("Should launch the kernel");
+        assert_eq!(data.to_vec().unwrap(), vec![11]);
+        // The kernel incremented the global after reading it.
+        assert_eq!(bias.read().unwrap(), 11);
+    }
+}
@@ -0,0 +1,570 @@
+use crate::bindings::*;
+use crate::code_object::KernelMetadata;
+use crate::device_properties::DeviceProperties;
+use crate::error::{check, HipError, HipResult};
+use crate::module::Function;
+
+/// Block size that maximizes occupancy and the smallest grid reaching it on every compute
+/// unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSizeSuggestion {
+    /// Number of blocks filling every compute unit at the occupancy of `block_size`.
+    pub min_grid_size: u32,
+    /// Threads per block.
+    pub block_size: u32,
+}
+
+/// Occupancy queries of a loaded kernel, created by [`Function::occupancy`].
+#[derive(Debug, Clone, Copy)]
+pub struct Occupancy<'a> {
+    function: &'a Function<'a>,
+    flags: u32,
+}
+
+impl Function<'_> {
+    /// Return the occupancy calculator of the function on the current device.
+    pub fn occupancy(&self) -> Occupancy<'_> {
+        Occupancy {
+            function: self,
+            flags: hipOccupancyDefault,
+        }
+    }
+}
+
+impl Occupancy<'_> {
+    /// Use the `WithFlags` variants of the queries with `flags`, for instance
+    /// `hipOccupancyDisableCachingOverride`.
+    pub fn with_flags(mut self, flags: u32) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Return the number of blocks of `block_size` threads that can be resident on a compute
+    /// unit, with `hipModuleOccupancyMaxActiveBlocksPerMultiprocessor`.
+    pub fn max_active_blocks(&self, block_size: u32, dynamic_shared_mem: usize) -> HipResult<u32> {
+        let mut blocks = 0;
+        let raw = self.function.as_raw();
+        let block_size = to_int(block_size)?;
+        unsafe {
+            check(if self.flags == hipOccupancyDefault {
+                hipModuleOccupancyMaxActiveBlocksPerMultiprocessor(
+                    &mut blocks,
+                    raw,
+                    block_size,
+                    dynamic_shared_mem,
+                )
+            } else {
+                hipModuleOccupancyMaxActiveBlocksPerMultiprocessorWithFlags(
+                    &mut blocks,
+                    raw,
+                    block_size,
+                    dynamic_shared_mem,
+                    self.flags,
+                )
+            })?
+        };
+        Ok(blocks as u32)
+    }
+
+    /// Return the block size that maximizes occupancy with the same dynamic shared memory
+    /// for every block size, with `hipModuleOccupancyMaxPotentialBlockSize`.
+    ///
+    /// A `block_size_limit` of 0 means no limit other than the one of the kernel.
+    pub fn max_potential_block_size(
+        &self,
+        dynamic_shared_mem: usize,
+        block_size_limit: u32,
+    ) -> HipResult<BlockSizeSuggestion> {
+        let mut grid_size = 0;
+        let mut block_size = 0;
+        let raw = self.function.as_raw();
+        let limit = to_int(block_size_limit)?;
+        unsafe {
+            check(if self.flags == hipOccupancyDefault {
+                hipModuleOccupancyMaxPotentialBlockSize(
+                    &mut grid_size,
+                    &mut block_size,
+                    raw,
+                    dynamic_shared_mem,
+                    limit,
+                )
+            } else {
+                hipModuleOccupancyMaxPotentialBlockSizeWithFlags(
+                    &mut grid_size,
+                    &mut block_size,
+                    raw,
+                    dynamic_shared_mem,
+                    limit,
+                    self.flags,
+                )
+            })?
+        };
+        Ok(BlockSizeSuggestion {
+            min_grid_size: grid_size as u32,
+            block_size: block_size as u32,
+        })
+    }
+
+    /// Return the block size that maximizes occupancy when the dynamic shared memory of a
+    /// block depends on its size, for instance one element per thread.
+    ///
+    /// HIP only accepts a constant amount of shared memory, so every multiple of the warp size
+    /// is tried with [`Occupancy::max_active_blocks`].
+    pub fn max_potential_block_size_with<F>(
+        &self,
+        dynamic_shared_mem: F,
+        block_size_limit: u32,
+    ) -> HipResult<BlockSizeSuggestion>
+    where
+        F: Fn(u32) -> usize,
+    {
+        let properties = DeviceProperties::current()?;
+        best_block_size(
+            properties.warp_size,
+            limit(properties.max_threads_per_block, block_size_limit),
+            properties.multiprocessor_count,
+            dynamic_shared_mem,
+            |block_size, shared_mem| self.max_active_blocks(block_size, shared_mem),
+        )
+    }
+}
+
+fn to_int(value: u32) -> HipResult<i32> {
+    i32::try_from(value)
+        .map_err(|_| HipError::InvalidArgument(format!("{value} does not fit in an int")))
+}
+
+fn limit(max: u32, limit: u32) -> u32 {
+    if limit == 0 {
+        max
+    } else {
+        max.min(limit)
+    }
+}
+
+/// Try block sizes from `max_block_size` down to `granularity` in steps of `granularity` and
+/// keep the one with the most resident threads, the largest one on ties.
+fn best_block_size<S, A>(
+    granularity: u32,
+    max_block_size: u32,
+    compute_units: u32,
+    dynamic_shared_mem: S,
+    mut active_blocks: A,
+) -> HipResult<BlockSizeSuggestion>
+where
+    S: Fn(u32) -> usize,
+    A: FnMut(u32, usize) -> HipResult<u32>,
+{
+    let granularity = granularity.max(1);
+    let unaligned = (max_block_size % granularity != 0).then_some(max_block_size);
+    let aligned = (1..=max_block_size / granularity)
+        .rev()
+        .map(|n| n * granularity);
+    let mut best: Option<(u32, BlockSizeSuggestion)> = None;
+    for block_size in unaligned.into_iter().chain(aligned) {
+        let blocks = active_blocks(block_size, dynamic_shared_mem(block_size))?;
+        let threads = blocks * block_size;
+        if threads > best.map_or(0, |(threads, _)| threads) {
+            let suggestion = BlockSizeSuggestion {
+                min_grid_size: blocks * compute_units,
+                block_size,
+            };
+            best = Some((threads, suggestion));
+        }
+    }
+    best.map(|(_, suggestion)| suggestion).ok_or_else(|| {
+        HipError::InvalidArgument(format!(
+            "no block size up to {max_block_size} fits on a compute unit"
+        ))
+    })
+}
+
+/// Resource that limits the number of resident blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OccupancyLimiter {
+    /// Wave slots of the SIMDs.
+    Waves,
+    /// Vector registers of the SIMDs, including AGPRs on devices that unify them.
+    Vgprs,
+    /// Scalar registers of the SIMDs, only on gfx9 and earlier.
+    Sgprs,
+    /// Local data share, the shared memory of the compute unit.
+    Lds,
+    /// The block is larger than what the kernel or the device accepts.
+    BlockSize,
+}
+
+/// Predicted occupancy of a block size on a compute unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OccupancyEstimate {
+    /// Resident blocks on a compute unit.
+    pub blocks_per_cu: u32,
+    /// Resident waves on a compute unit, over all its SIMDs.
+    pub waves_per_cu: u32,
+    /// Resource reached first, which limits `blocks_per_cu`.
+    pub limiter: OccupancyLimiter,
+}
+
+/// Occupancy model of a device that predicts occupancy from code object metadata without
+/// loading the kernel, following the register allocation rules of the AMDGPU backend.
+///
+/// Register counts are given for wave64 and doubled for wave32 kernels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OccupancyModel {
+    /// Number of compute units of the device.
+    pub compute_units: u32,
+    /// Number of SIMDs of a compute unit.
+    pub simds_per_cu: u32,
+    /// Wave slots of a SIMD.
+    pub max_waves_per_simd: u32,
+    /// VGPRs available to the waves of a SIMD.
+    pub vgprs_per_simd: u32,
+    /// Allocation granule of VGPRs, as returned by `getVGPRAllocGranule` in LLVM's
+    /// `AMDGPUBaseInfo.cpp` for wave64: 4 before gfx10 except on gfx90a and later CDNA, 8 on
+    /// gfx10 and later, 12 with the larger register files of some gfx11 devices.
+    pub vgpr_granule: u32,
+    /// True if AGPRs are allocated from the VGPR file, as on gfx90a and later CDNA devices.
+    pub unified_agprs: bool,
+    /// SGPRs available to the waves of a SIMD, `None` when they do not limit occupancy as on
+    /// gfx10 and later.
+    pub sgprs_per_simd: Option<u32>,
+    /// Local data share in bytes of a compute unit.
+    pub lds_per_cu: usize,
+    /// Largest block in threads the device accepts.
+    pub max_threads_per_block: u32,
+}
+
+const SGPR_GRANULE: u32 = 16;
+
+impl OccupancyModel {
+    /// Build the model of a device from its architecture and properties.
+    pub fn new(properties: &DeviceProperties) -> HipResult<Self> {
+        let arch = properties.arch();
+        let generation = arch
+            .strip_prefix("gfx")
+            .filter(|version| version.len() >= 3)
+            .and_then(|version| version[..version.len() - 2].parse::<u32>().ok())
+            .ok_or_else(|| HipError::InvalidArgument(format!("unknown architecture '{arch}'")))?;
+        let cdna2 = ["gfx90a", "gfx94", "gfx95"]
+            .iter()
+            .any(|prefix| arch.starts_with(prefix));
+        let full_vgprs = matches!(arch, "gfx1100" | "gfx1101" | "gfx1151") || generation >= 12;
+        let (max_waves_per_simd, vgprs_per_simd, vgpr_granule) = match generation {
+            _ if cdna2 => (8, 512, 8),
+            ..=9 => (10, 256, 4),
+            10 => (20, 512, 8),
+            _ if full_vgprs => (16, 768, 12),
+            _ => (16, 512, 8),
+        };
+        Ok(Self {
+            compute_units: properties.multiprocessor_count,
+            simds_per_cu: 4,
+            max_waves_per_simd,
+            vgprs_per_simd,
+            vgpr_granule,
+            unified_agprs: cdna2,
+            sgprs_per_simd: (generation <= 9).then_some(800),
+            lds_per_cu: properties.shared_mem_per_multiprocessor,
+            max_threads_per_block: properties.max_threads_per_block,
+        })
+    }
+
+    /// Predict how many blocks of `block_size` threads of `kernel` can be resident on a
+    /// compute unit.
+    pub fn estimate(
+        &self,
+        kernel: &KernelMetadata,
+        block_size: u32,
+        dynamic_shared_mem: usize,
+    ) -> OccupancyEstimate {
+        let wavefront_size = kernel.wavefront_size.max(1);
+        let waves_per_block = block_size.div_ceil(wavefront_size);
+        if block_size == 0
+            || block_size > kernel.max_flat_workgroup_size
+            || block_size > self.max_threads_per_block
+        {
+            return OccupancyEstimate {
+                blocks_per_cu: 0,
+                waves_per_cu: 0,
+                limiter: OccupancyLimiter::BlockSize,
+            };
+        }
+
+        // The register files hold twice as many wave32 waves.
+        let scale = (64 / wavefront_size).max(1);
+        let vgprs = if self.unified_agprs {
+            kernel.vgpr_count.next_multiple_of(4) + kernel.agpr_count.unwrap_or(0)
+        } else {
+            kernel.vgpr_count.max(kernel.agpr_count.unwrap_or(0))
+        };
+        let vgpr_waves = (self.vgprs_per_simd * scale)
+            / vgprs.max(1).next_multiple_of(self.vgpr_granule * scale);
+        let sgpr_waves = self
+            .sgprs_per_simd
+            .map(|sgprs| sgprs / (kernel.sgpr_count.max(1).next_multiple_of(SGPR_GRANULE)));
+        let lds = kernel.lds_size as usize + dynamic_shared_mem;
+
+        let blocks = |waves_per_simd: u32| self.simds_per_cu * waves_per_simd / waves_per_block;
+        let mut limits = vec![
+            (OccupancyLimiter::Waves, blocks(self.max_waves_per_simd)),
+            (OccupancyLimiter::Vgprs, blocks(vgpr_waves)),
+        ];
+        if let Some(sgpr_waves) = sgpr_waves {
+            limits.push((OccupancyLimiter::Sgprs, blocks(sgpr_waves)));
+        }
+        if let Some(lds_blocks) = self.lds_per_cu.checked_div(lds) {
+            limits.push((
+                OccupancyLimiter::Lds,
+                lds_blocks.min(u32::MAX as usize) as u32,
+            ));
+        }
+        // The first limit wins ties, so register pressure is only reported when it matters.
+        let (limiter, blocks_per_cu) = limits
+            .into_iter()
+            .reduce(|best, limit| if limit.1 < best.1 { limit } else { best })
+            .unwrap();
+        OccupancyEstimate {
+            blocks_per_cu,
+            waves_per_cu: blocks_per_cu * waves_per_block,
+            limiter,
+        }
+    }
+
+    /// Predict the block size that maximizes the occupancy of `kernel`, with the dynamic
+    /// shared memory of a block given by `dynamic_shared_mem` for each block size.
+    pub fn max_potential_block_size<F>(
+        &self,
+        kernel: &KernelMetadata,
+        dynamic_shared_mem: F,
+        block_size_limit: u32,
+    ) -> HipResult<BlockSizeSuggestion>
+    where
+        F: Fn(u32) -> usize,
+    {
+        best_block_size(
+            kernel.wavefront_size,
+            limit(
+                self.max_threads_per_block
+                    .min(kernel.max_flat_workgroup_size),
+                block_size_limit,
+            ),
+            self.compute_units,
+            dynamic_shared_mem,
+            |block_size, shared_mem| {
+                Ok(self.estimate(kernel, block_size, shared_mem).blocks_per_cu)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_object::CodeObjectMetadata;
+    use rstest::*;
+
+    const GFX90A: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/kernels_gfx90a.co"
+    ));
+    const GFX1030: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/kernels_gfx1030.co"
+    ));
+
+    fn properties(gcn_arch_name: &str, warp_size: u32) -> DeviceProperties {
+        DeviceProperties {
+            name: String::new(),
+            gcn_arch_name: gcn_arch_name.to_string(),
+            total_global_mem: 64 << 30,
+            multiprocessor_count: 110,
+            warp_size,
+            max_threads_per_block: 1024,
+            max_threads_per_multiprocessor: 2048,
+            max_block_dim: [1024; 3],
+            max_grid_dim: [i32::MAX as u32; 3],
+            shared_mem_per_block: 64 << 10,
+            shared_mem_per_multiprocessor: 64 << 10,
+            regs_per_block: 65536,
+            regs_per_multiprocessor: 65536,
+            l2_cache_size: 8 << 20,
+            clock_rate_khz: 1_700_000,
+        }
+    }
+
+    fn kernel(code: &[u8], name: &str) -> KernelMetadata {
+        CodeObjectMetadata::parse(code)
+            .unwrap()
+            .kernel(name)
+            .unwrap()
+            .clone()
+    }
+
+    #[rstest]
+    #[case::mi250("gfx90a:sramecc+:xnack-", 8, 512, 8, true, Some(800))]
+    #[case::mi100("gfx908", 10, 256, 4, false, Some(800))]
+    #[case::mi300("gfx942", 8, 512, 8, true, Some(800))]
+    #[case::rdna2("gfx1030", 20, 512, 8, false, None)]
+    #[case::rdna3("gfx1100", 16, 768, 12, false, None)]
+    #[case::rdna3_small("gfx1102", 16, 512, 8, false, None)]
+    fn test_model_from_arch(
+        #[case] arch: &str,
+        #[case] max_waves_per_simd: u32,
+        #[case] vgprs_per_simd: u32,
+        #[case] vgpr_granule: u32,
+        #[case] unified_agprs: bool,
+        #[case] sgprs_per_simd: Option<u32>,
+    ) {
+        let model = OccupancyModel::new(&properties(arch, 64)).unwrap();
+        assert_eq!(model.max_waves_per_simd, max_waves_per_simd);
+        assert_eq!(model.vgprs_per_simd, vgprs_per_simd);
+        assert_eq!(model.vgpr_granule, vgpr_granule);
+        assert_eq!(model.unified_agprs, unified_agprs);
+        assert_eq!(model.sgprs_per_simd, sgprs_per_simd);
+        assert_eq!(model.compute_units, 110);
+    }
+
+    #[test]
+    fn test_model_rejects_unknown_arch() {
+        assert!(OccupancyModel::new(&properties("sm_90", 32)).is_err());
+    }
+
+    #[rstest]
+    #[case::wave_slots(6, None, 256, 8, OccupancyLimiter::Waves)]
+    #[case::vgprs(128, None, 256, 4, OccupancyLimiter::Vgprs)]
+    #[case::agprs_share_the_file(64, Some(192), 256, 2, OccupancyLimiter::Vgprs)]
+    #[case::block_too_large(6, None, 512, 0, OccupancyLimiter::BlockSize)]
+    fn test_estimate_gfx90a(
+        #[case] vgpr_count: u32,
+        #[case] agpr_count: Option<u32>,
+        #[case] block_size: u32,
+        #[case] blocks: u32,
+        #[case] limiter: OccupancyLimiter,
+    ) {
+        let model = OccupancyModel::new(&properties("gfx90a", 64)).unwrap();
+        let mut axpb = kernel(GFX90A, "axpb");
+        axpb.vgpr_count = vgpr_count;
+        axpb.agpr_count = agpr_count;
+        let estimate = model.estimate(&axpb, block_size, 0);
+        assert_eq!(estimate.blocks_per_cu, blocks);
+        assert_eq!(estimate.limiter, limiter);
+        assert_eq!(estimate.waves_per_cu, blocks * block_size.div_ceil(64));
+    }
+
+    #[test]
+    fn test_estimate_sgprs_limit_gfx9_only() {
+        let mut axpb = kernel(GFX90A, "axpb");
+        axpb.sgpr_count = 100;
+        let gfx90a = OccupancyModel::new(&properties("gfx90a", 64)).unwrap();
+        let estimate = gfx90a.estimate(&axpb, 64, 0);
+        assert_eq!(estimate.limiter, OccupancyLimiter::Sgprs);
+        assert_eq!(estimate.blocks_per_cu, 4 * (800 / 112));
+
+        let mut axpb = kernel(GFX1030, "axpb");
+        axpb.sgpr_count = 100;
+        let gfx1030 = OccupancyModel::new(&properties("gfx1030", 32)).unwrap();
+        assert_eq!(
+            gfx1030.estimate(&axpb, 64, 0).limiter,
+            OccupancyLimiter::Waves
+        );
+    }
+
+    #[test]
+    fn test_estimate_lds_includes_static_and_dynamic() {
+        let model = OccupancyModel::new(&properties("gfx1030", 32)).unwrap();
+        let reduce_tile = kernel(GFX1030, "reduce_tile");
+        // 1 KiB of static LDS and 15 KiB of dynamic LDS leave room for 4 blocks.
+        let estimate = model.estimate(&reduce_tile, 256, 15 << 10);
+        assert_eq!(estimate.blocks_per_cu, 4);
+        assert_eq!(estimate.limiter, OccupancyLimiter::Lds);
+        assert_eq!(estimate.waves_per_cu, 32);
+    }
+
+    #[test]
+    fn test_wave32_doubles_register_files() {
+        let model = OccupancyModel::new(&properties("gfx1030", 32)).unwrap();
+        let mut axpb = kernel(GFX1030, "axpb");
+        axpb.vgpr_count = 256;
+        // 1024 wave32 VGPRs hold 4 waves of 256 registers on each of the 4 SIMDs.
+        let estimate = model.estimate(&axpb, 32, 0);
+        assert_eq!(estimate.blocks_per_cu, 16);
+        assert_eq!(estimate.limiter, OccupancyLimiter::Vgprs);
+    }
+
+    #[test]
+    fn test_gfx10_wave32_vgpr_granule() {
+        let model = OccupancyModel::new(&properties("gfx1030", 32)).unwrap();
+        let mut axpb = kernel(GFX1030, "axpb");
+        axpb.vgpr_count = 52;
+        // wave32 VGPRs are allocated by 16, so 52 registers take 64 and 16 waves fit a SIMD.
+        let estimate = model.estimate(&axpb, 32, 0);
+        assert_eq!(estimate.blocks_per_cu, 4 * 16);
+        assert_eq!(estimate.limiter, OccupancyLimiter::Vgprs);
+    }
+
+    #[test]
+    fn test_max_potential_block_size_fixture() {
+        let model = OccupancyModel::new(&properties("gfx90a", 64)).unwrap();
+        let axpb = kernel(GFX90A, "axpb");
+        let suggestion = model.max_potential_block_size(&axpb, |_| 0, 0).unwrap();
+        assert_eq!(
+            suggestion,
+            BlockSizeSuggestion {
+                min_grid_size: 8 * 110,
+                block_size: 256,
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_potential_block_size_with_variable_shared_mem() {
+        let model = OccupancyModel::new(&properties("gfx90a", 64)).unwrap();
+        let reduce_tile = kernel(GFX90A, "reduce_tile");
+        // With 1 KiB of static LDS and 128 bytes per thread, 7 blocks of 64 threads fit in
+        // 64 KiB, more threads than with any larger block.
+        let suggestion = model
+            .max_potential_block_size(&reduce_tile, |threads| threads as usize * 128, 0)
+            .unwrap();
+        assert_eq!(suggestion.block_size, 64);
+        assert_eq!(suggestion.min_grid_size, 7 * 110);
+    }
+
+    #[rstest]
+    #[case::prefers_larger_on_ties(64, 256, vec![(256, 1), (192, 1), (128, 2), (64, 4)], 256)]
+    #[case::most_threads(64, 256, vec![(256, 1), (192, 2), (128, 2), (64, 4)], 192)]
+    #[case::unaligned_limit(64, 100, vec![(100, 3), (64, 4)], 100)]
+    fn test_best_block_size(
+        #[case] granularity: u32,
+        #[case] max_block_size: u32,
+        #[case] blocks: Vec<(u32, u32)>,
+        #[case] expected: u32,
+    ) {
+        let mut tried = Vec::new();
+        let suggestion = best_block_size(
+            granularity,
+            max_block_size,
+            2,
+            |_| 0,
+            |block_size, _| {
+                tried.push(block_size);
+                Ok(blocks
+                    .iter()
+                    .find(|(size, _)| *size == block_size)
+                    .unwrap()
+                    .1)
+            },
+        )
+        .unwrap();
+        assert_eq!(suggestion.block_size, expected);
+        assert_eq!(
+            tried,
+            blocks.iter().map(|(size, _)| *size).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_best_block_size_without_fit() {
+        let result = best_block_size(64, 128, 1, |_| 0, |_, _| Ok(0));
+        assert!(matches!(result, Err(HipError::InvalidArgument(_))));
+    }
+}
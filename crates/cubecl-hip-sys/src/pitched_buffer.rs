@@ -0,0 +1,797 @@
+use std::{ffi::c_void, marker::PhantomData, mem, ptr};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::KernelArgValue;
+use crate::launch::KernelArg;
+use crate::mem_pool::byte_size;
+use crate::stream::Stream;
+
+/// Host slice seen as rows starting every `row_stride` elements, and for 3D copies as slices
+/// starting every `slice_rows` rows.
+#[derive(Debug, Clone, Copy)]
+pub struct StridedView<'a, T> {
+    pub(crate) data: &'a [T],
+    pub(crate) row_stride: usize,
+    pub(crate) slice_rows: usize,
+}
+
+impl<'a, T> StridedView<'a, T> {
+    /// View `data` with rows starting every `row_stride` elements and no padding rows
+    /// between slices.
+    pub fn new(data: &'a [T], row_stride: usize) -> Self {
+        Self {
+            data,
+            row_stride,
+            slice_rows: 0,
+        }
+    }
+
+    /// Start the slices every `slice_rows` rows.
+    pub fn with_slice_rows(mut self, slice_rows: usize) -> Self {
+        self.slice_rows = slice_rows;
+        self
+    }
+}
+
+/// Mutable host slice seen as rows starting every `row_stride` elements, and for 3D copies
+/// as slices starting every `slice_rows` rows.
+#[derive(Debug)]
+pub struct StridedViewMut<'a, T> {
+    pub(crate) data: &'a mut [T],
+    pub(crate) row_stride: usize,
+    pub(crate) slice_rows: usize,
+}
+
+impl<'a, T> StridedViewMut<'a, T> {
+    /// View `data` with rows starting every `row_stride` elements and no padding rows
+    /// between slices.
+    pub fn new(data: &'a mut [T], row_stride: usize) -> Self {
+        Self {
+            data,
+            row_stride,
+            slice_rows: 0,
+        }
+    }
+
+    /// Start the slices every `slice_rows` rows.
+    pub fn with_slice_rows(mut self, slice_rows: usize) -> Self {
+        self.slice_rows = slice_rows;
+        self
+    }
+}
+
+/// Host layout checked against the extent of a copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct HostLayout {
+    /// Distance between rows in bytes.
+    pub(crate) pitch: usize,
+    /// Distance between slices in rows.
+    pub(crate) slice_rows: usize,
+}
+
+/// Check that a host slice of `len` elements holds `[width, height, depth]` elements with
+/// the given strides.
+pub(crate) fn check_layout<T>(
+    len: usize,
+    row_stride: usize,
+    slice_rows: usize,
+    [width, height, depth]: [usize; 3],
+) -> HipResult<HostLayout> {
+    let slice_rows = if slice_rows == 0 { height } else { slice_rows };
+    if row_stride < width {
+        return Err(HipError::InvalidArgument(format!(
+            "row stride of {row_stride} elements is smaller than the width of {width} elements"
+        )));
+    }
+    if slice_rows < height {
+        return Err(HipError::InvalidArgument(format!(
+            "slices of {slice_rows} rows are smaller than the height of {height} rows"
+        )));
+    }
+    if width > 0 && height > 0 && depth > 0 {
+        let required = (depth - 1)
+            .checked_mul(slice_rows)
+            .and_then(|rows| rows.checked_add(height - 1))
+            .and_then(|rows| rows.checked_mul(row_stride))
+            .and_then(|elements| elements.checked_add(width))
+            .ok_or_else(|| HipError::InvalidArgument("view size overflows usize".to_string()))?;
+        if len < required {
+            return Err(HipError::InvalidArgument(format!(
+                "view of {len} elements is too short for {width}x{height}x{depth} elements, \
+                 {required} are needed"
+            )));
+        }
+    }
+    Ok(HostLayout {
+        pitch: byte_size::<T>(row_stride)?,
+        slice_rows,
+    })
+}
+
+fn check_extent(expected: [usize; 3], actual: [usize; 3]) -> HipResult<()> {
+    if expected != actual {
+        return Err(HipError::InvalidArgument(format!(
+            "buffer extent {expected:?} does not match {actual:?}"
+        )));
+    }
+    Ok(())
+}
+
+/// Typed 2D device allocation made with `hipMallocPitch`, whose rows start every
+/// [`pitch`](Self::pitch) bytes, freed on drop.
+#[derive(Debug)]
+pub struct PitchedBuffer2D<T: KernelArgValue> {
+    ptr: *mut T,
+    pitch: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<T: KernelArgValue> PitchedBuffer2D<T> {
+    /// Allocate `height` rows of `width` elements on the current device.
+    pub fn new(width: usize, height: usize) -> HipResult<Self> {
+        let width_bytes = byte_size::<T>(width)?;
+        let mut raw: *mut c_void = ptr::null_mut();
+        let mut pitch = width_bytes;
+        if width_bytes > 0 && height > 0 {
+            unsafe { check(hipMallocPitch(&mut raw, &mut pitch, width_bytes, height))? };
+        }
+        Ok(Self {
+            ptr: raw as *mut T,
+            pitch,
+            width,
+            height,
+        })
+    }
+
+    /// Allocate `height` rows of `width` elements with all their bytes set to zero.
+    pub fn zeroed(width: usize, height: usize) -> HipResult<Self> {
+        let mut buffer = Self::new(width, height)?;
+        buffer.memset(0)?;
+        Ok(buffer)
+    }
+
+    /// Set every byte of the rows, without the padding, to `value` with `hipMemset2D`.
+    pub fn memset(&mut self, value: u8) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        unsafe {
+            check(hipMemset2D(
+                self.ptr as *mut c_void,
+                self.pitch,
+                value as i32,
+                self.width_in_bytes(),
+                self.height,
+            ))
+        }
+    }
+
+    /// Copy tightly packed rows from the host, blocking until the copy is complete.
+    pub fn copy_from_host(&mut self, data: &[T]) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        self.copy_from_view(StridedView::new(data, self.width))
+    }
+
+    /// Copy the buffer into tightly packed rows on the host, blocking until the copy is
+    /// complete.
+    pub fn copy_to_host(&self, data: &mut [T]) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        self.copy_to_view(StridedViewMut::new(data, self.width))
+    }
+
+    /// Copy the buffer into a new vector of tightly packed rows on the host.
+    pub fn to_vec(&self) -> HipResult<Vec<T>> {
+        let len = self.width * self.height;
+        let mut data = Vec::with_capacity(len);
+        self.copy_2d(
+            data.as_mut_ptr() as *mut c_void,
+            self.width_in_bytes(),
+            self.ptr as *const c_void,
+            self.pitch,
+            hipMemcpyKind_hipMemcpyDeviceToHost,
+            None,
+        )?;
+        unsafe { data.set_len(len) };
+        Ok(data)
+    }
+
+    /// Copy rows from a strided host view with `hipMemcpy2D`.
+    pub fn copy_from_view(&mut self, view: StridedView<T>) -> HipResult<()> {
+        let layout = check_layout::<T>(view.data.len(), view.row_stride, 0, self.extent())?;
+        self.copy_2d(
+            self.ptr as *mut c_void,
+            self.pitch,
+            view.data.as_ptr() as *const c_void,
+            layout.pitch,
+            hipMemcpyKind_hipMemcpyHostToDevice,
+            None,
+        )
+    }
+
+    /// Copy the buffer into a strided host view with `hipMemcpy2D`.
+    pub fn copy_to_view(&self, view: StridedViewMut<T>) -> HipResult<()> {
+        let layout = check_layout::<T>(view.data.len(), view.row_stride, 0, self.extent())?;
+        self.copy_2d(
+            view.data.as_mut_ptr() as *mut c_void,
+            layout.pitch,
+            self.ptr as *const c_void,
+            self.pitch,
+            hipMemcpyKind_hipMemcpyDeviceToHost,
+            None,
+        )
+    }
+
+    /// Queue a copy of tightly packed rows from the host on `stream` with
+    /// `hipMemcpy2DAsync`.
+    ///
+    /// # Safety
+    ///
+    /// The copy runs after this call returns. `data` must stay alive and unmodified, and the
+    /// buffer must stay alive and not be accessed by the host or another stream, until `stream`
+    /// has completed it.
+    pub unsafe fn copy_from_host_async(&mut self, data: &[T], stream: &Stream) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        self.copy_2d(
+            self.ptr as *mut c_void,
+            self.pitch,
+            data.as_ptr() as *const c_void,
+            self.width_in_bytes(),
+            hipMemcpyKind_hipMemcpyHostToDevice,
+            Some(stream),
+        )
+    }
+
+    /// Queue a copy of the buffer into tightly packed rows on the host on `stream` with
+    /// `hipMemcpy2DAsync`.
+    ///
+    /// # Safety
+    ///
+    /// The copy runs after this call returns. `data` must stay alive and not be accessed, and
+    /// the buffer must stay alive and not be written by the host or another stream, until
+    /// `stream` has completed it.
+    pub unsafe fn copy_to_host_async(&self, data: &mut [T], stream: &Stream) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        self.copy_2d(
+            data.as_mut_ptr() as *mut c_void,
+            self.width_in_bytes(),
+            self.ptr as *const c_void,
+            self.pitch,
+            hipMemcpyKind_hipMemcpyDeviceToHost,
+            Some(stream),
+        )
+    }
+
+    /// Copy `src`, which may have a different pitch, into the buffer with `hipMemcpyParam2D`.
+    pub fn copy_from(&mut self, src: &PitchedBuffer2D<T>) -> HipResult<()> {
+        check_extent(self.extent(), src.extent())?;
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut params: hip_Memcpy2D = unsafe { zeroed_raw() };
+        params.srcMemoryType = hipMemoryType_hipMemoryTypeDevice;
+        params.srcDevice = src.ptr as hipDeviceptr_t;
+        params.srcPitch = src.pitch;
+        params.dstMemoryType = hipMemoryType_hipMemoryTypeDevice;
+        params.dstDevice = self.ptr as hipDeviceptr_t;
+        params.dstPitch = self.pitch;
+        params.WidthInBytes = self.width_in_bytes();
+        params.Height = self.height;
+        unsafe { check(hipMemcpyParam2D(&params)) }
+    }
+
+    fn copy_2d(
+        &self,
+        dst: *mut c_void,
+        dst_pitch: usize,
+        src: *const c_void,
+        src_pitch: usize,
+        kind: hipMemcpyKind,
+        stream: Option<&Stream>,
+    ) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let width = self.width_in_bytes();
+        unsafe {
+            check(match stream {
+                Some(stream) => hipMemcpy2DAsync(
+                    dst,
+                    dst_pitch,
+                    src,
+                    src_pitch,
+                    width,
+                    self.height,
+                    kind,
+                    stream.as_raw(),
+                ),
+                None => hipMemcpy2D(dst, dst_pitch, src, src_pitch, width, self.height, kind),
+            })
+        }
+    }
+
+    fn check_packed_len(&self, len: usize) -> HipResult<()> {
+        if len != self.width * self.height {
+            return Err(HipError::InvalidArgument(format!(
+                "buffer has {}x{} elements, got {len} elements",
+                self.width, self.height
+            )));
+        }
+        Ok(())
+    }
+
+    fn extent(&self) -> [usize; 3] {
+        [self.width, self.height, 1]
+    }
+
+    fn width_in_bytes(&self) -> usize {
+        self.width * mem::size_of::<T>()
+    }
+
+    /// Return the device pointer to the first row.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// Return the distance between rows in bytes.
+    pub fn pitch(&self) -> usize {
+        self.pitch
+    }
+
+    /// Return the number of elements in a row.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Return the number of rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Return true if the buffer has no element.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+impl<T: KernelArgValue> Drop for PitchedBuffer2D<T> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe {
+                hipFree(self.ptr as *mut c_void);
+            }
+        }
+    }
+}
+
+unsafe impl<T: KernelArgValue> KernelArg for PitchedBuffer2D<T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut T as *mut c_void
+    }
+}
+
+/// Typed 3D device allocation made with `hipMalloc3D`, whose rows start every
+/// [`pitch`](Self::pitch) bytes, freed on drop.
+#[derive(Debug)]
+pub struct PitchedBuffer3D<T: KernelArgValue> {
+    raw: hipPitchedPtr,
+    width: usize,
+    height: usize,
+    depth: usize,
+    _element: PhantomData<T>,
+}
+
+impl<T: KernelArgValue> PitchedBuffer3D<T> {
+    /// Allocate `depth` slices of `height` rows of `width` elements on the current device.
+    pub fn new(width: usize, height: usize, depth: usize) -> HipResult<Self> {
+        let width_bytes = byte_size::<T>(width)?;
+        let mut raw = hipPitchedPtr {
+            ptr: ptr::null_mut(),
+            pitch: width_bytes,
+            xsize: width_bytes,
+            ysize: height,
+        };
+        let mut buffer = Self {
+            raw,
+            width,
+            height,
+            depth,
+            _element: PhantomData,
+        };
+        if !buffer.is_empty() {
+            unsafe { check(hipMalloc3D(&mut raw, buffer.extent_in_bytes()))? };
+            buffer.raw = raw;
+        }
+        Ok(buffer)
+    }
+
+    /// Allocate `depth` slices of `height` rows of `width` elements with all their bytes set
+    /// to zero.
+    pub fn zeroed(width: usize, height: usize, depth: usize) -> HipResult<Self> {
+        let mut buffer = Self::new(width, height, depth)?;
+        buffer.memset(0)?;
+        Ok(buffer)
+    }
+
+    /// Set every byte of the rows, without the padding, to `value` with `hipMemset3D`.
+    pub fn memset(&mut self, value: u8) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        unsafe { check(hipMemset3D(self.raw, value as i32, self.extent_in_bytes())) }
+    }
+
+    /// Copy tightly packed slices from the host, blocking until the copy is complete.
+    pub fn copy_from_host(&mut self, data: &[T]) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        self.copy_from_view(StridedView::new(data, self.width))
+    }
+
+    /// Copy the buffer into tightly packed slices on the host, blocking until the copy is
+    /// complete.
+    pub fn copy_to_host(&self, data: &mut [T]) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        self.copy_to_view(StridedViewMut::new(data, self.width))
+    }
+
+    /// Copy the buffer into a new vector of tightly packed slices on the host.
+    pub fn to_vec(&self) -> HipResult<Vec<T>> {
+        let len = self.width * self.height * self.depth;
+        let mut data = Vec::with_capacity(len);
+        let host = hipPitchedPtr {
+            ptr: data.as_mut_ptr() as *mut c_void,
+            pitch: self.width * mem::size_of::<T>(),
+            xsize: self.width * mem::size_of::<T>(),
+            ysize: self.height,
+        };
+        self.copy_3d(self.raw, host, hipMemcpyKind_hipMemcpyDeviceToHost, None)?;
+        unsafe { data.set_len(len) };
+        Ok(data)
+    }
+
+    /// Copy slices from a strided host view with `hipMemcpy3D`.
+    pub fn copy_from_view(&mut self, view: StridedView<T>) -> HipResult<()> {
+        let host = self.host_ptr(
+            view.data.as_ptr() as *mut c_void,
+            view.data.len(),
+            view.row_stride,
+            view.slice_rows,
+        )?;
+        self.copy_3d(host, self.raw, hipMemcpyKind_hipMemcpyHostToDevice, None)
+    }
+
+    /// Copy the buffer into a strided host view with `hipMemcpy3D`.
+    pub fn copy_to_view(&self, view: StridedViewMut<T>) -> HipResult<()> {
+        let host = self.host_ptr(
+            view.data.as_mut_ptr() as *mut c_void,
+            view.data.len(),
+            view.row_stride,
+            view.slice_rows,
+        )?;
+        self.copy_3d(self.raw, host, hipMemcpyKind_hipMemcpyDeviceToHost, None)
+    }
+
+    /// Queue a copy of tightly packed slices from the host on `stream` with
+    /// `hipMemcpy3DAsync`.
+    ///
+    /// # Safety
+    ///
+    /// The copy runs after this call returns. `data` must stay alive and unmodified, and the
+    /// buffer must stay alive and not be accessed by the host or another stream, until `stream`
+    /// has completed it.
+    pub unsafe fn copy_from_host_async(&mut self, data: &[T], stream: &Stream) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        let host = self.host_ptr(data.as_ptr() as *mut c_void, data.len(), self.width, 0)?;
+        self.copy_3d(
+            host,
+            self.raw,
+            hipMemcpyKind_hipMemcpyHostToDevice,
+            Some(stream),
+        )
+    }
+
+    /// Queue a copy of the buffer into tightly packed slices on the host on `stream` with
+    /// `hipMemcpy3DAsync`.
+    ///
+    /// # Safety
+    ///
+    /// The copy runs after this call returns. `data` must stay alive and not be accessed, and
+    /// the buffer must stay alive and not be written by the host or another stream, until
+    /// `stream` has completed it.
+    pub unsafe fn copy_to_host_async(&self, data: &mut [T], stream: &Stream) -> HipResult<()> {
+        self.check_packed_len(data.len())?;
+        let host = self.host_ptr(data.as_mut_ptr() as *mut c_void, data.len(), self.width, 0)?;
+        self.copy_3d(
+            self.raw,
+            host,
+            hipMemcpyKind_hipMemcpyDeviceToHost,
+            Some(stream),
+        )
+    }
+
+    /// Copy `src`, which may have a different pitch, into the buffer with `hipMemcpy3D`.
+    pub fn copy_from(&mut self, src: &PitchedBuffer3D<T>) -> HipResult<()> {
+        check_extent(self.extent(), src.extent())?;
+        self.copy_3d(
+            src.raw,
+            self.raw,
+            hipMemcpyKind_hipMemcpyDeviceToDevice,
+            None,
+        )
+    }
+
+    /// Describe a host slice as a pitched pointer after checking its layout.
+    fn host_ptr(
+        &self,
+        data: *mut c_void,
+        len: usize,
+        row_stride: usize,
+        slice_rows: usize,
+    ) -> HipResult<hipPitchedPtr> {
+        let layout = check_layout::<T>(len, row_stride, slice_rows, self.extent())?;
+        Ok(hipPitchedPtr {
+            ptr: data,
+            pitch: layout.pitch,
+            xsize: self.width * mem::size_of::<T>(),
+            ysize: layout.slice_rows,
+        })
+    }
+
+    fn copy_3d(
+        &self,
+        src: hipPitchedPtr,
+        dst: hipPitchedPtr,
+        kind: hipMemcpyKind,
+        stream: Option<&Stream>,
+    ) -> HipResult<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let params = copy_3d_params(src, dst, self.extent_in_bytes(), kind);
+        unsafe {
+            check(match stream {
+                Some(stream) => hipMemcpy3DAsync(&params, stream.as_raw()),
+                None => hipMemcpy3D(&params),
+            })
+        }
+    }
+
+    fn check_packed_len(&self, len: usize) -> HipResult<()> {
+        if len != self.width * self.height * self.depth {
+            return Err(HipError::InvalidArgument(format!(
+                "buffer has {}x{}x{} elements, got {len} elements",
+                self.width, self.height, self.depth
+            )));
+        }
+        Ok(())
+    }
+
+    fn extent(&self) -> [usize; 3] {
+        [self.width, self.height, self.depth]
+    }
+
+    fn extent_in_bytes(&self) -> hipExtent {
+        hipExtent {
+            width: self.width * mem::size_of::<T>(),
+            height: self.height,
+            depth: self.depth,
+        }
+    }
+
+    /// Return the device pointer to the first row.
+    pub fn as_ptr(&self) -> *mut T {
+        self.raw.ptr as *mut T
+    }
+
+    /// Return the underlying `hipPitchedPtr`.
+    pub fn as_pitched_ptr(&self) -> hipPitchedPtr {
+        self.raw
+    }
+
+    /// Return the distance between rows in bytes.
+    pub fn pitch(&self) -> usize {
+        self.raw.pitch
+    }
+
+    /// Return the number of elements in a row.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Return the number of rows in a slice.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Return the number of slices.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Return true if the buffer has no element.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0 || self.depth == 0
+    }
+}
+
+impl<T: KernelArgValue> Drop for PitchedBuffer3D<T> {
+    fn drop(&mut self) {
+        if !self.raw.ptr.is_null() {
+            unsafe {
+                hipFree(self.raw.ptr);
+            }
+        }
+    }
+}
+
+unsafe impl<T: KernelArgValue> KernelArg for PitchedBuffer3D<T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.raw.ptr as *const *mut c_void as *mut c_void
+    }
+}
+
+pub(crate) fn copy_3d_params(
+    src: hipPitchedPtr,
+    dst: hipPitchedPtr,
+    extent: hipExtent,
+    kind: hipMemcpyKind,
+) -> hipMemcpy3DParms {
+    // Null arrays and zero positions select the pitched pointers.
+    let mut params: hipMemcpy3DParms = unsafe { zeroed_raw() };
+    params.srcPtr = src;
+    params.dstPtr = dst;
+    params.extent = extent;
+    params.kind = kind;
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::packed(12, 4, 0, [4, 3, 1], true)]
+    #[case::padded_rows(10, 4, 0, [2, 3, 1], true)]
+    #[case::stride_smaller_than_width(12, 2, 0, [4, 3, 1], false)]
+    #[case::too_short(11, 4, 0, [4, 3, 1], false)]
+    #[case::padded_slices(2 * 4 * 5 - 4 * 2, 4, 5, [4, 3, 2], true)]
+    #[case::slices_smaller_than_height(24, 4, 2, [4, 3, 2], false)]
+    #[case::empty(0, 4, 0, [4, 0, 1], true)]
+    fn test_check_layout(
+        #[case] len: usize,
+        #[case] row_stride: usize,
+        #[case] slice_rows: usize,
+        #[case] extent: [usize; 3],
+        #[case] valid: bool,
+    ) {
+        assert_eq!(
+            check_layout::<f32>(len, row_stride, slice_rows, extent).is_ok(),
+            valid
+        );
+    }
+
+    #[test]
+    fn test_layout_in_bytes_and_default_slice_rows() {
+        let layout = check_layout::<f32>(64, 8, 0, [5, 4, 2]).unwrap();
+        assert_eq!(
+            layout,
+            HostLayout {
+                pitch: 32,
+                slice_rows: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_copy_3d_params() {
+        let src = hipPitchedPtr {
+            ptr: 0x1000 as *mut c_void,
+            pitch: 64,
+            xsize: 48,
+            ysize: 4,
+        };
+        let dst = hipPitchedPtr {
+            ptr: 0x2000 as *mut c_void,
+            pitch: 256,
+            xsize: 48,
+            ysize: 4,
+        };
+        let extent = hipExtent {
+            width: 48,
+            height: 4,
+            depth: 2,
+        };
+        let params = copy_3d_params(src, dst, extent, hipMemcpyKind_hipMemcpyDeviceToDevice);
+        assert!(params.srcArray.is_null() && params.dstArray.is_null());
+        assert_eq!(params.srcPtr.pitch, 64);
+        assert_eq!(params.dstPtr.ptr, 0x2000 as *mut c_void);
+        assert_eq!(params.extent.depth, 2);
+        assert_eq!(params.kind, hipMemcpyKind_hipMemcpyDeviceToDevice);
+    }
+
+    #[test]
+    fn test_empty_buffers_need_no_runtime() {
+        let mut buffer = PitchedBuffer2D::<f32>::zeroed(0, 4).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.to_vec().unwrap(), Vec::<f32>::new());
+        assert!(buffer.copy_from_host(&[1.0]).is_err());
+
+        let buffer = PitchedBuffer3D::<u16>::zeroed(8, 4, 0).unwrap();
+        assert_eq!(buffer.pitch(), 16);
+        assert!(buffer.as_ptr().is_null());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pitched_2d_roundtrip_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        // Rows of 3 elements are padded to the pitch the runtime picks.
+        let mut buffer = PitchedBuffer2D::<u32>::zeroed(3, 4).expect("Should allocate");
+        assert!(buffer.pitch() >= 3 * mem::size_of::<u32>());
+        let strided: Vec<u32> = (0..20).collect();
+        buffer
+            .copy_from_view(StridedView::new(&strided, 5))
+            .expect("Should copy from the strided view");
+        let packed = buffer.to_vec().expect("Should read the buffer back");
+        assert_eq!(packed, vec![0, 1, 2, 5, 6, 7, 10, 11, 12, 15, 16, 17]);
+
+        let mut copy = PitchedBuffer2D::<u32>::zeroed(3, 4).expect("Should allocate");
+        copy.copy_from(&buffer)
+            .expect("Should copy between buffers");
+        let mut out = vec![u32::MAX; 20];
+        copy.copy_to_view(StridedViewMut::new(&mut out, 5))
+            .expect("Should copy into the strided view");
+        for (i, value) in out.iter().enumerate() {
+            let expected = if i % 5 < 3 { i as u32 } else { u32::MAX };
+            assert_eq!(*value, expected, "element {i}");
+        }
+
+        let stream = Stream::new().expect("Should create a stream");
+        let doubled: Vec<u32> = packed.iter().map(|value| value * 2).collect();
+        let mut read_back = vec![0; 12];
+        unsafe {
+            buffer.copy_from_host_async(&doubled, &stream).unwrap();
+            buffer.copy_to_host_async(&mut read_back, &stream).unwrap();
+        }
+        stream.synchronize().unwrap();
+        assert_eq!(read_back, doubled);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pitched_3d_roundtrip_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let mut buffer = PitchedBuffer3D::<u16>::zeroed(3, 2, 2).expect("Should allocate");
+        // Rows every 4 elements and slices every 3 rows, with a padding row between slices.
+        let strided: Vec<u16> = (0..24).collect();
+        buffer
+            .copy_from_view(StridedView::new(&strided, 4).with_slice_rows(3))
+            .expect("Should copy from the strided view");
+        let packed = buffer.to_vec().expect("Should read the buffer back");
+        assert_eq!(packed, vec![0, 1, 2, 4, 5, 6, 12, 13, 14, 16, 17, 18]);
+
+        let mut copy = PitchedBuffer3D::<u16>::zeroed(3, 2, 2).expect("Should allocate");
+        copy.copy_from(&buffer)
+            .expect("Should copy between buffers");
+        let mut out = vec![u16::MAX; 24];
+        copy.copy_to_view(StridedViewMut::new(&mut out, 4).with_slice_rows(3))
+            .expect("Should copy into the strided view");
+        for (i, value) in out.iter().enumerate() {
+            let in_extent = i % 4 < 3 && (i / 4) % 3 < 2;
+            let expected = if in_extent { i as u16 } else { u16::MAX };
+            assert_eq!(*value, expected, "element {i}");
+        }
+
+        let stream = Stream::new().expect("Should create a stream");
+        let reversed: Vec<u16> = packed.iter().rev().copied().collect();
+        let mut read_back = vec![0; 12];
+        unsafe {
+            buffer.copy_from_host_async(&reversed, &stream).unwrap();
+            buffer.copy_to_host_async(&mut read_back, &stream).unwrap();
+        }
+        stream.synchronize().unwrap();
+        assert_eq!(read_back, reversed);
+    }
+}
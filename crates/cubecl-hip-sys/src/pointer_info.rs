@@ -0,0 +1,243 @@
+use std::{ffi::c_void, ptr};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+
+/// Kind of memory an address belongs to, see `hipMemoryType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryType {
+    /// Host memory unknown to HIP.
+    Unregistered,
+    /// Pinned or registered host memory.
+    Host,
+    /// Device memory, for instance from `hipMalloc`.
+    Device,
+    /// Managed memory from `hipMallocManaged`, migrated between the host and the devices.
+    Managed,
+    /// Memory of a HIP array, for instance behind a texture.
+    Array,
+    /// Memory addressed the same way by the host and every device.
+    Unified,
+    /// Memory type unknown to this crate.
+    Other(hipMemoryType),
+}
+
+impl From<hipMemoryType> for MemoryType {
+    fn from(memory_type: hipMemoryType) -> Self {
+        match memory_type {
+            hipMemoryType_hipMemoryTypeUnregistered => MemoryType::Unregistered,
+            hipMemoryType_hipMemoryTypeHost => MemoryType::Host,
+            hipMemoryType_hipMemoryTypeDevice => MemoryType::Device,
+            hipMemoryType_hipMemoryTypeManaged => MemoryType::Managed,
+            hipMemoryType_hipMemoryTypeArray => MemoryType::Array,
+            hipMemoryType_hipMemoryTypeUnified => MemoryType::Unified,
+            other => MemoryType::Other(other),
+        }
+    }
+}
+
+/// Allocation containing an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressRange {
+    /// Start address of the allocation.
+    pub base: *mut c_void,
+    /// Size of the allocation in bytes.
+    pub size: usize,
+}
+
+impl AddressRange {
+    /// Return true if `ptr` is inside the range.
+    pub fn contains<T>(&self, ptr: *const T) -> bool {
+        let base = self.base as usize;
+        (base..base.saturating_add(self.size)).contains(&(ptr as usize))
+    }
+}
+
+/// Description of the memory behind an address, from `hipPointerGetAttributes` and
+/// `hipDrvPointerGetAttributes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerInfo {
+    /// Kind of memory of the allocation.
+    pub memory_type: MemoryType,
+    /// Device owning the allocation, `None` for unregistered memory.
+    pub device: Option<i32>,
+    /// Address of the memory for device code, null if devices cannot access it.
+    pub device_pointer: *mut c_void,
+    /// Address of the memory for host code, null if the host cannot access it.
+    pub host_pointer: *mut c_void,
+    /// True if the allocation is managed memory from `hipMallocManaged`.
+    pub is_managed: bool,
+    /// Flags the allocation was made with, for instance `hipHostMallocMapped`.
+    pub allocation_flags: u32,
+    /// Allocation containing the address, `None` for unregistered memory.
+    pub range: Option<AddressRange>,
+    /// Identifier unique to the allocation, `None` for unregistered memory.
+    pub buffer_id: Option<u64>,
+}
+
+impl PointerInfo {
+    /// Describe the memory behind `ptr`, which does not have to point to memory allocated by
+    /// HIP. The pointer is never dereferenced.
+    pub fn of<T>(ptr: *const T) -> HipResult<Self> {
+        let address = check_not_null(ptr)?;
+        let mut raw: hipPointerAttribute_t = unsafe { zeroed_raw() };
+        match unsafe { hipPointerGetAttributes(&mut raw, address) } {
+            // Older runtimes reject addresses they do not know instead of reporting them as
+            // unregistered.
+            hipError_t_hipErrorInvalidValue => return Ok(Self::unregistered(ptr)),
+            status => check(status)?,
+        }
+        let memory_type = MemoryType::from(raw.type_);
+        if memory_type == MemoryType::Unregistered {
+            return Ok(Self::unregistered(ptr));
+        }
+
+        let mut base: hipDeviceptr_t = ptr::null_mut();
+        let mut size = 0usize;
+        let mut buffer_id = 0u64;
+        let mut attributes = [
+            hipPointer_attribute_HIP_POINTER_ATTRIBUTE_RANGE_START_ADDR,
+            hipPointer_attribute_HIP_POINTER_ATTRIBUTE_RANGE_SIZE,
+            hipPointer_attribute_HIP_POINTER_ATTRIBUTE_BUFFER_ID,
+        ];
+        let mut data = [
+            &mut base as *mut hipDeviceptr_t as *mut c_void,
+            &mut size as *mut usize as *mut c_void,
+            &mut buffer_id as *mut u64 as *mut c_void,
+        ];
+        unsafe {
+            check(hipDrvPointerGetAttributes(
+                attributes.len() as u32,
+                attributes.as_mut_ptr(),
+                data.as_mut_ptr(),
+                address as hipDeviceptr_t,
+            ))?
+        };
+        Ok(Self {
+            memory_type,
+            device: Some(raw.device),
+            device_pointer: raw.devicePointer,
+            host_pointer: raw.hostPointer,
+            is_managed: raw.isManaged != 0,
+            allocation_flags: raw.allocationFlags,
+            range: Some(AddressRange { base, size }),
+            buffer_id: Some(buffer_id),
+        })
+    }
+
+    /// Return only the memory type of `ptr` with `hipPointerGetAttribute`.
+    pub fn memory_type<T>(ptr: *const T) -> HipResult<MemoryType> {
+        let address = check_not_null(ptr)?;
+        let mut memory_type: hipMemoryType = hipMemoryType_hipMemoryTypeUnregistered;
+        match unsafe {
+            hipPointerGetAttribute(
+                &mut memory_type as *mut hipMemoryType as *mut c_void,
+                hipPointer_attribute_HIP_POINTER_ATTRIBUTE_MEMORY_TYPE,
+                address as hipDeviceptr_t,
+            )
+        } {
+            hipError_t_hipErrorInvalidValue => Ok(MemoryType::Unregistered),
+            status => check(status).map(|_| memory_type.into()),
+        }
+    }
+
+    /// Return the device allocation containing `ptr` with `hipMemGetAddressRange`.
+    ///
+    /// Fails for addresses that are not in device memory.
+    pub fn address_range<T>(ptr: *const T) -> HipResult<AddressRange> {
+        let address = check_not_null(ptr)?;
+        let mut base: hipDeviceptr_t = ptr::null_mut();
+        let mut size = 0;
+        unsafe {
+            check(hipMemGetAddressRange(
+                &mut base,
+                &mut size,
+                address as hipDeviceptr_t,
+            ))?
+        };
+        Ok(AddressRange { base, size })
+    }
+
+    fn unregistered<T>(ptr: *const T) -> Self {
+        Self {
+            memory_type: MemoryType::Unregistered,
+            device: None,
+            device_pointer: ptr::null_mut(),
+            host_pointer: ptr as *mut c_void,
+            is_managed: false,
+            allocation_flags: 0,
+            range: None,
+            buffer_id: None,
+        }
+    }
+
+    /// Return true if device code can access the memory.
+    pub fn is_device_accessible(&self) -> bool {
+        !self.device_pointer.is_null()
+    }
+
+    /// Return true if host code can access the memory.
+    pub fn is_host_accessible(&self) -> bool {
+        !self.host_pointer.is_null()
+    }
+}
+
+fn check_not_null<T>(ptr: *const T) -> HipResult<*const c_void> {
+    if ptr.is_null() {
+        return Err(HipError::InvalidArgument(
+            "cannot describe a null pointer".to_string(),
+        ));
+    }
+    Ok(ptr as *const c_void)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(hipMemoryType_hipMemoryTypeUnregistered, MemoryType::Unregistered)]
+    #[case(hipMemoryType_hipMemoryTypeHost, MemoryType::Host)]
+    #[case(hipMemoryType_hipMemoryTypeDevice, MemoryType::Device)]
+    #[case(hipMemoryType_hipMemoryTypeManaged, MemoryType::Managed)]
+    #[case(hipMemoryType_hipMemoryTypeUnified, MemoryType::Unified)]
+    #[case(42, MemoryType::Other(42))]
+    fn test_memory_type_from_raw(#[case] raw: hipMemoryType, #[case] expected: MemoryType) {
+        assert_eq!(MemoryType::from(raw), expected);
+    }
+
+    #[rstest]
+    #[case::base(0x1000, true)]
+    #[case::last_byte(0x10ff, true)]
+    #[case::end(0x1100, false)]
+    #[case::before(0xfff, false)]
+    fn test_address_range_contains(#[case] address: usize, #[case] expected: bool) {
+        let range = AddressRange {
+            base: 0x1000 as *mut c_void,
+            size: 0x100,
+        };
+        assert_eq!(range.contains(address as *const u8), expected);
+    }
+
+    #[test]
+    fn test_null_pointer_is_an_error() {
+        let null = ptr::null::<f32>();
+        assert!(matches!(
+            PointerInfo::of(null),
+            Err(HipError::InvalidArgument(_))
+        ));
+        assert!(PointerInfo::memory_type(null).is_err());
+        assert!(PointerInfo::address_range(null).is_err());
+    }
+
+    #[test]
+    fn test_unregistered_is_host_accessible_only() {
+        let value = 1.0f32;
+        let info = PointerInfo::unregistered(&value as *const f32);
+        assert!(info.is_host_accessible());
+        assert!(!info.is_device_accessible());
+        assert_eq!(info.device, None);
+        assert_eq!(info.range, None);
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use crate::bindings::*;
+use crate::error::{check_rtc, HipError, HipResult};
+
+/// HIP C++ program compiled at runtime with hiprtc, destroyed on drop.
+#[derive(Debug)]
+pub struct Program {
+    raw: hiprtcProgram,
+    name_expressions: Vec<String>,
+}
+
+impl Program {
+    /// Create a program from `source` with `hiprtcCreateProgram`, `name` being used in the
+    /// compiler messages.
+    pub fn new(source: &str, name: &str) -> HipResult<Self> {
+        Self::with_headers(source, name, &[])
+    }
+
+    /// Create a program from `source` that can include the `(name, source)` headers.
+    pub fn with_headers(source: &str, name: &str, headers: &[(&str, &str)]) -> HipResult<Self> {
+        let source = c_string(source, "program source")?;
+        let name = c_string(name, "program name")?;
+        let include_names = headers
+            .iter()
+            .map(|(name, _)| c_string(name, "header name"))
+            .collect::<HipResult<Vec<_>>>()?;
+        let header_sources = headers
+            .iter()
+            .map(|(_, source)| c_string(source, "header source"))
+            .collect::<HipResult<Vec<_>>>()?;
+        let mut include_names: Vec<_> = include_names.iter().map(|name| name.as_ptr()).collect();
+        let mut header_sources: Vec<_> = header_sources
+            .iter()
+            .map(|source| source.as_ptr())
+            .collect();
+        let mut raw: hiprtcProgram = ptr::null_mut();
+        unsafe {
+            check_rtc(hiprtcCreateProgram(
+                &mut raw,
+                source.as_ptr(),
+                name.as_ptr(),
+                headers.len() as i32,
+                // Older bindings take mutable arrays, which newer ones accept as well.
+                header_sources.as_mut_ptr(),
+                include_names.as_mut_ptr(),
+            ))?
+        };
+        Ok(Self {
+            raw,
+            name_expressions: Vec::new(),
+        })
+    }
+
+    /// Register a C++ name expression, such as `&kernel<float, 4>` or `table<int>`, with
+    /// `hiprtcAddNameExpression` before compiling the program.
+    ///
+    /// Registered expressions are instantiated by the compilation, their mangled names are
+    /// returned by [`Program::lowered_name`] and resolved by the module loaded with
+    /// [`crate::Module::from_program`].
+    pub fn add_name_expression(&mut self, expression: &str) -> HipResult<()> {
+        let c_expression = c_string(expression, "name expression")?;
+        unsafe { check_rtc(hiprtcAddNameExpression(self.raw, c_expression.as_ptr()))? };
+        self.name_expressions.push(expression.to_string());
+        Ok(())
+    }
+
+    /// Return the mangled name of a registered name expression once the program is
+    /// compiled, with `hiprtcGetLoweredName`.
+    pub fn lowered_name(&self, expression: &str) -> HipResult<String> {
+        let c_expression = c_string(expression, "name expression")?;
+        let mut lowered: *const c_char = ptr::null();
+        unsafe {
+            check_rtc(hiprtcGetLoweredName(
+                self.raw,
+                c_expression.as_ptr(),
+                &mut lowered,
+            ))?;
+            // The name is owned by the program, copy it before it is destroyed.
+            Ok(CStr::from_ptr(lowered).to_string_lossy().into_owned())
+        }
+    }
+
+    /// Return the mangled name of every registered name expression, by expression.
+    pub fn lowered_names(&self) -> HipResult<HashMap<String, String>> {
+        self.name_expressions
+            .iter()
+            .map(|expression| Ok((expression.clone(), self.lowered_name(expression)?)))
+            .collect()
+    }
+
+    /// Return the registered name expressions, in registration order.
+    pub fn name_expressions(&self) -> &[String] {
+        &self.name_expressions
+    }
+
+    /// Compile the program with `hiprtcCompileProgram`, for the current device unless an
+    /// `--offload-arch` option is given.
+    ///
+    /// On failure the compiler messages are available from [`Program::log`].
+    pub fn compile<S: AsRef<str>>(&mut self, options: &[S]) -> HipResult<()> {
+        let options = options
+            .iter()
+            .map(|option| c_string(option.as_ref(), "compile option"))
+            .collect::<HipResult<Vec<_>>>()?;
+        let mut options: Vec<*const c_char> =
+            options.iter().map(|option| option.as_ptr()).collect();
+        unsafe {
+            check_rtc(hiprtcCompileProgram(
+                self.raw,
+                options.len() as i32,
+                options.as_mut_ptr(),
+            ))
+        }
+    }
+
+    /// Return the compiler messages of the last compilation.
+    pub fn log(&self) -> HipResult<String> {
+        let mut size = 0;
+        unsafe { check_rtc(hiprtcGetProgramLogSize(self.raw, &mut size))? };
+        let mut log = vec![0u8; size];
+        if size > 0 {
+            unsafe {
+                check_rtc(hiprtcGetProgramLog(
+                    self.raw,
+                    log.as_mut_ptr() as *mut c_char,
+                ))?
+            };
+        }
+        Ok(nul_terminated(log))
+    }
+
+    /// Return the code object of the compiled program, which [`crate::Module`] can load.
+    pub fn code(&self) -> HipResult<Vec<u8>> {
+        let mut size = 0;
+        unsafe { check_rtc(hiprtcGetCodeSize(self.raw, &mut size))? };
+        let mut code = vec![0u8; size];
+        unsafe { check_rtc(hiprtcGetCode(self.raw, code.as_mut_ptr() as *mut c_char))? };
+        Ok(code)
+    }
+
+    /// Return the underlying `hiprtcProgram`.
+    pub fn as_raw(&self) -> hiprtcProgram {
+        self.raw
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        unsafe {
+            hiprtcDestroyProgram(&mut self.raw);
+        }
+    }
+}
+
+fn c_string(value: &str, what: &str) -> HipResult<CString> {
+    CString::new(value)
+        .map_err(|_| HipError::InvalidArgument(format!("{what} '{value}' contains a nul byte")))
+}
+
+/// Convert a buffer filled by hiprtc to a string, dropping the nul terminator and after.
+fn nul_terminated(mut bytes: Vec<u8>) -> String {
+    if let Some(end) = bytes.iter().position(|&byte| byte == 0) {
+        bytes.truncate(end);
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::terminated(b"error: oops\n\0".to_vec(), "error: oops\n")]
+    #[case::unterminated(b"warning".to_vec(), "warning")]
+    #[case::empty(Vec::new(), "")]
+    fn test_nul_terminated(#[case] bytes: Vec<u8>, #[case] expected: &str) {
+        assert_eq!(nul_terminated(bytes), expected);
+    }
+
+    #[test]
+    fn test_nul_bytes_are_rejected() {
+        assert!(matches!(
+            Program::new("kernel\0", "fill.hip"),
+            Err(HipError::InvalidArgument(_))
+        ));
+    }
+}
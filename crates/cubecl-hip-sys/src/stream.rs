@@ -0,0 +1,44 @@
+use std::ptr;
+
+use crate::bindings::*;
+use crate::error::{check, HipResult};
+
+/// Owned HIP stream, destroyed on drop.
+#[derive(Debug)]
+pub struct Stream {
+    raw: hipStream_t,
+}
+
+impl Stream {
+    /// Create a new stream on the current device with `hipStreamCreate`.
+    pub fn new() -> HipResult<Self> {
+        let mut raw: hipStream_t = ptr::null_mut();
+        unsafe { check(hipStreamCreate(&mut raw))? };
+        Ok(Self { raw })
+    }
+
+    /// Return the underlying `hipStream_t`.
+    pub fn as_raw(&self) -> hipStream_t {
+        self.raw
+    }
+
+    /// Return the device the stream belongs to with `hipStreamGetDevice`.
+    pub fn device(&self) -> HipResult<i32> {
+        let mut device = 0;
+        unsafe { check(hipStreamGetDevice(self.raw, &mut device))? };
+        Ok(device)
+    }
+
+    /// Block the host until all the work queued on the stream is complete.
+    pub fn synchronize(&self) -> HipResult<()> {
+        unsafe { check(hipStreamSynchronize(self.raw)) }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        unsafe {
+            hipStreamDestroy(self.raw);
+        }
+    }
+}
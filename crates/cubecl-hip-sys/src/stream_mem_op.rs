@@ -0,0 +1,435 @@
+use std::ffi::c_void;
+
+use crate::bindings::*;
+use crate::device_buffer::DeviceBuffer;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::KernelArgValue;
+use crate::stream::Stream;
+
+/// Condition a stream waits for on a memory word, from the `hipStreamWaitValue*` flags.
+///
+/// The word is masked before it is compared, with every bit set unless a mask is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitCondition {
+    /// Wait until `word & mask >= value`.
+    Gte,
+    /// Wait until `word & mask == value`.
+    Eq,
+    /// Wait until `word & mask & value != 0`.
+    And,
+    /// Wait until `!((word & mask) | (value & mask)) != 0`.
+    Nor,
+}
+
+impl WaitCondition {
+    /// Return the `hipStreamWaitValue*` flag of the condition.
+    pub fn to_flags(self) -> u32 {
+        match self {
+            WaitCondition::Gte => hipStreamWaitValueGte,
+            WaitCondition::Eq => hipStreamWaitValueEq,
+            WaitCondition::And => hipStreamWaitValueAnd,
+            WaitCondition::Nor => hipStreamWaitValueNor,
+        }
+    }
+}
+
+/// Operand of a stream memory operation, which selects the 32 or 64 bits variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemOpValue {
+    U32(u32),
+    U64(u64),
+}
+
+/// Memory word streams can wait on and write, implemented for `u32` and `u64`.
+///
+/// # Safety
+///
+/// [`StreamValue::to_operand`] must return an operand of the size of the type.
+pub unsafe trait StreamValue: KernelArgValue {
+    /// Mask with every bit set, comparing the whole word.
+    const ALL_BITS: Self;
+
+    /// Return the value as the operand of a memory operation.
+    fn to_operand(self) -> MemOpValue;
+}
+
+unsafe impl StreamValue for u32 {
+    const ALL_BITS: Self = u32::MAX;
+
+    fn to_operand(self) -> MemOpValue {
+        MemOpValue::U32(self)
+    }
+}
+
+unsafe impl StreamValue for u64 {
+    const ALL_BITS: Self = u64::MAX;
+
+    fn to_operand(self) -> MemOpValue {
+        MemOpValue::U64(self)
+    }
+}
+
+impl Stream {
+    /// Queue a wait on the stream until element `index` of `buffer` meets `condition` with
+    /// `value`, with `hipStreamWaitValue32` or `hipStreamWaitValue64`.
+    ///
+    /// The work queued after the wait does not start before another stream or the host
+    /// writes a matching value. The buffer should not be dropped before the wait completes.
+    pub fn wait_value<T: StreamValue>(
+        &self,
+        buffer: &DeviceBuffer<T>,
+        index: usize,
+        condition: WaitCondition,
+        value: T,
+    ) -> HipResult<()> {
+        self.wait_value_masked(buffer, index, condition, value, T::ALL_BITS)
+    }
+
+    /// Queue a wait like [`Stream::wait_value`], comparing only the bits of the element set
+    /// in `mask`.
+    pub fn wait_value_masked<T: StreamValue>(
+        &self,
+        buffer: &DeviceBuffer<T>,
+        index: usize,
+        condition: WaitCondition,
+        value: T,
+        mask: T,
+    ) -> HipResult<()> {
+        let ptr = element_ptr(buffer, index)?;
+        let flags = condition.to_flags();
+        unsafe {
+            match (value.to_operand(), mask.to_operand()) {
+                (MemOpValue::U32(value), MemOpValue::U32(mask)) => {
+                    check(hipStreamWaitValue32(self.as_raw(), ptr, value, flags, mask))
+                }
+                (MemOpValue::U64(value), MemOpValue::U64(mask)) => {
+                    check(hipStreamWaitValue64(self.as_raw(), ptr, value, flags, mask))
+                }
+                _ => unreachable!("a value and its mask have the same type"),
+            }
+        }
+    }
+
+    /// Queue a write of `value` to element `index` of `buffer` on the stream, with
+    /// `hipStreamWriteValue32` or `hipStreamWriteValue64`.
+    ///
+    /// The write happens once the work queued before it is complete, which lets streams
+    /// waiting on the element resume. The buffer should not be dropped before the write
+    /// completes.
+    pub fn write_value<T: StreamValue>(
+        &self,
+        buffer: &DeviceBuffer<T>,
+        index: usize,
+        value: T,
+    ) -> HipResult<()> {
+        let ptr = element_ptr(buffer, index)?;
+        unsafe {
+            match value.to_operand() {
+                MemOpValue::U32(value) => {
+                    check(hipStreamWriteValue32(self.as_raw(), ptr, value, 0))
+                }
+                MemOpValue::U64(value) => {
+                    check(hipStreamWriteValue64(self.as_raw(), ptr, value, 0))
+                }
+            }
+        }
+    }
+}
+
+/// Return the device address of element `index` of `buffer`.
+fn element_ptr<T: KernelArgValue>(
+    buffer: &DeviceBuffer<T>,
+    index: usize,
+) -> HipResult<*mut c_void> {
+    check_index(buffer.len(), index)?;
+    Ok(buffer.as_ptr().wrapping_add(index) as *mut c_void)
+}
+
+fn check_index(len: usize, index: usize) -> HipResult<()> {
+    if index >= len {
+        return Err(HipError::InvalidArgument(format!(
+            "index {index} is out of bounds of a buffer of {len} elements"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(has_batch_mem_op)]
+mod batch {
+    use std::marker::PhantomData;
+
+    use crate::bindings::*;
+    use crate::device_buffer::DeviceBuffer;
+    use crate::error::{check, HipError, HipResult};
+    use crate::stream::Stream;
+
+    use super::{element_ptr, MemOpValue, StreamValue, WaitCondition};
+
+    /// Most operations `hipStreamBatchMemOp` accepts at once.
+    const MAX_BATCH_MEM_OPS: usize = 255;
+
+    /// Waits and writes queued together with `hipStreamBatchMemOp`, or added to a graph with
+    /// [`crate::GraphBuilder::add_batch_mem_ops`].
+    ///
+    /// The lifetime `'a` covers the buffers the operations reference.
+    #[derive(Clone, Default)]
+    pub struct BatchMemOps<'a> {
+        ops: Vec<hipStreamBatchMemOpParams>,
+        _buffers: PhantomData<&'a ()>,
+    }
+
+    impl<'a> BatchMemOps<'a> {
+        /// Create an empty batch.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Append a wait until element `index` of `buffer` meets `condition` with `value`.
+        ///
+        /// Batched waits compare the whole element, there is no mask.
+        pub fn wait_value<T: StreamValue>(
+            &mut self,
+            buffer: &'a DeviceBuffer<T>,
+            index: usize,
+            condition: WaitCondition,
+            value: T,
+        ) -> HipResult<&mut Self> {
+            let address = element_ptr(buffer, index)?;
+            self.ops
+                .push(wait_op(address, condition, value.to_operand()));
+            Ok(self)
+        }
+
+        /// Append a write of `value` to element `index` of `buffer`.
+        pub fn write_value<T: StreamValue>(
+            &mut self,
+            buffer: &'a DeviceBuffer<T>,
+            index: usize,
+            value: T,
+        ) -> HipResult<&mut Self> {
+            let address = element_ptr(buffer, index)?;
+            self.ops.push(write_op(address, value.to_operand()));
+            Ok(self)
+        }
+
+        /// Queue the operations in order on `stream` with `hipStreamBatchMemOp`.
+        pub fn launch(&self, stream: &Stream) -> HipResult<()> {
+            if self.ops.is_empty() {
+                return Ok(());
+            }
+            self.check_len()?;
+            unsafe {
+                check(hipStreamBatchMemOp(
+                    stream.as_raw(),
+                    self.ops.len() as u32,
+                    // The runtime only reads the operations.
+                    self.ops.as_ptr() as *mut hipStreamBatchMemOpParams,
+                    0,
+                ))
+            }
+        }
+
+        /// Return the number of operations.
+        pub fn len(&self) -> usize {
+            self.ops.len()
+        }
+
+        /// Return true if the batch has no operation.
+        pub fn is_empty(&self) -> bool {
+            self.ops.is_empty()
+        }
+
+        pub(crate) fn check_len(&self) -> HipResult<()> {
+            if self.ops.len() > MAX_BATCH_MEM_OPS {
+                return Err(HipError::InvalidArgument(format!(
+                    "a batch has at most {MAX_BATCH_MEM_OPS} memory operations, got {}",
+                    self.ops.len()
+                )));
+            }
+            Ok(())
+        }
+
+        pub(crate) fn as_raw(&self) -> &[hipStreamBatchMemOpParams] {
+            &self.ops
+        }
+    }
+
+    impl std::fmt::Debug for BatchMemOps<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("BatchMemOps")
+                .field("ops", &self.ops.len())
+                .finish()
+        }
+    }
+
+    fn wait_op(
+        address: *mut std::ffi::c_void,
+        condition: WaitCondition,
+        value: MemOpValue,
+    ) -> hipStreamBatchMemOpParams {
+        let mut op: hipStreamBatchMemOpParams = unsafe { zeroed_raw() };
+        unsafe {
+            let wait = &mut op.waitValue;
+            wait.address = address;
+            wait.flags = condition.to_flags();
+            match value {
+                MemOpValue::U32(value) => {
+                    wait.operation = hipStreamBatchMemOpType_hipStreamMemOpWaitValue32;
+                    wait.__bindgen_anon_1.value = value;
+                }
+                MemOpValue::U64(value) => {
+                    wait.operation = hipStreamBatchMemOpType_hipStreamMemOpWaitValue64;
+                    wait.__bindgen_anon_1.value64 = value;
+                }
+            }
+        }
+        op
+    }
+
+    fn write_op(address: *mut std::ffi::c_void, value: MemOpValue) -> hipStreamBatchMemOpParams {
+        let mut op: hipStreamBatchMemOpParams = unsafe { zeroed_raw() };
+        unsafe {
+            let write = &mut op.writeValue;
+            write.address = address;
+            match value {
+                MemOpValue::U32(value) => {
+                    write.operation = hipStreamBatchMemOpType_hipStreamMemOpWriteValue32;
+                    write.__bindgen_anon_1.value = value;
+                }
+                MemOpValue::U64(value) => {
+                    write.operation = hipStreamBatchMemOpType_hipStreamMemOpWriteValue64;
+                    write.__bindgen_anon_1.value64 = value;
+                }
+            }
+        }
+        op
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use rstest::*;
+
+        #[test]
+        fn test_wait_op_fields() {
+            let address = 0x1000 as *mut std::ffi::c_void;
+            let op = wait_op(address, WaitCondition::Eq, MemOpValue::U64(u64::MAX));
+            unsafe {
+                assert_eq!(
+                    op.operation,
+                    hipStreamBatchMemOpType_hipStreamMemOpWaitValue64
+                );
+                assert_eq!(op.waitValue.address, address);
+                assert_eq!(op.waitValue.flags, hipStreamWaitValueEq);
+                assert_eq!(op.waitValue.__bindgen_anon_1.value64, u64::MAX);
+            }
+        }
+
+        #[test]
+        fn test_write_op_fields() {
+            let address = 0x2000 as *mut std::ffi::c_void;
+            let op = write_op(address, MemOpValue::U32(7));
+            unsafe {
+                assert_eq!(
+                    op.operation,
+                    hipStreamBatchMemOpType_hipStreamMemOpWriteValue32
+                );
+                assert_eq!(op.writeValue.address, address);
+                assert_eq!(op.writeValue.flags, 0);
+                assert_eq!(op.writeValue.__bindgen_anon_1.value, 7);
+            }
+        }
+
+        #[rstest]
+        #[case(0, true)]
+        #[case(MAX_BATCH_MEM_OPS, true)]
+        #[case(MAX_BATCH_MEM_OPS + 1, false)]
+        fn test_check_len(#[case] len: usize, #[case] valid: bool) {
+            let op = write_op(std::ptr::null_mut(), MemOpValue::U32(0));
+            let ops = BatchMemOps {
+                ops: vec![op; len],
+                _buffers: PhantomData,
+            };
+            assert_eq!(ops.check_len().is_ok(), valid);
+        }
+    }
+}
+
+#[cfg(has_batch_mem_op)]
+pub use batch::BatchMemOps;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(WaitCondition::Gte, 0)]
+    #[case(WaitCondition::Eq, 1)]
+    #[case(WaitCondition::And, 2)]
+    #[case(WaitCondition::Nor, 3)]
+    fn test_wait_condition_flags(#[case] condition: WaitCondition, #[case] flags: u32) {
+        assert_eq!(condition.to_flags(), flags);
+    }
+
+    #[rstest]
+    #[case(4, 0, true)]
+    #[case(4, 3, true)]
+    #[case(4, 4, false)]
+    #[case(0, 0, false)]
+    fn test_check_index(#[case] len: usize, #[case] index: usize, #[case] valid: bool) {
+        assert_eq!(check_index(len, index).is_ok(), valid);
+    }
+
+    #[test]
+    fn test_operands_match_type_size() {
+        assert_eq!(7u32.to_operand(), MemOpValue::U32(7));
+        assert_eq!(u64::ALL_BITS.to_operand(), MemOpValue::U64(u64::MAX));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_wait_for_write_across_streams_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let flag = DeviceBuffer::<u32>::zeroed(1).expect("Should allocate");
+        let src = DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+        let dst = DeviceBuffer::<u32>::zeroed(64).expect("Should allocate");
+        let producer = Stream::new().expect("Should create a stream");
+        let consumer = Stream::new().expect("Should create a stream");
+
+        consumer
+            .wait_value(&flag, 0, WaitCondition::Eq, 1)
+            .expect("Should queue the wait");
+        unsafe {
+            check(hipMemcpyAsync(
+                dst.as_ptr() as *mut c_void,
+                src.as_ptr() as *const c_void,
+                dst.size_in_bytes(),
+                hipMemcpyKind_hipMemcpyDeviceToDevice,
+                consumer.as_raw(),
+            ))
+            .unwrap();
+            // Nothing has written the flag yet, the consumer is blocked on the wait.
+            assert_eq!(
+                hipStreamQuery(consumer.as_raw()),
+                hipError_t_hipErrorNotReady
+            );
+            check(hipMemsetD32Async(
+                src.as_ptr() as hipDeviceptr_t,
+                42,
+                src.len(),
+                producer.as_raw(),
+            ))
+            .unwrap();
+        }
+        producer
+            .write_value(&flag, 0, 1)
+            .expect("Should queue the write");
+
+        consumer
+            .synchronize()
+            .expect("Should resume after the write");
+        assert_eq!(dst.to_vec().unwrap(), vec![42; 64]);
+        assert_eq!(flag.to_vec().unwrap(), vec![1]);
+    }
+}
@@ -0,0 +1,1084 @@
+use std::{ffi::c_void, marker::PhantomData, mem, ptr};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::kernel_args::KernelArgValue;
+use crate::launch::KernelArg;
+use crate::pitched_buffer::{check_layout, StridedView, StridedViewMut};
+
+/// Kind of the channels of an array element, see `hipChannelFormatKind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    /// Signed integer channels.
+    Signed,
+    /// Unsigned integer channels.
+    Unsigned,
+    /// Floating point channels.
+    Float,
+}
+
+impl ChannelKind {
+    fn to_raw(self) -> hipChannelFormatKind {
+        match self {
+            ChannelKind::Signed => hipChannelFormatKind_hipChannelFormatKindSigned,
+            ChannelKind::Unsigned => hipChannelFormatKind_hipChannelFormatKindUnsigned,
+            ChannelKind::Float => hipChannelFormatKind_hipChannelFormatKindFloat,
+        }
+    }
+}
+
+/// Element type of a HIP array, made of `CHANNELS` channels of `BITS` bits each.
+///
+/// # Safety
+///
+/// The type must be laid out as `CHANNELS` consecutive values of `BITS` bits of kind `KIND`.
+pub unsafe trait ChannelFormat: KernelArgValue {
+    const KIND: ChannelKind;
+    const BITS: u32;
+    const CHANNELS: u32;
+
+    /// Return the channel descriptor of the type, the one `hipCreateChannelDesc` builds
+    /// from its channel sizes and kind.
+    fn channel_desc() -> hipChannelFormatDesc {
+        let bits = |channel| {
+            if channel < Self::CHANNELS {
+                Self::BITS as i32
+            } else {
+                0
+            }
+        };
+        hipChannelFormatDesc {
+            x: bits(0),
+            y: bits(1),
+            z: bits(2),
+            w: bits(3),
+            f: Self::KIND.to_raw(),
+        }
+    }
+}
+
+macro_rules! impl_channel_format {
+    ($($ty:ty => $kind:ident, $bits:expr);* $(;)?) => {
+        $(
+            unsafe impl ChannelFormat for $ty {
+                const KIND: ChannelKind = ChannelKind::$kind;
+                const BITS: u32 = $bits;
+                const CHANNELS: u32 = 1;
+            }
+            unsafe impl ChannelFormat for [$ty; 2] {
+                const KIND: ChannelKind = ChannelKind::$kind;
+                const BITS: u32 = $bits;
+                const CHANNELS: u32 = 2;
+            }
+            unsafe impl ChannelFormat for [$ty; 4] {
+                const KIND: ChannelKind = ChannelKind::$kind;
+                const BITS: u32 = $bits;
+                const CHANNELS: u32 = 4;
+            }
+        )*
+    };
+}
+
+impl_channel_format!(
+    u8 => Unsigned, 8;
+    i8 => Signed, 8;
+    u16 => Unsigned, 16;
+    i16 => Signed, 16;
+    u32 => Unsigned, 32;
+    i32 => Signed, 32;
+    f32 => Float, 32;
+);
+
+/// Return the driver array format of `T`, used by `hipMipmappedArrayCreate`.
+fn array_format<T: ChannelFormat>() -> HipResult<hipArray_Format> {
+    let format = match (T::KIND, T::BITS) {
+        (ChannelKind::Unsigned, 8) => hipArray_Format_HIP_AD_FORMAT_UNSIGNED_INT8,
+        (ChannelKind::Unsigned, 16) => hipArray_Format_HIP_AD_FORMAT_UNSIGNED_INT16,
+        (ChannelKind::Unsigned, 32) => hipArray_Format_HIP_AD_FORMAT_UNSIGNED_INT32,
+        (ChannelKind::Signed, 8) => hipArray_Format_HIP_AD_FORMAT_SIGNED_INT8,
+        (ChannelKind::Signed, 16) => hipArray_Format_HIP_AD_FORMAT_SIGNED_INT16,
+        (ChannelKind::Signed, 32) => hipArray_Format_HIP_AD_FORMAT_SIGNED_INT32,
+        (ChannelKind::Float, 16) => hipArray_Format_HIP_AD_FORMAT_HALF,
+        (ChannelKind::Float, 32) => hipArray_Format_HIP_AD_FORMAT_FLOAT,
+        (kind, bits) => {
+            return Err(HipError::InvalidArgument(format!(
+                "arrays do not support {bits}-bit {kind:?} channels"
+            )))
+        }
+    };
+    check_channels::<T>()?;
+    Ok(format)
+}
+
+/// Return the resource view format of `T`.
+fn view_format<T: ChannelFormat>() -> HipResult<hipResourceViewFormat> {
+    check_channels::<T>()?;
+    // The formats are ordered by kind and size, then by 1, 2 and 4 channels.
+    let first = match (T::KIND, T::BITS) {
+        (ChannelKind::Unsigned, 8) => hipResourceViewFormat_hipResViewFormatUnsignedChar1,
+        (ChannelKind::Signed, 8) => hipResourceViewFormat_hipResViewFormatSignedChar1,
+        (ChannelKind::Unsigned, 16) => hipResourceViewFormat_hipResViewFormatUnsignedShort1,
+        (ChannelKind::Signed, 16) => hipResourceViewFormat_hipResViewFormatSignedShort1,
+        (ChannelKind::Unsigned, 32) => hipResourceViewFormat_hipResViewFormatUnsignedInt1,
+        (ChannelKind::Signed, 32) => hipResourceViewFormat_hipResViewFormatSignedInt1,
+        (ChannelKind::Float, 16) => hipResourceViewFormat_hipResViewFormatHalf1,
+        (ChannelKind::Float, 32) => hipResourceViewFormat_hipResViewFormatFloat1,
+        (kind, bits) => {
+            return Err(HipError::InvalidArgument(format!(
+                "resource views do not support {bits}-bit {kind:?} channels"
+            )))
+        }
+    };
+    let offset = match T::CHANNELS {
+        1 => 0,
+        2 => 1,
+        _ => 2,
+    };
+    Ok(first + offset)
+}
+
+fn check_channels<T: ChannelFormat>() -> HipResult<()> {
+    if !matches!(T::CHANNELS, 1 | 2 | 4) {
+        return Err(HipError::InvalidArgument(format!(
+            "arrays have 1, 2 or 4 channels, not {}",
+            T::CHANNELS
+        )));
+    }
+    if T::BITS as usize * T::CHANNELS as usize != mem::size_of::<T>() * 8 {
+        return Err(HipError::InvalidArgument(format!(
+            "{} channels of {} bits do not match an element of {} bytes",
+            T::CHANNELS,
+            T::BITS,
+            mem::size_of::<T>()
+        )));
+    }
+    Ok(())
+}
+
+/// Typed HIP array made with `hipMallocArray`, freed on drop.
+///
+/// A height of zero makes a 1D array, which holds a single row.
+#[derive(Debug)]
+pub struct Array<T: ChannelFormat> {
+    raw: hipArray_t,
+    width: usize,
+    height: usize,
+    flags: u32,
+    _element: PhantomData<T>,
+}
+
+impl<T: ChannelFormat> Array<T> {
+    /// Allocate an array of `height` rows of `width` elements on the current device.
+    pub fn new(width: usize, height: usize) -> HipResult<Self> {
+        Self::with_flags(width, height, hipArrayDefault)
+    }
+
+    /// Allocate an array with `flags`, for instance `hipArraySurfaceLoadStore` for arrays
+    /// backing a [`SurfaceObject`].
+    pub fn with_flags(width: usize, height: usize, flags: u32) -> HipResult<Self> {
+        check_channels::<T>()?;
+        if width == 0 {
+            return Err(HipError::InvalidArgument(
+                "arrays must be at least one element wide".to_string(),
+            ));
+        }
+        let desc = T::channel_desc();
+        let mut raw: hipArray_t = ptr::null_mut();
+        unsafe { check(hipMallocArray(&mut raw, &desc, width, height, flags))? };
+        Ok(Self {
+            raw,
+            width,
+            height,
+            flags,
+            _element: PhantomData,
+        })
+    }
+
+    /// Copy tightly packed rows from the host with `hipMemcpy2DToArray`.
+    pub fn copy_from_host(&mut self, data: &[T]) -> HipResult<()> {
+        self.copy_from_view(StridedView::new(data, self.width))
+    }
+
+    /// Copy the array into tightly packed rows on the host with `hipMemcpy2DFromArray`.
+    pub fn copy_to_host(&self, data: &mut [T]) -> HipResult<()> {
+        self.copy_to_view(StridedViewMut::new(data, self.width))
+    }
+
+    /// Copy rows from a strided host view with `hipMemcpy2DToArray`.
+    pub fn copy_from_view(&mut self, view: StridedView<T>) -> HipResult<()> {
+        let layout = check_layout::<T>(view.data.len(), view.row_stride, 0, self.extent())?;
+        unsafe {
+            check(hipMemcpy2DToArray(
+                self.raw,
+                0,
+                0,
+                view.data.as_ptr() as *const c_void,
+                layout.pitch,
+                self.width * mem::size_of::<T>(),
+                self.rows(),
+                hipMemcpyKind_hipMemcpyHostToDevice,
+            ))
+        }
+    }
+
+    /// Copy the array into a strided host view with `hipMemcpy2DFromArray`.
+    pub fn copy_to_view(&self, view: StridedViewMut<T>) -> HipResult<()> {
+        let layout = check_layout::<T>(view.data.len(), view.row_stride, 0, self.extent())?;
+        unsafe {
+            check(hipMemcpy2DFromArray(
+                view.data.as_mut_ptr() as *mut c_void,
+                layout.pitch,
+                self.raw,
+                0,
+                0,
+                self.width * mem::size_of::<T>(),
+                self.rows(),
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+            ))
+        }
+    }
+
+    /// Return the underlying `hipArray_t`.
+    pub fn as_raw(&self) -> hipArray_t {
+        self.raw
+    }
+
+    /// Return the number of elements in a row.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Return the number of rows, zero for 1D arrays.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Return the flags the array was allocated with.
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    fn rows(&self) -> usize {
+        self.height.max(1)
+    }
+
+    fn extent(&self) -> [usize; 3] {
+        [self.width, self.rows(), 1]
+    }
+}
+
+impl<T: ChannelFormat> Drop for Array<T> {
+    fn drop(&mut self) {
+        unsafe {
+            hipFreeArray(self.raw);
+        }
+    }
+}
+
+/// Typed HIP mipmapped array made with `hipMipmappedArrayCreate`, destroyed on drop.
+///
+/// Level 0 is `[width, height, depth]` elements and each following level halves every
+/// non-zero dimension, down to one element.
+#[derive(Debug)]
+pub struct MipmappedArray<T: ChannelFormat> {
+    raw: hipMipmappedArray_t,
+    extent: [usize; 3],
+    levels: u32,
+    _element: PhantomData<T>,
+}
+
+impl<T: ChannelFormat> MipmappedArray<T> {
+    /// Allocate `levels` mipmap levels of `[width, height, depth]` elements on the current
+    /// device, a height or depth of zero making a 1D or 2D array.
+    pub fn new(extent: [usize; 3], levels: u32) -> HipResult<Self> {
+        Self::with_flags(extent, levels, hipArrayDefault)
+    }
+
+    /// Allocate a mipmapped array with `flags`.
+    pub fn with_flags(extent: [usize; 3], levels: u32, flags: u32) -> HipResult<Self> {
+        check_mip_levels(extent, levels)?;
+        let [width, height, depth] = extent;
+        let mut desc = HIP_ARRAY3D_DESCRIPTOR {
+            Width: width,
+            Height: height,
+            Depth: depth,
+            Format: array_format::<T>()?,
+            NumChannels: T::CHANNELS,
+            Flags: flags,
+        };
+        let mut raw: hipMipmappedArray_t = ptr::null_mut();
+        unsafe { check(hipMipmappedArrayCreate(&mut raw, &mut desc, levels))? };
+        Ok(Self {
+            raw,
+            extent,
+            levels,
+            _element: PhantomData,
+        })
+    }
+
+    /// Copy tightly packed elements from the host into `level` with `hipMemcpy3D`.
+    pub fn copy_level_from_host(&mut self, level: u32, data: &[T]) -> HipResult<()> {
+        let mut params = self.level_copy_params(level, data.len())?;
+        params.srcPtr.ptr = data.as_ptr() as *mut c_void;
+        params.dstArray = self.level(level)?;
+        params.kind = hipMemcpyKind_hipMemcpyHostToDevice;
+        unsafe { check(hipMemcpy3D(&params)) }
+    }
+
+    /// Copy `level` into tightly packed elements on the host with `hipMemcpy3D`.
+    pub fn copy_level_to_host(&self, level: u32, data: &mut [T]) -> HipResult<()> {
+        let mut params = self.level_copy_params(level, data.len())?;
+        params.dstPtr = params.srcPtr;
+        params.dstPtr.ptr = data.as_mut_ptr() as *mut c_void;
+        params.srcPtr = unsafe { zeroed_raw() };
+        params.srcArray = self.level(level)?;
+        params.kind = hipMemcpyKind_hipMemcpyDeviceToHost;
+        unsafe { check(hipMemcpy3D(&params)) }
+    }
+
+    /// Return the array of `level` with `hipMipmappedArrayGetLevel`, owned by this array.
+    pub fn level(&self, level: u32) -> HipResult<hipArray_t> {
+        self.check_level(level)?;
+        let mut raw: hipArray_t = ptr::null_mut();
+        unsafe { check(hipMipmappedArrayGetLevel(&mut raw, self.raw, level))? };
+        Ok(raw)
+    }
+
+    /// Return the `[width, height, depth]` of `level`, zero for the missing dimensions.
+    pub fn level_extent(&self, level: u32) -> HipResult<[usize; 3]> {
+        self.check_level(level)?;
+        Ok(mip_level_extent(self.extent, level))
+    }
+
+    /// Return the underlying `hipMipmappedArray_t`.
+    pub fn as_raw(&self) -> hipMipmappedArray_t {
+        self.raw
+    }
+
+    /// Return the `[width, height, depth]` of level 0.
+    pub fn extent(&self) -> [usize; 3] {
+        self.extent
+    }
+
+    /// Return the number of mipmap levels.
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    fn check_level(&self, level: u32) -> HipResult<()> {
+        if level >= self.levels {
+            return Err(HipError::InvalidArgument(format!(
+                "level {level} is out of the {} levels of the array",
+                self.levels
+            )));
+        }
+        Ok(())
+    }
+
+    /// Return the copy parameters of `level` with the host side in `srcPtr`.
+    fn level_copy_params(&self, level: u32, len: usize) -> HipResult<hipMemcpy3DParms> {
+        let [width, height, depth] = self.level_extent(level)?;
+        let extent = [width, height.max(1), depth.max(1)];
+        let expected = extent.iter().product::<usize>();
+        if len != expected {
+            return Err(HipError::InvalidArgument(format!(
+                "host slice of {len} elements does not match the {expected} elements of \
+                 level {level}"
+            )));
+        }
+        let layout = check_layout::<T>(len, width, 0, extent)?;
+        // Null pointers and arrays are filled by the caller, and zero positions copy the
+        // whole level.
+        let mut params: hipMemcpy3DParms = unsafe { zeroed_raw() };
+        params.srcPtr = hipPitchedPtr {
+            ptr: ptr::null_mut(),
+            pitch: layout.pitch,
+            xsize: width,
+            ysize: extent[1],
+        };
+        // Copies involving an array measure the width in elements.
+        params.extent = hipExtent {
+            width: extent[0],
+            height: extent[1],
+            depth: extent[2],
+        };
+        Ok(params)
+    }
+}
+
+impl<T: ChannelFormat> Drop for MipmappedArray<T> {
+    fn drop(&mut self) {
+        unsafe {
+            hipMipmappedArrayDestroy(self.raw);
+        }
+    }
+}
+
+fn mip_level_extent(extent: [usize; 3], level: u32) -> [usize; 3] {
+    extent.map(|size| {
+        if size == 0 {
+            0
+        } else {
+            size.checked_shr(level).unwrap_or(0).max(1)
+        }
+    })
+}
+
+fn check_mip_levels(extent: [usize; 3], levels: u32) -> HipResult<()> {
+    let [width, height, depth] = extent;
+    if width == 0 || (height == 0 && depth > 0) {
+        return Err(HipError::InvalidArgument(format!(
+            "mipmapped array extent {extent:?} is empty"
+        )));
+    }
+    let largest = width.max(height).max(depth);
+    let max_levels = usize::BITS - largest.leading_zeros();
+    if levels == 0 || levels > max_levels {
+        return Err(HipError::InvalidArgument(format!(
+            "{levels} levels do not fit an extent of {extent:?}, which has 1 to {max_levels}"
+        )));
+    }
+    Ok(())
+}
+
+/// Texture coordinate handling outside of the texture, see `hipTextureAddressMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressMode {
+    /// Repeat the texture.
+    Wrap,
+    /// Read the closest edge element.
+    Clamp,
+    /// Repeat the texture, flipping every other copy.
+    Mirror,
+    /// Read the border color.
+    Border,
+}
+
+impl AddressMode {
+    fn to_raw(self) -> hipTextureAddressMode {
+        match self {
+            AddressMode::Wrap => hipTextureAddressMode_hipAddressModeWrap,
+            AddressMode::Clamp => hipTextureAddressMode_hipAddressModeClamp,
+            AddressMode::Mirror => hipTextureAddressMode_hipAddressModeMirror,
+            AddressMode::Border => hipTextureAddressMode_hipAddressModeBorder,
+        }
+    }
+}
+
+/// Texture filtering between elements, see `hipTextureFilterMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    /// Read the closest element.
+    Point,
+    /// Interpolate linearly between the closest elements.
+    Linear,
+}
+
+impl FilterMode {
+    fn to_raw(self) -> hipTextureFilterMode {
+        match self {
+            FilterMode::Point => hipTextureFilterMode_hipFilterModePoint,
+            FilterMode::Linear => hipTextureFilterMode_hipFilterModeLinear,
+        }
+    }
+}
+
+/// Value returned by texture fetches, see `hipTextureReadMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Return the element as stored.
+    ElementType,
+    /// Return integer channels mapped to `[0, 1]` or `[-1, 1]`.
+    NormalizedFloat,
+}
+
+impl ReadMode {
+    fn to_raw(self) -> hipTextureReadMode {
+        match self {
+            ReadMode::ElementType => hipTextureReadMode_hipReadModeElementType,
+            ReadMode::NormalizedFloat => hipTextureReadMode_hipReadModeNormalizedFloat,
+        }
+    }
+}
+
+/// Sampling state of a texture, the typed counterpart of `hipTextureDesc`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDesc {
+    /// Address mode of each dimension.
+    pub address_mode: [AddressMode; 3],
+    /// Filtering between the elements of a mipmap level.
+    pub filter_mode: FilterMode,
+    /// Value returned by fetches.
+    pub read_mode: ReadMode,
+    /// Address the texture with coordinates in `[0, 1)` instead of elements.
+    pub normalized_coords: bool,
+    /// Convert sRGB encoded channels to linear values on reads.
+    pub srgb: bool,
+    /// Color read outside of the texture with [`AddressMode::Border`].
+    pub border_color: [f32; 4],
+    /// Largest anisotropy ratio of filtered fetches, 0 or 1 to disable anisotropic filtering.
+    pub max_anisotropy: u32,
+    /// Filtering between mipmap levels.
+    pub mipmap_filter_mode: FilterMode,
+    /// Offset added to the mipmap level computed by fetches.
+    pub mipmap_level_bias: f32,
+    /// Lowest mipmap level fetches can read, the most detailed one being 0.
+    pub min_mipmap_level_clamp: f32,
+    /// Highest mipmap level fetches can read.
+    pub max_mipmap_level_clamp: f32,
+}
+
+impl Default for TextureDesc {
+    /// Clamped, unfiltered and unnormalized reads of the elements as stored.
+    fn default() -> Self {
+        Self {
+            address_mode: [AddressMode::Clamp; 3],
+            filter_mode: FilterMode::Point,
+            read_mode: ReadMode::ElementType,
+            normalized_coords: false,
+            srgb: false,
+            border_color: [0.0; 4],
+            max_anisotropy: 0,
+            mipmap_filter_mode: FilterMode::Point,
+            mipmap_level_bias: 0.0,
+            min_mipmap_level_clamp: 0.0,
+            max_mipmap_level_clamp: 0.0,
+        }
+    }
+}
+
+impl TextureDesc {
+    /// Check the sampling state against the element type `T` of the texture.
+    fn validate<T: ChannelFormat>(&self) -> HipResult<()> {
+        if self.read_mode == ReadMode::NormalizedFloat
+            && (T::KIND == ChannelKind::Float || T::BITS > 16)
+        {
+            return Err(HipError::InvalidArgument(format!(
+                "normalized float reads need 8 or 16-bit integer channels, not {}-bit {:?}",
+                T::BITS,
+                T::KIND
+            )));
+        }
+        if self.filter_mode == FilterMode::Linear
+            && T::KIND != ChannelKind::Float
+            && self.read_mode == ReadMode::ElementType
+        {
+            return Err(HipError::InvalidArgument(
+                "linear filtering of integer channels needs normalized float reads".to_string(),
+            ));
+        }
+        if !self.normalized_coords
+            && self
+                .address_mode
+                .iter()
+                .any(|mode| matches!(mode, AddressMode::Wrap | AddressMode::Mirror))
+        {
+            return Err(HipError::InvalidArgument(
+                "wrap and mirror address modes need normalized coordinates".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn to_raw(self) -> hipTextureDesc {
+        hipTextureDesc {
+            addressMode: self.address_mode.map(AddressMode::to_raw),
+            filterMode: self.filter_mode.to_raw(),
+            readMode: self.read_mode.to_raw(),
+            sRGB: self.srgb as i32,
+            borderColor: self.border_color,
+            normalizedCoords: self.normalized_coords as i32,
+            maxAnisotropy: self.max_anisotropy,
+            mipmapFilterMode: self.mipmap_filter_mode.to_raw(),
+            mipmapLevelBias: self.mipmap_level_bias,
+            minMipmapLevelClamp: self.min_mipmap_level_clamp,
+            maxMipmapLevelClamp: self.max_mipmap_level_clamp,
+        }
+    }
+}
+
+/// Part of a resource seen by a texture, the typed counterpart of `hipResourceViewDesc`
+/// whose format comes from the element type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceViewDesc {
+    /// Width of the view in elements.
+    pub width: usize,
+    /// Height of the view in elements, 0 for 1D resources.
+    pub height: usize,
+    /// Depth of the view in elements, 0 for 1D and 2D resources.
+    pub depth: usize,
+    /// Most detailed mipmap level seen by the texture.
+    pub first_mipmap_level: u32,
+    /// Least detailed mipmap level seen by the texture.
+    pub last_mipmap_level: u32,
+    /// First layer of a layered array seen by the texture.
+    pub first_layer: u32,
+    /// Last layer of a layered array seen by the texture.
+    pub last_layer: u32,
+}
+
+impl ResourceViewDesc {
+    /// View `[width, height, depth]` elements of the first mipmap level and layer.
+    pub fn new([width, height, depth]: [usize; 3]) -> Self {
+        Self {
+            width,
+            height,
+            depth,
+            first_mipmap_level: 0,
+            last_mipmap_level: 0,
+            first_layer: 0,
+            last_layer: 0,
+        }
+    }
+
+    /// View the mipmap levels `first..=last`.
+    pub fn with_mipmap_levels(mut self, first: u32, last: u32) -> Self {
+        self.first_mipmap_level = first;
+        self.last_mipmap_level = last;
+        self
+    }
+
+    /// View the layers `first..=last`.
+    pub fn with_layers(mut self, first: u32, last: u32) -> Self {
+        self.first_layer = first;
+        self.last_layer = last;
+        self
+    }
+
+    fn to_raw<T: ChannelFormat>(self) -> HipResult<hipResourceViewDesc> {
+        if self.first_mipmap_level > self.last_mipmap_level || self.first_layer > self.last_layer {
+            return Err(HipError::InvalidArgument(format!(
+                "resource view {self:?} has an empty range of levels or layers"
+            )));
+        }
+        Ok(hipResourceViewDesc {
+            format: view_format::<T>()?,
+            width: self.width,
+            height: self.height,
+            depth: self.depth,
+            firstMipmapLevel: self.first_mipmap_level,
+            lastMipmapLevel: self.last_mipmap_level,
+            firstLayer: self.first_layer,
+            lastLayer: self.last_layer,
+        })
+    }
+}
+
+/// Array backing a [`TextureObject`].
+#[derive(Debug)]
+pub enum TextureResource<T: ChannelFormat> {
+    /// Array with a single level.
+    Array(Array<T>),
+    /// Array with a chain of mipmap levels.
+    Mipmapped(MipmappedArray<T>),
+}
+
+impl<T: ChannelFormat> TextureResource<T> {
+    fn to_raw(&self) -> hipResourceDesc {
+        match self {
+            TextureResource::Array(array) => array_resource_desc(array.as_raw()),
+            TextureResource::Mipmapped(array) => {
+                let mut desc: hipResourceDesc = unsafe { zeroed_raw() };
+                desc.resType = hipResourceType_hipResourceTypeMipmappedArray;
+                desc.res.mipmap.mipmap = array.as_raw();
+                desc
+            }
+        }
+    }
+}
+
+fn array_resource_desc(array: hipArray_t) -> hipResourceDesc {
+    let mut desc: hipResourceDesc = unsafe { zeroed_raw() };
+    desc.resType = hipResourceType_hipResourceTypeArray;
+    desc.res.array.array = array;
+    desc
+}
+
+impl<T: ChannelFormat> From<Array<T>> for TextureResource<T> {
+    fn from(array: Array<T>) -> Self {
+        TextureResource::Array(array)
+    }
+}
+
+impl<T: ChannelFormat> From<MipmappedArray<T>> for TextureResource<T> {
+    fn from(array: MipmappedArray<T>) -> Self {
+        TextureResource::Mipmapped(array)
+    }
+}
+
+/// Builder of a [`TextureObject`], created by [`TextureObject::builder`].
+#[derive(Debug)]
+pub struct TextureObjectBuilder<T: ChannelFormat> {
+    resource: TextureResource<T>,
+    desc: TextureDesc,
+    view: Option<ResourceViewDesc>,
+}
+
+impl<T: ChannelFormat> TextureObjectBuilder<T> {
+    /// Set the address mode of every dimension.
+    pub fn with_address_mode(mut self, mode: AddressMode) -> Self {
+        self.desc.address_mode = [mode; 3];
+        self
+    }
+
+    /// Set the address mode of each dimension.
+    pub fn with_address_modes(mut self, modes: [AddressMode; 3]) -> Self {
+        self.desc.address_mode = modes;
+        self
+    }
+
+    /// Set the filtering between elements.
+    pub fn with_filter_mode(mut self, mode: FilterMode) -> Self {
+        self.desc.filter_mode = mode;
+        self
+    }
+
+    /// Set the value returned by fetches.
+    pub fn with_read_mode(mut self, mode: ReadMode) -> Self {
+        self.desc.read_mode = mode;
+        self
+    }
+
+    /// Set whether the texture is addressed with coordinates in `[0, 1)`.
+    pub fn with_normalized_coords(mut self, normalized: bool) -> Self {
+        self.desc.normalized_coords = normalized;
+        self
+    }
+
+    /// Set the color read outside of the texture with [`AddressMode::Border`].
+    pub fn with_border_color(mut self, color: [f32; 4]) -> Self {
+        self.desc.border_color = color;
+        self
+    }
+
+    /// Replace the whole sampling state.
+    pub fn with_desc(mut self, desc: TextureDesc) -> Self {
+        self.desc = desc;
+        self
+    }
+
+    /// Restrict the texture to a view of its resource.
+    pub fn with_view(mut self, view: ResourceViewDesc) -> Self {
+        self.view = Some(view);
+        self
+    }
+
+    /// Create the texture object with `hipCreateTextureObject`.
+    pub fn build(self) -> HipResult<TextureObject<T>> {
+        self.desc.validate::<T>()?;
+        let resource = self.resource.to_raw();
+        let desc = self.desc.to_raw();
+        let view = self.view.map(|view| view.to_raw::<T>()).transpose()?;
+        let view_ptr = view
+            .as_ref()
+            .map_or(ptr::null(), |view| view as *const hipResourceViewDesc);
+        let mut raw: hipTextureObject_t = ptr::null_mut();
+        unsafe { check(hipCreateTextureObject(&mut raw, &resource, &desc, view_ptr))? };
+        Ok(TextureObject {
+            raw,
+            resource: self.resource,
+        })
+    }
+}
+
+/// Texture object reading an array it owns, destroyed before the array on drop.
+#[derive(Debug)]
+pub struct TextureObject<T: ChannelFormat> {
+    raw: hipTextureObject_t,
+    resource: TextureResource<T>,
+}
+
+impl<T: ChannelFormat> TextureObject<T> {
+    /// Start building a texture object reading `resource` with the default [`TextureDesc`].
+    pub fn builder(resource: impl Into<TextureResource<T>>) -> TextureObjectBuilder<T> {
+        TextureObjectBuilder {
+            resource: resource.into(),
+            desc: TextureDesc::default(),
+            view: None,
+        }
+    }
+
+    /// Return the underlying `hipTextureObject_t`.
+    pub fn as_raw(&self) -> hipTextureObject_t {
+        self.raw
+    }
+
+    /// Return the array read by the texture.
+    pub fn resource(&self) -> &TextureResource<T> {
+        &self.resource
+    }
+}
+
+impl<T: ChannelFormat> Drop for TextureObject<T> {
+    fn drop(&mut self) {
+        unsafe {
+            hipDestroyTextureObject(self.raw);
+        }
+    }
+}
+
+unsafe impl<T: ChannelFormat> KernelArg for TextureObject<T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.raw as *const hipTextureObject_t as *mut c_void
+    }
+}
+
+/// Surface object reading and writing an array it owns, destroyed before the array on drop.
+#[derive(Debug)]
+pub struct SurfaceObject<T: ChannelFormat> {
+    raw: hipSurfaceObject_t,
+    array: Array<T>,
+}
+
+impl<T: ChannelFormat> SurfaceObject<T> {
+    /// Create a surface object over `array` with `hipCreateSurfaceObject`.
+    ///
+    /// The array must be allocated with `hipArraySurfaceLoadStore`.
+    pub fn new(array: Array<T>) -> HipResult<Self> {
+        if array.flags() & hipArraySurfaceLoadStore == 0 {
+            return Err(HipError::InvalidArgument(
+                "surface arrays must be allocated with hipArraySurfaceLoadStore".to_string(),
+            ));
+        }
+        let resource = array_resource_desc(array.as_raw());
+        let mut raw: hipSurfaceObject_t = ptr::null_mut();
+        unsafe { check(hipCreateSurfaceObject(&mut raw, &resource))? };
+        Ok(Self { raw, array })
+    }
+
+    /// Return the underlying `hipSurfaceObject_t`.
+    pub fn as_raw(&self) -> hipSurfaceObject_t {
+        self.raw
+    }
+
+    /// Return the array of the surface.
+    pub fn array(&self) -> &Array<T> {
+        &self.array
+    }
+
+    /// Return the array of the surface, to copy data in and out of it.
+    pub fn array_mut(&mut self) -> &mut Array<T> {
+        &mut self.array
+    }
+}
+
+impl<T: ChannelFormat> Drop for SurfaceObject<T> {
+    fn drop(&mut self) {
+        unsafe {
+            hipDestroySurfaceObject(self.raw);
+        }
+    }
+}
+
+unsafe impl<T: ChannelFormat> KernelArg for SurfaceObject<T> {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.raw as *const hipSurfaceObject_t as *mut c_void
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[test]
+    fn test_channel_desc_matches_element_type() {
+        let desc = <[u8; 4]>::channel_desc();
+        assert_eq!((desc.x, desc.y, desc.z, desc.w), (8, 8, 8, 8));
+        assert_eq!(desc.f, hipChannelFormatKind_hipChannelFormatKindUnsigned);
+        let desc = <[f32; 2]>::channel_desc();
+        assert_eq!((desc.x, desc.y, desc.z, desc.w), (32, 32, 0, 0));
+        assert_eq!(desc.f, hipChannelFormatKind_hipChannelFormatKindFloat);
+        let desc = i16::channel_desc();
+        assert_eq!((desc.x, desc.y, desc.z, desc.w), (16, 0, 0, 0));
+        assert_eq!(desc.f, hipChannelFormatKind_hipChannelFormatKindSigned);
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(
+            array_format::<[i8; 2]>().unwrap(),
+            hipArray_Format_HIP_AD_FORMAT_SIGNED_INT8
+        );
+        assert_eq!(
+            array_format::<f32>().unwrap(),
+            hipArray_Format_HIP_AD_FORMAT_FLOAT
+        );
+        assert_eq!(
+            view_format::<u16>().unwrap(),
+            hipResourceViewFormat_hipResViewFormatUnsignedShort1
+        );
+        assert_eq!(
+            view_format::<[i32; 2]>().unwrap(),
+            hipResourceViewFormat_hipResViewFormatSignedInt2
+        );
+        assert_eq!(
+            view_format::<[f32; 4]>().unwrap(),
+            hipResourceViewFormat_hipResViewFormatFloat4
+        );
+    }
+
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    struct Rgb([u8; 3]);
+
+    unsafe impl KernelArgValue for Rgb {}
+    unsafe impl ChannelFormat for Rgb {
+        const KIND: ChannelKind = ChannelKind::Unsigned;
+        const BITS: u32 = 8;
+        const CHANNELS: u32 = 3;
+    }
+
+    #[test]
+    fn test_three_channels_are_rejected() {
+        assert!(array_format::<Rgb>().is_err());
+        assert!(view_format::<Rgb>().is_err());
+    }
+
+    #[rstest]
+    #[case::level_0([64, 32, 0], 0, [64, 32, 0])]
+    #[case::level_1([64, 32, 0], 1, [32, 16, 0])]
+    #[case::clamped_to_one([64, 32, 0], 6, [1, 1, 0])]
+    #[case::volume([8, 4, 2], 2, [2, 1, 1])]
+    fn test_mip_level_extent(
+        #[case] extent: [usize; 3],
+        #[case] level: u32,
+        #[case] expected: [usize; 3],
+    ) {
+        assert_eq!(mip_level_extent(extent, level), expected);
+    }
+
+    #[rstest]
+    #[case::one_level([64, 32, 0], 1, true)]
+    #[case::full_chain([64, 32, 0], 7, true)]
+    #[case::too_many([64, 32, 0], 8, false)]
+    #[case::no_level([64, 32, 0], 0, false)]
+    #[case::empty([0, 32, 0], 1, false)]
+    #[case::depth_without_height([64, 0, 4], 1, false)]
+    fn test_check_mip_levels(#[case] extent: [usize; 3], #[case] levels: u32, #[case] valid: bool) {
+        assert_eq!(check_mip_levels(extent, levels).is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case::default(TextureDesc::default(), true)]
+    #[case::linear_float(TextureDesc { filter_mode: FilterMode::Linear, ..Default::default() }, true)]
+    #[case::wrap_unnormalized(TextureDesc { address_mode: [AddressMode::Wrap; 3], ..Default::default() }, false)]
+    #[case::wrap_normalized(TextureDesc { address_mode: [AddressMode::Wrap; 3], normalized_coords: true, ..Default::default() }, true)]
+    #[case::normalized_float_read(TextureDesc { read_mode: ReadMode::NormalizedFloat, ..Default::default() }, false)]
+    fn test_validate_float_texture(#[case] desc: TextureDesc, #[case] valid: bool) {
+        assert_eq!(desc.validate::<f32>().is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case::linear_element_type(FilterMode::Linear, ReadMode::ElementType, false)]
+    #[case::linear_normalized(FilterMode::Linear, ReadMode::NormalizedFloat, true)]
+    #[case::point_element_type(FilterMode::Point, ReadMode::ElementType, true)]
+    fn test_validate_integer_texture(
+        #[case] filter_mode: FilterMode,
+        #[case] read_mode: ReadMode,
+        #[case] valid: bool,
+    ) {
+        let desc = TextureDesc {
+            filter_mode,
+            read_mode,
+            ..Default::default()
+        };
+        assert_eq!(desc.validate::<[u8; 4]>().is_ok(), valid);
+    }
+
+    #[test]
+    fn test_normalized_float_reads_need_narrow_channels() {
+        let desc = TextureDesc {
+            read_mode: ReadMode::NormalizedFloat,
+            ..Default::default()
+        };
+        assert!(desc.validate::<i16>().is_ok());
+        assert!(desc.validate::<u32>().is_err());
+    }
+
+    #[test]
+    fn test_texture_desc_to_raw() {
+        let desc = TextureDesc {
+            address_mode: [AddressMode::Border, AddressMode::Clamp, AddressMode::Mirror],
+            filter_mode: FilterMode::Linear,
+            normalized_coords: true,
+            border_color: [1.0, 0.0, 0.0, 1.0],
+            ..Default::default()
+        };
+        let raw = desc.to_raw();
+        assert_eq!(
+            raw.addressMode,
+            [
+                hipTextureAddressMode_hipAddressModeBorder,
+                hipTextureAddressMode_hipAddressModeClamp,
+                hipTextureAddressMode_hipAddressModeMirror,
+            ]
+        );
+        assert_eq!(raw.filterMode, hipTextureFilterMode_hipFilterModeLinear);
+        assert_eq!(raw.readMode, hipTextureReadMode_hipReadModeElementType);
+        assert_eq!(raw.normalizedCoords, 1);
+        assert_eq!(raw.borderColor, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_resource_view_desc_to_raw() {
+        let view = ResourceViewDesc::new([16, 8, 0]).with_mipmap_levels(1, 3);
+        let raw = view.to_raw::<[f32; 2]>().unwrap();
+        assert_eq!(raw.format, hipResourceViewFormat_hipResViewFormatFloat2);
+        assert_eq!((raw.width, raw.height, raw.depth), (16, 8, 0));
+        assert_eq!((raw.firstMipmapLevel, raw.lastMipmapLevel), (1, 3));
+        let empty = ResourceViewDesc::new([16, 8, 0]).with_layers(2, 1);
+        assert!(empty.to_raw::<f32>().is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_texture_and_surface_kernels_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let source = r#"
+extern "C" __global__ void fetch(hipTextureObject_t tex, float *out, int width) {
+  int x = threadIdx.x;
+  int y = threadIdx.y;
+  out[y * width + x] = tex2D<float>(tex, x + 0.5f, y + 0.5f);
+}
+
+extern "C" __global__ void store(hipSurfaceObject_t surf) {
+  int x = threadIdx.x;
+  int y = threadIdx.y;
+  surf2Dwrite(float(y * 10 + x), surf, x * (int)sizeof(float), y);
+}
+"#;
+        let mut program = crate::Program::new(source, "texture.hip").expect("Should create");
+        program
+            .compile::<&str>(&[])
+            .expect("Should compile the kernels");
+        let module = crate::Module::from_program(&program).expect("Should load the module");
+        let (width, height) = (8usize, 4usize);
+        let config = crate::LaunchConfig::new(1, (width as u32, height as u32));
+
+        // Write the array through a surface, then read it back through a texture.
+        let array = Array::<f32>::with_flags(width, height, hipArraySurfaceLoadStore)
+            .expect("Should allocate the array");
+        let surface = SurfaceObject::new(array).expect("Should create the surface");
+        let store = module.function("store").unwrap();
+        unsafe { store.launch(&config, &(&surface,)) }.expect("Should launch the store");
+        unsafe { check(hipDeviceSynchronize()).unwrap() };
+        let mut stored = vec![0.0f32; width * height];
+        surface.array().copy_to_host(&mut stored).unwrap();
+        let expected: Vec<f32> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (y * 10 + x) as f32))
+            .collect();
+        assert_eq!(stored, expected);
+
+        let mut array = Array::<f32>::new(width, height).unwrap();
+        array.copy_from_host(&expected).unwrap();
+        let texture = TextureObject::builder(array)
+            .build()
+            .expect("Should create the texture");
+        let out = crate::DeviceBuffer::<f32>::zeroed(width * height).unwrap();
+        let fetch = module.function("fetch").unwrap();
+        unsafe { fetch.launch(&config, &(&texture, &out, width as i32)) }
+            .expect("Should launch the fetch");
+        assert_eq!(out.to_vec().unwrap(), expected);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::bindings::*;
+use crate::device::{current_device, device_count, set_device};
+use crate::error::{check, HipResult};
+
+/// Kind of interconnect between two devices, as reported by `hipExtGetLinkTypeAndHopCount`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkType {
+    /// AMD HyperTransport link.
+    HyperTransport,
+    /// Intel QuickPath Interconnect link.
+    Qpi,
+    /// PCI Express link.
+    Pcie,
+    /// InfiniBand link.
+    Infiniband,
+    /// AMD Infinity Fabric link between GPUs.
+    Xgmi,
+    /// Link type unknown to this crate.
+    Other(u32),
+}
+
+impl From<u32> for LinkType {
+    /// Convert a HSA link type, e.g. `HSA_AMD_LINK_INFO_TYPE_XGMI`.
+    fn from(value: u32) -> Self {
+        match value {
+            0 => LinkType::HyperTransport,
+            1 => LinkType::Qpi,
+            2 => LinkType::Pcie,
+            3 => LinkType::Infiniband,
+            4 => LinkType::Xgmi,
+            other => LinkType::Other(other),
+        }
+    }
+}
+
+/// Connection from a device to one of its peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerLink {
+    /// True if the source device can access the memory of the peer.
+    pub can_access: bool,
+    /// True if atomic operations on the memory of the peer are supported natively.
+    pub native_atomics: bool,
+    /// Relative performance of the link, lower is better.
+    pub performance_rank: i32,
+    /// Kind of interconnect on the first hop to the peer.
+    pub link_type: LinkType,
+    /// Number of hops between the two devices.
+    pub hop_count: u32,
+}
+
+impl PeerLink {
+    /// Query the link from `device` to `peer`.
+    pub fn query(device: i32, peer: i32) -> HipResult<Self> {
+        let mut can_access = 0;
+        let mut link_type = 0;
+        let mut hop_count = 0;
+        unsafe {
+            check(hipDeviceCanAccessPeer(&mut can_access, device, peer))?;
+            check(hipExtGetLinkTypeAndHopCount(
+                device,
+                peer,
+                &mut link_type,
+                &mut hop_count,
+            ))?;
+        }
+        Ok(Self {
+            can_access: can_access != 0,
+            native_atomics: p2p_attribute(
+                hipDeviceP2PAttr_hipDevP2PAttrNativeAtomicSupported,
+                device,
+                peer,
+            )? != 0,
+            performance_rank: p2p_attribute(
+                hipDeviceP2PAttr_hipDevP2PAttrPerformanceRank,
+                device,
+                peer,
+            )?,
+            link_type: link_type.into(),
+            hop_count,
+        })
+    }
+}
+
+fn p2p_attribute(attribute: hipDeviceP2PAttr, device: i32, peer: i32) -> HipResult<i32> {
+    let mut value = 0;
+    unsafe {
+        check(hipDeviceGetP2PAttribute(
+            &mut value, attribute, device, peer,
+        ))?
+    };
+    Ok(value)
+}
+
+/// N×N matrix of the links between all the visible devices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topology {
+    device_count: usize,
+    links: Vec<Option<PeerLink>>,
+}
+
+impl Topology {
+    /// Query the links between every pair of visible devices.
+    pub fn query() -> HipResult<Self> {
+        let count = device_count()?;
+        Self::from_fn(count as usize, |device, peer| {
+            PeerLink::query(device as i32, peer as i32)
+        })
+    }
+
+    /// Build a topology of `device_count` devices from the link of each ordered pair of
+    /// distinct devices.
+    pub fn from_fn<F>(device_count: usize, mut link: F) -> HipResult<Self>
+    where
+        F: FnMut(usize, usize) -> HipResult<PeerLink>,
+    {
+        let mut links = Vec::with_capacity(device_count * device_count);
+        for device in 0..device_count {
+            for peer in 0..device_count {
+                links.push(if device == peer {
+                    None
+                } else {
+                    Some(link(device, peer)?)
+                });
+            }
+        }
+        Ok(Self {
+            device_count,
+            links,
+        })
+    }
+
+    /// Return the number of devices.
+    pub fn device_count(&self) -> usize {
+        self.device_count
+    }
+
+    /// Return the link from `device` to `peer`, `None` when both are the same device or out
+    /// of range.
+    pub fn link(&self, device: usize, peer: usize) -> Option<&PeerLink> {
+        if device >= self.device_count || peer >= self.device_count {
+            return None;
+        }
+        self.links[device * self.device_count + peer].as_ref()
+    }
+
+    /// Return the peers `device` can access, closest first: by link type with xGMI first,
+    /// then hop count, then performance rank.
+    pub fn peers(&self, device: usize) -> Vec<usize> {
+        let mut peers: Vec<_> = (0..self.device_count)
+            .filter(|peer| self.link(device, *peer).is_some_and(|link| link.can_access))
+            .collect();
+        peers.sort_by_key(|peer| {
+            let link = self.link(device, *peer).unwrap();
+            (
+                link.link_type != LinkType::Xgmi,
+                link.hop_count,
+                link.performance_rank,
+            )
+        });
+        peers
+    }
+
+    /// Enable peer access for every pair of devices that supports it.
+    ///
+    /// The current device of the calling thread is restored afterward.
+    pub fn enable_peer_access(&self) -> HipResult<()> {
+        let current = current_device()?;
+        let result = self.enable_all_peers();
+        let restored = set_device(current);
+        result.and(restored)
+    }
+
+    fn enable_all_peers(&self) -> HipResult<()> {
+        for device in 0..self.device_count {
+            set_device(device as i32)?;
+            for peer in 0..self.device_count {
+                if !self.link(device, peer).is_some_and(|link| link.can_access) {
+                    continue;
+                }
+                match unsafe { hipDeviceEnablePeerAccess(peer as i32, 0) } {
+                    hipError_t_hipErrorPeerAccessAlreadyEnabled => {}
+                    status => check(status)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Two xGMI hives of two devices, connected to each other over PCIe.
+    fn two_hives() -> Topology {
+        Topology::from_fn(4, |device, peer| {
+            let same_hive = device / 2 == peer / 2;
+            Ok(PeerLink {
+                can_access: true,
+                native_atomics: same_hive,
+                performance_rank: 0,
+                link_type: if same_hive {
+                    LinkType::Xgmi
+                } else {
+                    LinkType::Pcie
+                },
+                hop_count: if same_hive {
+                    1
+                } else {
+                    1 + (device as u32 ^ peer as u32)
+                },
+            })
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diagonal_and_out_of_range_have_no_link() {
+        let topology = two_hives();
+        assert_eq!(topology.device_count(), 4);
+        for device in 0..4 {
+            assert_eq!(topology.link(device, device), None);
+        }
+        assert_eq!(topology.link(0, 4), None);
+        assert_eq!(topology.link(4, 0), None);
+    }
+
+    #[rstest]
+    #[case(0, 1, LinkType::Xgmi)]
+    #[case(1, 0, LinkType::Xgmi)]
+    #[case(0, 2, LinkType::Pcie)]
+    #[case(3, 2, LinkType::Xgmi)]
+    fn test_link_is_indexed_by_device_then_peer(
+        #[case] device: usize,
+        #[case] peer: usize,
+        #[case] expected: LinkType,
+    ) {
+        assert_eq!(two_hives().link(device, peer).unwrap().link_type, expected);
+    }
+
+    #[rstest]
+    #[case(0, vec![1, 2, 3])]
+    #[case(1, vec![0, 3, 2])]
+    #[case(2, vec![3, 0, 1])]
+    fn test_peers_prefer_xgmi_then_fewer_hops(#[case] device: usize, #[case] expected: Vec<usize>) {
+        assert_eq!(two_hives().peers(device), expected);
+    }
+
+    #[test]
+    fn test_peers_skip_inaccessible_devices() {
+        let topology = Topology::from_fn(3, |_, peer| {
+            Ok(PeerLink {
+                can_access: peer != 2,
+                native_atomics: false,
+                performance_rank: 0,
+                link_type: LinkType::Pcie,
+                hop_count: 1,
+            })
+        })
+        .unwrap();
+        assert_eq!(topology.peers(0), vec![1]);
+        assert_eq!(topology.peers(2), vec![0, 1]);
+    }
+
+    #[rstest]
+    #[case(2, LinkType::Pcie)]
+    #[case(4, LinkType::Xgmi)]
+    #[case(9, LinkType::Other(9))]
+    fn test_link_type_from_hsa(#[case] value: u32, #[case] expected: LinkType) {
+        assert_eq!(LinkType::from(value), expected);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{ffi::c_void, ptr};
+
+use crate::bindings::*;
+use crate::error::{check, HipError, HipResult};
+use crate::launch::KernelArg;
+
+/// Device allocation backed by the virtual memory management API, growable in place.
+///
+/// A range of virtual addresses is reserved up front and physical chunks are mapped at its
+/// end on demand, so the device pointer never changes and growing never copies. All the sizes
+/// are rounded up to the allocation granularity of the device.
+#[derive(Debug)]
+pub struct VirtualBuffer {
+    ptr: *mut c_void,
+    device: i32,
+    handle_type: hipMemAllocationHandleType,
+    granularity: usize,
+    reserved: usize,
+    mapped: usize,
+    chunks: Vec<Chunk>,
+}
+
+/// Physical allocation mapped at `offset` in the reserved range.
+#[derive(Debug)]
+struct Chunk {
+    handle: hipMemGenericAllocationHandle_t,
+    offset: usize,
+    size: usize,
+}
+
+impl VirtualBuffer {
+    /// Reserve `max_size` bytes of virtual addresses for `device`, without any memory mapped.
+    pub fn new(device: i32, max_size: usize) -> HipResult<Self> {
+        Self::reserve(
+            device,
+            max_size,
+            hipMemAllocationHandleType_hipMemHandleTypeNone,
+        )
+    }
+
+    /// Reserve the virtual addresses of a buffer whose chunks are created with `handle_type`.
+    pub(crate) fn reserve(
+        device: i32,
+        max_size: usize,
+        handle_type: hipMemAllocationHandleType,
+    ) -> HipResult<Self> {
+        let granularity = Self::allocation_granularity(device)?;
+        let reserved = round_up(max_size, granularity)?;
+        if reserved == 0 {
+            return Err(HipError::InvalidArgument(
+                "virtual buffer size should not be zero".to_string(),
+            ));
+        }
+        let mut ptr: *mut c_void = ptr::null_mut();
+        unsafe {
+            check(hipMemAddressReserve(
+                &mut ptr,
+                reserved,
+                granularity,
+                ptr::null_mut(),
+                0,
+            ))?
+        };
+        Ok(Self {
+            ptr,
+            device,
+            handle_type,
+            granularity,
+            reserved,
+            mapped: 0,
+            chunks: Vec::new(),
+        })
+    }
+
+    /// Reserve `max_size` bytes of virtual addresses for `device` and map the first `size` bytes.
+    pub fn with_size(device: i32, size: usize, max_size: usize) -> HipResult<Self> {
+        let mut buffer = Self::new(device, max_size)?;
+        buffer.grow(size)?;
+        Ok(buffer)
+    }
+
+    /// Return the minimum allocation granularity of `device` in bytes.
+    pub fn allocation_granularity(device: i32) -> HipResult<usize> {
+        let prop = allocation_prop(device, hipMemAllocationHandleType_hipMemHandleTypeNone);
+        let mut granularity = 0;
+        unsafe {
+            check(hipMemGetAllocationGranularity(
+                &mut granularity,
+                &prop,
+                hipMemAllocationGranularity_flags_hipMemAllocationGranularityMinimum,
+            ))?
+        };
+        Ok(granularity)
+    }
+
+    /// Map physical memory until at least `size` bytes are accessible from the device.
+    ///
+    /// Does nothing when `size` bytes are already mapped. The device pointer is unchanged and
+    /// the content of the mapped memory is preserved.
+    pub fn grow(&mut self, size: usize) -> HipResult<()> {
+        if size <= self.mapped {
+            return Ok(());
+        }
+        let size = round_up(size, self.granularity)?;
+        if size > self.reserved {
+            return Err(HipError::InvalidArgument(format!(
+                "cannot grow virtual buffer to {size} bytes, only {} bytes are reserved",
+                self.reserved
+            )));
+        }
+        let chunk_size = size - self.mapped;
+        let prop = allocation_prop(self.device, self.handle_type);
+        let mut handle: hipMemGenericAllocationHandle_t = ptr::null_mut();
+        unsafe { check(hipMemCreate(&mut handle, chunk_size, &prop, 0))? };
+        self.map_chunk(handle, chunk_size)
+    }
+
+    /// Map the physical allocation `handle` of `size` bytes at the end of the mapped range,
+    /// taking ownership of the handle even on failure.
+    pub(crate) fn map_chunk(
+        &mut self,
+        handle: hipMemGenericAllocationHandle_t,
+        size: usize,
+    ) -> HipResult<()> {
+        let chunk_ptr = self.ptr_at(self.mapped);
+        let end = self
+            .mapped
+            .checked_add(size)
+            .filter(|end| *end <= self.reserved);
+        let Some(end) = end else {
+            unsafe { hipMemRelease(handle) };
+            return Err(HipError::InvalidArgument(format!(
+                "cannot map {size} more bytes, only {} bytes are reserved",
+                self.reserved
+            )));
+        };
+        if let Err(err) = unsafe { check(hipMemMap(chunk_ptr, size, 0, handle, 0)) } {
+            unsafe { hipMemRelease(handle) };
+            return Err(err);
+        }
+        let access = hipMemAccessDesc {
+            location: hipMemLocation {
+                type_: hipMemLocationType_hipMemLocationTypeDevice,
+                id: self.device,
+            },
+            flags: hipMemAccessFlags_hipMemAccessFlagsProtReadWrite,
+        };
+        if let Err(err) = unsafe { check(hipMemSetAccess(chunk_ptr, size, &access, 1)) } {
+            unsafe {
+                hipMemUnmap(chunk_ptr, size);
+                hipMemRelease(handle);
+            }
+            return Err(err);
+        }
+        self.chunks.push(Chunk {
+            handle,
+            offset: self.mapped,
+            size,
+        });
+        self.mapped = end;
+        Ok(())
+    }
+
+    /// Return the physical allocations mapped in the buffer with their size, in address order.
+    pub(crate) fn chunks(
+        &self,
+    ) -> impl Iterator<Item = (hipMemGenericAllocationHandle_t, usize)> + '_ {
+        self.chunks.iter().map(|chunk| (chunk.handle, chunk.size))
+    }
+
+    /// Return the device pointer to the start of the buffer.
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.ptr
+    }
+
+    /// Return the number of bytes currently mapped.
+    pub fn len(&self) -> usize {
+        self.mapped
+    }
+
+    /// Return true if no memory is mapped.
+    pub fn is_empty(&self) -> bool {
+        self.mapped == 0
+    }
+
+    /// Return the number of bytes reserved, the maximum size the buffer can grow to.
+    pub fn capacity(&self) -> usize {
+        self.reserved
+    }
+
+    /// Return the granularity the sizes are rounded to.
+    pub fn granularity(&self) -> usize {
+        self.granularity
+    }
+
+    fn ptr_at(&self, offset: usize) -> *mut c_void {
+        (self.ptr as *mut u8).wrapping_add(offset) as *mut c_void
+    }
+}
+
+impl Drop for VirtualBuffer {
+    fn drop(&mut self) {
+        for chunk in self.chunks.iter().rev() {
+            unsafe {
+                hipMemUnmap(self.ptr_at(chunk.offset), chunk.size);
+                hipMemRelease(chunk.handle);
+            }
+        }
+        unsafe {
+            hipMemAddressFree(self.ptr, self.reserved);
+        }
+    }
+}
+
+unsafe impl KernelArg for VirtualBuffer {
+    fn as_kernel_param(&self) -> *mut c_void {
+        &self.ptr as *const *mut c_void as *mut c_void
+    }
+}
+
+/// Return the properties of pinned device memory on `device`, exportable as `handle_type`.
+fn allocation_prop(device: i32, handle_type: hipMemAllocationHandleType) -> hipMemAllocationProp {
+    let mut prop: hipMemAllocationProp = unsafe { zeroed_raw() };
+    prop.type_ = hipMemAllocationType_hipMemAllocationTypePinned;
+    #[cfg(has_handle_type_union)]
+    {
+        prop.__bindgen_anon_1.requestedHandleType = handle_type;
+    }
+    #[cfg(not(has_handle_type_union))]
+    {
+        prop.requestedHandleType = handle_type;
+    }
+    prop.location = hipMemLocation {
+        type_: hipMemLocationType_hipMemLocationTypeDevice,
+        id: device,
+    };
+    prop
+}
+
+/// Round `size` up to a multiple of `granularity`.
+fn round_up(size: usize, granularity: usize) -> HipResult<usize> {
+    if granularity == 0 {
+        return Err(HipError::InvalidArgument(
+            "allocation granularity should not be zero".to_string(),
+        ));
+    }
+    size.checked_next_multiple_of(granularity).ok_or_else(|| {
+        HipError::InvalidArgument(format!(
+            "{size} bytes overflow when rounded to a granularity of {granularity}"
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::zero(0, 4096, Some(0))]
+    #[case::exact(8192, 4096, Some(8192))]
+    #[case::round(4097, 4096, Some(8192))]
+    #[case::small(1, 2 << 20, Some(2 << 20))]
+    #[case::overflow(usize::MAX, 4096, None)]
+    #[case::zero_granularity(1, 0, None)]
+    fn test_round_up(
+        #[case] size: usize,
+        #[case] granularity: usize,
+        #[case] expected: Option<usize>,
+    ) {
+        assert_eq!(round_up(size, granularity).ok(), expected);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_grow_keeps_data_across_chunks_end_to_end() {
+        crate::device::set_device(0).expect("Should set the GPU device");
+        let granularity = VirtualBuffer::allocation_granularity(0).expect("Should get granularity");
+        let mut buffer = VirtualBuffer::with_size(0, granularity, 4 * granularity)
+            .expect("Should create the buffer");
+        let ptr = buffer.as_ptr();
+        let first: Vec<u8> = (0..granularity).map(|i| (i % 251) as u8).collect();
+        unsafe {
+            check(hipMemcpy(
+                ptr,
+                first.as_ptr() as *const c_void,
+                first.len(),
+                hipMemcpyKind_hipMemcpyHostToDevice,
+            ))
+            .expect("Should write the first chunk");
+        }
+
+        buffer
+            .grow(3 * granularity)
+            .expect("Should grow the buffer");
+        assert_eq!(buffer.as_ptr(), ptr, "growing should not move the buffer");
+        assert_eq!(buffer.len(), 3 * granularity);
+
+        // Write a range starting in the first chunk and ending in the second one.
+        let offset = granularity / 2;
+        let spanning: Vec<u8> = (0..2 * granularity).map(|i| (i % 241) as u8).collect();
+        let mut read = vec![0u8; 3 * granularity];
+        unsafe {
+            check(hipMemcpy(
+                buffer.ptr_at(offset),
+                spanning.as_ptr() as *const c_void,
+                spanning.len(),
+                hipMemcpyKind_hipMemcpyHostToDevice,
+            ))
+            .expect("Should write across the chunks");
+            check(hipMemcpy(
+                read.as_mut_ptr() as *mut c_void,
+                ptr,
+                read.len(),
+                hipMemcpyKind_hipMemcpyDeviceToHost,
+            ))
+            .expect("Should read the whole buffer");
+        }
+        assert_eq!(&read[..offset], &first[..offset]);
+        assert_eq!(&read[offset..offset + spanning.len()], &spanning[..]);
+    }
+}
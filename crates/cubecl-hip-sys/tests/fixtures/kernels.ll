@@ -0,0 +1,72 @@
+; Source of the AMDGPU code object fixtures used by the unit tests.
+; Regenerate them with LLVM (14 or newer):
+;   llc -mtriple=amdgcn-amd-amdhsa -mcpu=gfx90a -filetype=obj kernels.ll -o kernels_gfx90a.co
+;   llc -mtriple=amdgcn-amd-amdhsa -mcpu=gfx1030 -filetype=obj kernels.ll -o kernels_gfx1030.co
+
+target datalayout = "e-p:64:64-p1:64:64-p2:32:32-p3:32:32-p4:64:64-p5:32:32-p6:32:32-i64:64-v16:16-v24:32-v32:32-v48:64-v96:128-v192:256-v256:256-v512:512-v1024:1024-v2048:2048-n32:64-S32-A5-G1-ni:7"
+target triple = "amdgcn-amd-amdhsa"
+
+@tile = internal addrspace(3) global [256 x float] undef, align 4
+
+declare i32 @llvm.amdgcn.workitem.id.x()
+declare i32 @llvm.amdgcn.workgroup.id.x()
+declare void @llvm.amdgcn.s.barrier()
+
+define amdgpu_kernel void @axpb(float %a, float addrspace(1)* %x, float addrspace(1)* %b, float addrspace(1)* %out, i32 %n) #0 {
+entry:
+  %tid = call i32 @llvm.amdgcn.workitem.id.x()
+  %bid = call i32 @llvm.amdgcn.workgroup.id.x()
+  %base = mul i32 %bid, 64
+  %gid = add i32 %base, %tid
+  %inb = icmp slt i32 %gid, %n
+  br i1 %inb, label %body, label %exit
+body:
+  %idx = sext i32 %gid to i64
+  %px = getelementptr float, float addrspace(1)* %x, i64 %idx
+  %pb = getelementptr float, float addrspace(1)* %b, i64 %idx
+  %po = getelementptr float, float addrspace(1)* %out, i64 %idx
+  %vx = load float, float addrspace(1)* %px
+  %vb = load float, float addrspace(1)* %pb
+  %m = fmul float %vx, %a
+  %r = fadd float %m, %vb
+  store float %r, float addrspace(1)* %po
+  br label %exit
+exit:
+  ret void
+}
+
+define amdgpu_kernel void @reduce_tile(float addrspace(1)* %input, float addrspace(1)* %output, i64 %len, <2 x i32> %stride) #0 {
+entry:
+  %tid = call i32 @llvm.amdgcn.workitem.id.x()
+  %idx = zext i32 %tid to i64
+  %p = getelementptr float, float addrspace(1)* %input, i64 %idx
+  %v = load float, float addrspace(1)* %p
+  %lp = getelementptr [256 x float], [256 x float] addrspace(3)* @tile, i32 0, i32 %tid
+  store float %v, float addrspace(3)* %lp
+  call void @llvm.amdgcn.s.barrier()
+  %inv = xor i32 %tid, 1
+  %lq = getelementptr [256 x float], [256 x float] addrspace(3)* @tile, i32 0, i32 %inv
+  %w = load float, float addrspace(3)* %lq
+  %s = fadd float %v, %w
+  %po = getelementptr float, float addrspace(1)* %output, i64 %idx
+  store float %s, float addrspace(1)* %po
+  ret void
+}
+
+attributes #0 = { "amdgpu-flat-work-group-size"="1,256" }
+
+define amdgpu_kernel void @scratch_walk(i32 addrspace(1)* %out, i32 %k) #0 {
+entry:
+  %buf = alloca [64 x i32], align 4, addrspace(5)
+  %tid = call i32 @llvm.amdgcn.workitem.id.x()
+  %slot = and i32 %tid, 63
+  %p = getelementptr [64 x i32], [64 x i32] addrspace(5)* %buf, i32 0, i32 %slot
+  store volatile i32 %tid, i32 addrspace(5)* %p
+  %sel = and i32 %k, 63
+  %q = getelementptr [64 x i32], [64 x i32] addrspace(5)* %buf, i32 0, i32 %sel
+  %v = load volatile i32, i32 addrspace(5)* %q
+  %idx = zext i32 %tid to i64
+  %po = getelementptr i32, i32 addrspace(1)* %out, i64 %idx
+  store i32 %v, i32 addrspace(1)* %po
+  ret void
+}